@@ -1,2 +1,373 @@
-pub mod borrowed;
-pub mod owned;
\ No newline at end of file
+use crate::error::{Error, Result};
+use crate::sm::error::Error as SmError;
+use crate::sm::CipherSuite;
+use super::error::Error as ApduError;
+use iso7816_tlv::ber as tlv;
+use std::borrow::Cow;
+use subtle::ConstantTimeEq;
+
+/// Maximum command data length of a short command APDU
+pub const MAX_SHORT_LC: usize = 255;
+/// Maximum command data length of an extended command APDU
+pub const MAX_EXTENDED_LC: usize = 65535;
+/// Maximum expected response data length of a short command APDU
+pub const MAX_SHORT_LE: usize = 256;
+/// Maximum expected response data length of an extended command APDU
+pub const MAX_EXTENDED_LE: usize = 65536;
+
+/// A command APDU, which either borrows or owns its command data.
+///
+/// `rx_len` is the expected response data length Ne, where 0 means no
+/// response data is expected (absent Le field).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApduCommand<'a> {
+	pub cla: u8,
+	pub ins: u8,
+	pub p1: u8,
+	pub p2: u8,
+	pub data: Cow<'a, [u8]>,
+	pub rx_len: usize,
+}
+
+/// Former borrowed command APDU, now [`ApduCommand`] with borrowed data.
+#[deprecated(note = "use apdu::command::ApduCommand")]
+pub mod borrowed {
+	pub use super::ApduCommand;
+}
+
+/// Former owned command APDU, now [`ApduCommand`] with owned data.
+#[deprecated(note = "use apdu::command::ApduCommand")]
+pub mod owned {
+	pub type ApduCommand = super::ApduCommand<'static>;
+}
+
+/// Command APDU cases, as per ISO/IEC 7816-4 section 5.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+	/// No command data, no response data
+	Case1,
+	/// No command data, short Le
+	Case2Short,
+	/// No command data, extended Le
+	Case2Extended,
+	/// Short Lc and command data, no response data
+	Case3Short,
+	/// Extended Lc and command data, no response data
+	Case3Extended,
+	/// Short Lc, command data and short Le
+	Case4Short,
+	/// Extended Lc, command data and extended Le
+	Case4Extended,
+}
+
+impl Case {
+	/// Detects the case of a raw command APDU, validating the Lc and Le fields
+	/// against the length of the command body.
+	pub fn detect(raw: &[u8]) -> std::result::Result<Self, ApduError> {
+		let body = raw.get(4..).ok_or(ApduError::TooShort)?;
+		match body {
+			[] => Ok(Self::Case1),
+			[_] => Ok(Self::Case2Short),
+			[0x00, _, _] => Ok(Self::Case2Extended),
+			[0x00, lc1, lc2, ..] => {
+				let lc = u16::from_be_bytes([*lc1, *lc2]) as usize;
+				if lc == 0 {
+					return Err(ApduError::InvalidLc);
+				}
+				if body.len() == 3 + lc {
+					Ok(Self::Case3Extended)
+				} else if body.len() == 5 + lc {
+					Ok(Self::Case4Extended)
+				} else {
+					Err(ApduError::InvalidLength { lc, body_len: body.len() })
+				}
+			}
+			[0x00, _] => Err(ApduError::InvalidLc),
+			[lc, ..] => {
+				let lc = *lc as usize;
+				if body.len() == 1 + lc {
+					Ok(Self::Case3Short)
+				} else if body.len() == 2 + lc {
+					Ok(Self::Case4Short)
+				} else {
+					Err(ApduError::InvalidLength { lc, body_len: body.len() })
+				}
+			}
+		}
+	}
+
+	pub fn is_extended(&self) -> bool {
+		matches!(self, Self::Case2Extended | Self::Case3Extended | Self::Case4Extended)
+	}
+}
+
+impl<'a> ApduCommand<'a> {
+	/// Creates a case 1 command APDU, without command data or expected response data.
+	pub const fn new(cla: u8, ins: u8, p1: u8, p2: u8) -> Self {
+		Self { cla, ins, p1, p2, data: Cow::Borrowed(&[]), rx_len: 0 }
+	}
+
+	pub fn with_data(mut self, data: impl Into<Cow<'a, [u8]>>) -> Self {
+		self.data = data.into();
+		self
+	}
+
+	pub const fn with_rx_len(mut self, rx_len: usize) -> Self {
+		self.rx_len = rx_len;
+		self
+	}
+
+	pub const fn with_cla(mut self, cla: u8) -> Self {
+		self.cla = cla;
+		self
+	}
+
+	/// Converts the command into one that owns its command data.
+	pub fn into_owned(self) -> ApduCommand<'static> {
+		ApduCommand { cla: self.cla, ins: self.ins, p1: self.p1, p2: self.p2, data: Cow::Owned(self.data.into_owned()), rx_len: self.rx_len }
+	}
+
+	/// Borrows the command data of the command.
+	pub fn as_borrowed(&self) -> ApduCommand<'_> {
+		ApduCommand { cla: self.cla, ins: self.ins, p1: self.p1, p2: self.p2, data: Cow::Borrowed(&self.data), rx_len: self.rx_len }
+	}
+
+	/// Returns the case of the command APDU, which is extended when either the
+	/// command data or the expected response data does not fit a short APDU.
+	pub fn case(&self) -> std::result::Result<Case, ApduError> {
+		if self.data.len() > MAX_EXTENDED_LC {
+			return Err(ApduError::DataTooLong(self.data.len()));
+		}
+		if self.rx_len > MAX_EXTENDED_LE {
+			return Err(ApduError::RxLenTooLong(self.rx_len));
+		}
+		let extended = self.data.len() > MAX_SHORT_LC || self.rx_len > MAX_SHORT_LE;
+		Ok(match (self.data.is_empty(), self.rx_len == 0, extended) {
+			(true, true, _) => Case::Case1,
+			(true, false, false) => Case::Case2Short,
+			(true, false, true) => Case::Case2Extended,
+			(false, true, false) => Case::Case3Short,
+			(false, true, true) => Case::Case3Extended,
+			(false, false, false) => Case::Case4Short,
+			(false, false, true) => Case::Case4Extended,
+		})
+	}
+
+	/// Encodes the command APDU, as per ISO/IEC 7816-4 section 5.1
+	pub fn to_vec(&self) -> std::result::Result<Vec<u8>, ApduError> {
+		let case = self.case()?;
+		let mut buf = Vec::with_capacity(4 + 3 + self.data.len() + 3);
+		buf.extend_from_slice(&[self.cla, self.ins, self.p1, self.p2]);
+
+		// Lc field
+		match case {
+			Case::Case3Short | Case::Case4Short => buf.push(self.data.len() as u8),
+			Case::Case3Extended | Case::Case4Extended => {
+				buf.push(0x00);
+				buf.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+			}
+			_ => {}
+		}
+
+		buf.extend_from_slice(&self.data);
+
+		// Le field, where the maximum length is encoded as zero
+		match case {
+			Case::Case2Short | Case::Case4Short => buf.push((self.rx_len % 256) as u8),
+			Case::Case2Extended => {
+				buf.push(0x00);
+				buf.extend_from_slice(&((self.rx_len % 65536) as u16).to_be_bytes());
+			}
+			Case::Case4Extended => buf.extend_from_slice(&((self.rx_len % 65536) as u16).to_be_bytes()),
+			_ => {}
+		}
+
+		Ok(buf)
+	}
+
+	pub fn to_protected(&self, cipher: CipherSuite, ks_enc: &[u8], ks_mac: &[u8], ssc: &mut u64) -> Result<ApduCommand<'static>> {
+		let case = self.case()?;
+
+		// Increment SSC with 1, which is used for both the IV and the MAC of this command
+		*ssc = ssc.wrapping_add(1);
+
+		// a) Mask class byte and pad command header, keeping the command chaining bit
+		let cla = self.cla | 0x0C;
+		let cmd_header = cipher.pad(&[cla, self.ins, self.p1, self.p2]);
+
+		// Build [DO'97'], with one byte Le for short and two bytes for extended length
+		let tlv_le = if self.rx_len > 0 {
+			let le = if case.is_extended() {
+				((self.rx_len % 65536) as u16).to_be_bytes().to_vec()
+			} else {
+				vec![(self.rx_len % 256) as u8]
+			};
+			tlv::Tlv::new(tlv::Tag::try_from(0x97)?, tlv::Value::Primitive(le))?.to_vec()
+		} else {
+			Vec::new()
+		};
+
+		let tlv_data = if !self.data.is_empty() {
+			// b) Pad data
+			// c) Encrypt data with KSEnc
+			let data = cipher.encrypt(&self.data, ks_enc, *ssc)?;
+
+			// d) Build [DO'85' or DO'87']
+			// In case INS is even, [DO'87'] SHALL be used, and in case INS is odd, [DO'85'] SHALL be used
+			let (tlv_tag, data) = if self.ins.is_multiple_of(2) {
+				let mut padding_indicator_and_data = Vec::with_capacity(data.len() + 1);
+				padding_indicator_and_data.extend_from_slice(&[0x01]);
+				padding_indicator_and_data.extend_from_slice(&data);
+				(tlv::Tag::try_from(0x87)?, padding_indicator_and_data)
+			} else {
+				(tlv::Tag::try_from(0x85)?, data)
+			};
+
+			tlv::Tlv::new(tlv_tag, tlv::Value::Primitive(data))?.to_vec()
+		} else {
+			Vec::new()
+		};
+
+		// e) Concatenate CmdHeader [DO'85' or DO'87'] [DO'97']
+		let mut m = Vec::with_capacity(cmd_header.len() + tlv_data.len() + tlv_le.len());
+		m.extend_from_slice(&cmd_header);
+		m.extend_from_slice(&tlv_data);
+		m.extend_from_slice(&tlv_le);
+
+		// f) Compute MAC of M
+
+		// i) Concatenate SSC and M
+		let ssc_bytes = cipher.ssc_bytes(*ssc);
+		let mut n = Vec::with_capacity(ssc_bytes.len() + m.len());
+		n.extend_from_slice(&ssc_bytes);
+		n.extend_from_slice(&m);
+
+		// ii) Compute MAC over N with KSMAC
+		let cc = cipher.mac(&n, ks_mac)?;
+
+		// g) Build [DO'8E']
+		let tlv_mac = tlv::Tlv::new(tlv::Tag::try_from(0x8E)?, tlv::Value::Primitive(cc))?.to_vec();
+
+		// Build protected APDU data: [DO'85' or DO'87'] [DO'97'] [DO'8E']
+		let mut protected_apdu_data = Vec::with_capacity(tlv_data.len() + tlv_le.len() + tlv_mac.len());
+		protected_apdu_data.extend_from_slice(&tlv_data);
+		protected_apdu_data.extend_from_slice(&tlv_le);
+		protected_apdu_data.extend_from_slice(&tlv_mac);
+
+		// Build protected APDU: CmdHeader Lc' [DO'85' or DO'87'] [DO'97'] [DO'8E'] ['00' or '00 00']
+		// - rx_len = 256   ['00']    for standard length
+		// - rx_len = 65536 ['00 00'] for extended length
+		let rx_len = if case.is_extended() || protected_apdu_data.len() > MAX_SHORT_LC {
+			MAX_EXTENDED_LE
+		} else {
+			MAX_SHORT_LE
+		};
+		Ok(ApduCommand::new(cla, self.ins, self.p1, self.p2).with_data(protected_apdu_data).with_rx_len(rx_len))
+	}
+
+	/// Card side of [`Self::to_protected`]: verifies and decrypts a protected
+	/// command APDU with [DO'85' or DO'87'] [DO'97'] [DO'8E'].
+	///
+	/// The SSC is incremented before the MAC is verified, so after a
+	/// successful call it is the value the response must be protected with.
+	pub fn from_protected(command: &ApduCommand, cipher: CipherSuite, ks_enc: &[u8], ks_mac: &[u8], ssc: &mut u64) -> Result<ApduCommand<'static>> {
+		if command.cla & 0x0C != 0x0C {
+			return Err(SmError::NotProtected.into());
+		}
+
+		// extract TLV parts from command
+		let tlv_parts = tlv::Tlv::parse_all(&command.data);
+		let tlv_data = tlv_parts.iter().find(|part| [0x85_u64, 0x87_u64].contains(&Into::<u64>::into(part.tag().clone())));
+		let tlv_le = tlv_parts.iter().find(|part| Into::<u64>::into(part.tag().clone()) == 0x97);
+		let tlv_mac = tlv_parts.iter().find(|part| Into::<u64>::into(part.tag().clone()) == 0x8E);
+
+		let mac_value = if let Some(tlv::Value::Primitive(mac_value)) = tlv_mac.map(|t| t.value()) {
+			mac_value
+		} else {
+			return Err(SmError::MissingDataObject(0x8E).into());
+		};
+
+		// Verify CC by computing MAC of CmdHeader [DO'85' or DO'87'] [DO'97']
+		let cmd_header = cipher.pad(&[command.cla, command.ins, command.p1, command.p2]);
+		let tlv_data_bytes = tlv_data.map(|t| t.to_vec()).unwrap_or_default();
+		let tlv_le_bytes = tlv_le.map(|t| t.to_vec()).unwrap_or_default();
+
+		// i) Increment SSC with 1
+		*ssc = ssc.wrapping_add(1);
+
+		// ii) Concatenate SSC CmdHeader [DO'85' or DO'87'] [DO'97']
+		let ssc_bytes = cipher.ssc_bytes(*ssc);
+		let mut n = Vec::with_capacity(ssc_bytes.len() + cmd_header.len() + tlv_data_bytes.len() + tlv_le_bytes.len());
+		n.extend_from_slice(&ssc_bytes);
+		n.extend_from_slice(&cmd_header);
+		n.extend_from_slice(&tlv_data_bytes);
+		n.extend_from_slice(&tlv_le_bytes);
+
+		// iii) Compute MAC with KSMAC and compare with data of [DO'8E']
+		let cc = cipher.mac(&n, ks_mac)?;
+		if cc.ct_ne(mac_value).into() {
+			return Err(Error::MacMismatch);
+		}
+
+		// Decrypt data of [DO'85' or DO'87']
+		let data = match (tlv_data.map(|t| t.tag()), tlv_data.map(|t| t.value())) {
+			(Some(tag), Some(tlv::Value::Primitive(data))) if Into::<u64>::into(tag.clone()) == 0x87 => {
+				if data.first() != Some(&0x01) {
+					return Err(SmError::InvalidPaddingIndicator.into());
+				}
+				cipher.decrypt(&data[1..], ks_enc, *ssc)?
+			}
+			(Some(_), Some(tlv::Value::Primitive(data))) => cipher.decrypt(data, ks_enc, *ssc)?,
+			_ => Vec::new(),
+		};
+
+		// Decode Le from [DO'97']
+		let rx_len = match tlv_le.map(|t| t.value()) {
+			Some(tlv::Value::Primitive(le)) => match le.as_slice() {
+				[] => 0,
+				[0x00] => 256,
+				[le] => *le as usize,
+				[0x00, 0x00] => 65536,
+				[le1, le2] => u16::from_be_bytes([*le1, *le2]) as usize,
+				_ => return Err(SmError::InvalidDataObject(0x97).into()),
+			},
+			_ => 0,
+		};
+
+		Ok(ApduCommand::new(command.cla & !0x0C, command.ins, command.p1, command.p2).with_data(data).with_rx_len(rx_len))
+	}
+}
+
+impl<'a> TryFrom<&'a [u8]> for ApduCommand<'a> {
+	type Error = ApduError;
+
+	/// Parses a raw command APDU of any case, as per ISO/IEC 7816-4 section 5.1
+	fn try_from(raw: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+		let case = Case::detect(raw)?;
+		let (cla, ins, p1, p2) = (raw[0], raw[1], raw[2], raw[3]);
+		let body = &raw[4..];
+		let short_le = |le: u8| if le == 0 { 256 } else { le as usize };
+		let extended_le = |le1: u8, le2: u8| match u16::from_be_bytes([le1, le2]) {
+			0 => 65536,
+			le => le as usize,
+		};
+		let (data, rx_len) = match case {
+			Case::Case1 => (&body[0..0], 0),
+			Case::Case2Short => (&body[0..0], short_le(body[0])),
+			Case::Case2Extended => (&body[0..0], extended_le(body[1], body[2])),
+			Case::Case3Short => (&body[1..], 0),
+			Case::Case3Extended => (&body[3..], 0),
+			Case::Case4Short => (&body[1..body.len()-1], short_le(body[body.len()-1])),
+			Case::Case4Extended => (&body[3..body.len()-2], extended_le(body[body.len()-2], body[body.len()-1])),
+		};
+		Ok(Self { cla, ins, p1, p2, data: Cow::Borrowed(data), rx_len })
+	}
+}
+
+impl<'a> TryFrom<&ApduCommand<'a>> for Vec<u8> {
+	type Error = ApduError;
+
+	fn try_from(apdu: &ApduCommand<'a>) -> std::result::Result<Self, Self::Error> {
+		apdu.to_vec()
+	}
+}
\ No newline at end of file
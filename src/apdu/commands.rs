@@ -0,0 +1,194 @@
+use super::command::{ApduCommand, MAX_SHORT_LE};
+use super::error::Error as ApduError;
+use std::borrow::Cow;
+
+/// Application identifier of the eMRTD LDS1 application
+pub const AID_EMRTD: &[u8] = &[0xA0, 0x00, 0x00, 0x02, 0x47, 0x10, 0x01];
+
+/// Largest offset that can be encoded in P1-P2 of READ BINARY and UPDATE
+/// BINARY with even INS, since bit 8 of P1 indicates a short EF identifier
+pub const MAX_EVEN_INS_OFFSET: usize = 0x7FFF;
+
+/// MSE:Set AT P1 for PACE
+pub const MSE_SET_AT_PACE: u8 = 0xC1;
+/// MSE:Set AT P1 for Chip Authentication
+pub const MSE_SET_AT_CA: u8 = 0x41;
+/// MSE:Set AT P1 for Terminal Authentication
+pub const MSE_SET_AT_TA: u8 = 0x81;
+
+/// SELECT of an application by its AID, without response data.
+pub const fn select_aid(aid: &[u8]) -> ApduCommand<'_> {
+	ApduCommand { cla: 0x00, ins: 0xA4, p1: 0x04, p2: 0x0C, data: Cow::Borrowed(aid), rx_len: 0 }
+}
+
+/// SELECT of an EF under the current DF by its file identifier, without response data.
+pub fn select_fid<'a>(fileid: u16) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0xA4, 0x02, 0x0C).with_data(fileid.to_be_bytes().to_vec())
+}
+
+/// SELECT by path from the MF, without the identifier of the MF.
+pub fn select_path<'a>(path: impl Into<Cow<'a, [u8]>>) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0xA4, 0x08, 0x0C).with_data(path)
+}
+
+/// SELECT of the parent DF of the current DF.
+pub const fn select_parent<'a>() -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0xA4, 0x03, 0x0C)
+}
+
+/// GET CHALLENGE of a nonce of `len` bytes, which is 8 for BAC and Terminal Authentication.
+pub const fn get_challenge<'a>(len: usize) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0x84, 0x00, 0x00).with_rx_len(len)
+}
+
+/// EXTERNAL AUTHENTICATE with mutual authenticate function, as used by BAC.
+/// The IC responds with its 40 byte cryptogram and checksum.
+pub fn mutual_authenticate<'a>(data: impl Into<Cow<'a, [u8]>>) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0x82, 0x00, 0x00).with_data(data).with_rx_len(40)
+}
+
+/// EXTERNAL AUTHENTICATE without response data, as used by Terminal Authentication.
+pub fn external_authenticate<'a>(signature: impl Into<Cow<'a, [u8]>>) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0x82, 0x00, 0x00).with_data(signature)
+}
+
+/// INTERNAL AUTHENTICATE, as used by Active Authentication. The length of the
+/// signature depends on the key of the IC, so the maximum short Le is expected.
+pub fn internal_authenticate<'a>(challenge: impl Into<Cow<'a, [u8]>>) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0x88, 0x00, 0x00).with_data(challenge).with_rx_len(MAX_SHORT_LE)
+}
+
+/// MSE:Set AT with the given P1 and concatenated data objects.
+pub fn mse_set_at<'a>(p1: u8, data: impl Into<Cow<'a, [u8]>>) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0x22, p1, 0xA4).with_data(data)
+}
+
+/// MSE:Set AT for PACE, with the protocol OID, the password reference
+/// (1 = MRZ, 2 = CAN, 3 = PIN, 4 = PUK) and the standardized domain parameters, if any.
+pub fn mse_set_at_pace(oid: &[u8], password: u8, parameter_id: Option<u8>) -> Result<ApduCommand<'static>, ApduError> {
+	let mut data = data_object(0x80, oid)?;
+	data.extend_from_slice(&data_object(0x83, &[password])?);
+	if let Some(parameter_id) = parameter_id {
+		data.extend_from_slice(&data_object(0x84, &[parameter_id])?);
+	}
+	Ok(mse_set_at(MSE_SET_AT_PACE, data))
+}
+
+/// MSE:Set AT for Chip Authentication version 2, with the protocol OID and
+/// the key identifier, if the IC has more than one key.
+pub fn mse_set_at_ca(oid: &[u8], key_id: Option<&[u8]>) -> Result<ApduCommand<'static>, ApduError> {
+	let mut data = data_object(0x80, oid)?;
+	if let Some(key_id) = key_id {
+		data.extend_from_slice(&data_object(0x84, key_id)?);
+	}
+	Ok(mse_set_at(MSE_SET_AT_CA, data))
+}
+
+/// MSE:Set KAT for Chip Authentication version 1 with 3DES, with the
+/// ephemeral public key of the terminal and the key identifier, if any.
+pub fn mse_set_kat(public_key: &[u8], key_id: Option<&[u8]>) -> Result<ApduCommand<'static>, ApduError> {
+	let mut data = data_object(0x91, public_key)?;
+	if let Some(key_id) = key_id {
+		data.extend_from_slice(&data_object(0x84, key_id)?);
+	}
+	Ok(ApduCommand::new(0x00, 0x22, 0x41, 0xA6).with_data(data))
+}
+
+/// MSE:Set DST for Terminal Authentication, selecting the public key with
+/// the given reference (the CAR of the next certificate) to verify certificates with.
+pub fn mse_set_dst(key_reference: &[u8]) -> Result<ApduCommand<'static>, ApduError> {
+	Ok(ApduCommand::new(0x00, 0x22, 0x81, 0xB6).with_data(data_object(0x83, key_reference)?))
+}
+
+/// GENERAL AUTHENTICATE with the given data objects in the dynamic
+/// authentication data template DO'7C'.
+///
+/// All but the last command of a protocol such as PACE are sent with the
+/// command chaining bit set in CLA.
+pub fn general_authenticate(data_objects: &[(u8, &[u8])], last: bool) -> Result<ApduCommand<'static>, ApduError> {
+	let mut template = Vec::new();
+	for (tag, value) in data_objects {
+		template.extend_from_slice(&data_object(*tag, value)?);
+	}
+	let data = data_object(0x7C, &template)?;
+
+	// The response data is a dynamic authentication data template as well,
+	// where longer responses such as DH public keys are retrieved with GET RESPONSE
+	let cla = if last { 0x00 } else { 0x10 };
+	Ok(ApduCommand::new(cla, 0x86, 0x00, 0x00).with_data(data).with_rx_len(MAX_SHORT_LE))
+}
+
+/// PSO:Verify Certificate with the body DO'7F4E' and signature DO'5F37' of a CV certificate.
+pub fn pso_verify_certificate<'a>(certificate: impl Into<Cow<'a, [u8]>>) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0x2A, 0x00, 0xBE).with_data(certificate)
+}
+
+/// READ BINARY of the currently selected EF, which uses odd INS B1 for
+/// offsets beyond [`MAX_EVEN_INS_OFFSET`].
+pub fn read_binary<'a>(rx_len: usize, offset: usize) -> ApduCommand<'a> {
+	if offset > MAX_EVEN_INS_OFFSET {
+		return read_binary_odd(rx_len, offset);
+	}
+	let offset = (offset as u16).to_be_bytes();
+	ApduCommand::new(0x00, 0xB0, offset[0], offset[1]).with_rx_len(rx_len)
+}
+
+/// READ BINARY of the EF with the given short EF identifier, which also
+/// makes it the currently selected EF. The offset is limited to 255 bytes.
+pub fn read_binary_sfi<'a>(rx_len: usize, sfi: u8, offset: u8) -> ApduCommand<'a> {
+	ApduCommand::new(0x00, 0xB0, 0x80 | (sfi & 0x1F), offset).with_rx_len(rx_len)
+}
+
+/// READ BINARY with odd INS B1 of the currently selected EF, as per ISO/IEC 7816-4
+///
+/// The offset is encoded in DO'54' and the IC returns the data in DO'53',
+/// so `rx_len` is extended to fit the tag and length of DO'53'.
+pub fn read_binary_odd<'a>(rx_len: usize, offset: usize) -> ApduCommand<'a> {
+	let do_len = match rx_len {
+		0..=0x7F => 2,
+		0x80..=0xFF => 3,
+		_ => 4,
+	};
+	let offset = offset_bytes(offset);
+	let mut data = vec![0x54, offset.len() as u8];
+	data.extend_from_slice(&offset);
+	ApduCommand::new(0x00, 0xB1, 0x00, 0x00).with_data(data).with_rx_len(rx_len + do_len)
+}
+
+/// UPDATE BINARY of the currently selected EF, which uses odd INS D7 with
+/// the offset in DO'54' and the data in DO'53' for offsets beyond [`MAX_EVEN_INS_OFFSET`].
+pub fn update_binary<'a>(offset: usize, data: impl Into<Cow<'a, [u8]>>) -> Result<ApduCommand<'a>, ApduError> {
+	let data = data.into();
+	if offset > MAX_EVEN_INS_OFFSET {
+		let mut odd_data = data_object(0x54, &offset_bytes(offset))?;
+		odd_data.extend_from_slice(&data_object(0x53, &data)?);
+		return Ok(ApduCommand::new(0x00, 0xD7, 0x00, 0x00).with_data(odd_data));
+	}
+	let offset = (offset as u16).to_be_bytes();
+	Ok(ApduCommand::new(0x00, 0xD6, offset[0], offset[1]).with_data(data))
+}
+
+/// Encodes the offset for DO'54' with the fewest bytes possible.
+fn offset_bytes(offset: usize) -> Vec<u8> {
+	let offset = offset.to_be_bytes();
+	let start = offset.iter().position(|b| *b != 0x00).unwrap_or(offset.len() - 1);
+	offset[start..].to_vec()
+}
+
+/// Encodes a BER-TLV data object with a single byte tag. Values longer than
+/// 65535 bytes do not fit in the data of a command APDU.
+fn data_object(tag: u8, value: &[u8]) -> Result<Vec<u8>, ApduError> {
+	let mut buf = Vec::with_capacity(value.len() + 4);
+	buf.push(tag);
+	match value.len() {
+		len @ 0..=0x7F => buf.push(len as u8),
+		len @ 0x80..=0xFF => buf.extend_from_slice(&[0x81, len as u8]),
+		len @ 0x100..=0xFFFF => {
+			buf.push(0x82);
+			buf.extend_from_slice(&(len as u16).to_be_bytes());
+		}
+		len => return Err(ApduError::DataTooLong(len)),
+	}
+	buf.extend_from_slice(value);
+	Ok(buf)
+}
\ No newline at end of file
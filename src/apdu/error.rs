@@ -0,0 +1,22 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	TooShort,
+	InvalidLc,
+	InvalidLength { lc: usize, body_len: usize },
+	DataTooLong(usize),
+	RxLenTooLong(usize),
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TooShort => write!(f, "Command APDU is shorter than the 4 byte header"),
+			Self::InvalidLc => write!(f, "Invalid Lc field"),
+			Self::InvalidLength { lc, body_len } => write!(f, "Command APDU body length {} does not match Lc {}", body_len, lc),
+			Self::DataTooLong(len) => write!(f, "Command data length {} exceeds the maximum of 65535", len),
+			Self::RxLenTooLong(len) => write!(f, "Expected response data length {} exceeds the maximum of 65536", len),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
\ No newline at end of file
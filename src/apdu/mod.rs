@@ -1,2 +1,4 @@
 pub mod command;
+pub mod commands;
+pub mod error;
 pub mod response;
\ No newline at end of file
@@ -1 +1,2 @@
-pub mod owned;
\ No newline at end of file
+pub mod owned;
+pub mod status;
\ No newline at end of file
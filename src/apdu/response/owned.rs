@@ -1,8 +1,9 @@
-use crate::error::BoxResult;
-use crate::crypto::des::mac;
-use crate::crypto::tdes::decrypt_unpad;
+use crate::error::{Error, Result};
+use crate::sm::error::Error as SmError;
+use crate::sm::CipherSuite;
 use hex_fmt::HexFmt;
 use iso7816_tlv::ber as tlv;
+use subtle::ConstantTimeEq;
 
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -18,10 +19,17 @@ pub struct ApduResponse {
 }
 
 impl ApduResponse {
-	pub fn from_protected(input: Vec<u8>, ks_mac: &[u8], ks_enc: &[u8], ssc: &mut u64) -> BoxResult<Self> {
-		let res = ApduResponse::from(input);
-		if res.trailer != TRAILER_OK {
-			return Err(format!("SM APDU failed: {}", res.trailer).into());
+	pub fn to_vec(&self) -> Vec<u8> {
+		let mut buf = Vec::with_capacity(self.data.len() + 2);
+		buf.extend_from_slice(&self.data);
+		buf.extend_from_slice(&[self.trailer.sw1, self.trailer.sw2]);
+		buf
+	}
+
+	pub fn from_protected(res: ApduResponse, cipher: CipherSuite, ks_enc: &[u8], ks_mac: &[u8], ssc: &mut u64) -> Result<Self> {
+		// Errors detected by the IC in secure messaging are sent without data objects
+		if res.data.is_empty() {
+			return Err(SmError::UnprotectedResponse(res.trailer).into());
 		}
 
 		// extract TLV parts from response
@@ -41,33 +49,36 @@ impl ApduResponse {
 			// j) Verify RAPDU CC by computing MAC of [DO'99']
 
 			// i) Increment SSC with 1
-			*ssc += 1;
+			*ssc = ssc.wrapping_add(1);
 
 			// ii) Concatenate SSC [DO'85' or DO'87'] [DO'99']
-			let ssc_bytes = ssc.to_be_bytes();
+			let ssc_bytes = cipher.ssc_bytes(*ssc);
 			let mut k = Vec::with_capacity(ssc_bytes.len() + tlv_data_bytes.len() + tlv_status_bytes.len());
 			k.extend_from_slice(&ssc_bytes);
 			k.extend_from_slice(&tlv_data_bytes);
 			k.extend_from_slice(&tlv_status_bytes);
 
 			// iii) Compute MAC with KSMAC
-			let cc = mac(&k, ks_mac)?;
+			let cc = cipher.mac(&k, ks_mac)?;
 
 			// v) Compare CC' with data of [DO'8E'] of RAPDU
-			if &cc != mac_value {
-				return Err(format!("Invalid MAC {}, expected {}", HexFmt(mac_value), HexFmt(cc)).into());
+			if cc.ct_ne(mac_value).into() {
+				return Err(Error::MacMismatch);
 			}
 
 			status_value
 		} else {
-			return Err("Invalid TLV".into());
+			return Err(SmError::MissingDataObject(if tlv_status.is_none() { 0x99 } else { 0x8E }).into());
 		};
 
 		let res_apdu = if let (Some(tag), Some(tlv::Value::Primitive(data))) = (tlv_data.map(|t| t.tag()), tlv_data.map(|t| t.value())) {
 			let data = if Into::<u64>::into(tag.clone()) == 0x87 {
-				decrypt_unpad(&data[1..], ks_enc)?
+				if data.first() != Some(&0x01) {
+					return Err(SmError::InvalidPaddingIndicator.into());
+				}
+				cipher.decrypt(&data[1..], ks_enc, *ssc)?
 			} else {
-				decrypt_unpad(&data, ks_enc)?
+				cipher.decrypt(data, ks_enc, *ssc)?
 			};
 			let mut res_apdu = Vec::with_capacity(data.len() + status.len());
 			res_apdu.extend_from_slice(&data);
@@ -79,6 +90,55 @@ impl ApduResponse {
 
 		Ok(ApduResponse::from(res_apdu))
 	}
+
+	/// Card side of [`Self::from_protected`]: builds a protected response APDU
+	/// with [DO'85' or DO'87'] [DO'99'] [DO'8E'], for the command with the given INS.
+	///
+	/// The SSC is incremented once more after the command it responds to was
+	/// verified with [`crate::apdu::command::ApduCommand::from_protected`].
+	pub fn to_protected(&self, ins: u8, cipher: CipherSuite, ks_enc: &[u8], ks_mac: &[u8], ssc: &mut u64) -> Result<Self> {
+		// i) Increment SSC with 1, which is used for both the IV and the MAC of this response
+		*ssc = ssc.wrapping_add(1);
+
+		// Build [DO'85'] or [DO'87']
+		let tlv_data = if !self.data.is_empty() {
+			let data = cipher.encrypt(&self.data, ks_enc, *ssc)?;
+			// In case INS is even, [DO'87'] SHALL be used, and in case INS is odd, [DO'85'] SHALL be used
+			let (tag, data) = if ins & 0x01 == 0 {
+				let mut padding_indicator_and_data = Vec::with_capacity(data.len() + 1);
+				padding_indicator_and_data.extend_from_slice(&[0x01]);
+				padding_indicator_and_data.extend_from_slice(&data);
+				(tlv::Tag::try_from(0x87)?, padding_indicator_and_data)
+			} else {
+				(tlv::Tag::try_from(0x85)?, data)
+			};
+			tlv::Tlv::new(tag, tlv::Value::Primitive(data))?.to_vec()
+		} else {
+			Vec::new()
+		};
+
+		// Build [DO'99']
+		let tlv_status = tlv::Tlv::new(tlv::Tag::try_from(0x99)?, tlv::Value::Primitive(vec![self.trailer.sw1, self.trailer.sw2]))?.to_vec();
+
+		// ii) Concatenate SSC [DO'85' or DO'87'] [DO'99']
+		let ssc_bytes = cipher.ssc_bytes(*ssc);
+		let mut k = Vec::with_capacity(ssc_bytes.len() + tlv_data.len() + tlv_status.len());
+		k.extend_from_slice(&ssc_bytes);
+		k.extend_from_slice(&tlv_data);
+		k.extend_from_slice(&tlv_status);
+
+		// iii) Compute MAC with KSMAC and build [DO'8E']
+		let cc = cipher.mac(&k, ks_mac)?;
+		let tlv_mac = tlv::Tlv::new(tlv::Tag::try_from(0x8E)?, tlv::Value::Primitive(cc))?.to_vec();
+
+		// Build protected response data: [DO'85' or DO'87'] [DO'99'] [DO'8E']
+		let mut data = Vec::with_capacity(tlv_data.len() + tlv_status.len() + tlv_mac.len());
+		data.extend_from_slice(&tlv_data);
+		data.extend_from_slice(&tlv_status);
+		data.extend_from_slice(&tlv_mac);
+
+		Ok(Self { data, trailer: self.trailer })
+	}
 }
 
 impl From<Vec<u8>> for ApduResponse {
@@ -96,7 +156,7 @@ impl From<Vec<u8>> for ApduResponse {
 
 impl std::fmt::Display for ApduResponseTrailer {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "(SW1: 0x{:02X}, SW2: 0x{:02X})", self.sw1, self.sw2)
+		write!(f, "(SW1: 0x{:02X}, SW2: 0x{:02X}: {})", self.sw1, self.sw2, self.status())
 	}
 }
 
@@ -116,4 +176,14 @@ pub const TRAILER_WRONG_CLA: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x
 pub const TRAILER_FUNCTION_NOT_SUPPORTED: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x6A, sw2: 0x81 };
 pub const TRAILER_WRONG_P1_P2: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x6B, sw2: 0x00 };
 pub const TRAILER_WRONG_SM_OBJECTS: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x69, sw2: 0x88 };
+pub const TRAILER_AUTHENTICATION_FAILED: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x63, sw2: 0x00 };
+pub const TRAILER_SECURITY_STATUS_NOT_SATISFIED: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x69, sw2: 0x82 };
+pub const TRAILER_CONDITIONS_NOT_SATISFIED: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x69, sw2: 0x85 };
+pub const TRAILER_MISSING_SM_OBJECTS: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x69, sw2: 0x87 };
+pub const TRAILER_FILE_NOT_FOUND: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x6A, sw2: 0x82 };
+pub const TRAILER_INCORRECT_P1_P2: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x6A, sw2: 0x86 };
+pub const TRAILER_INCOMPATIBLE_FILE_STRUCTURE: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x69, sw2: 0x81 };
+pub const TRAILER_NO_CURRENT_EF: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x69, sw2: 0x86 };
+pub const TRAILER_INS_NOT_SUPPORTED: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x6D, sw2: 0x00 };
+pub const TRAILER_CLA_NOT_SUPPORTED: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x6E, sw2: 0x00 };
 pub const TRAILER_UNKNOWN: ApduResponseTrailer = ApduResponseTrailer { sw1: 0x6C, sw2: 0x00 };
\ No newline at end of file
@@ -0,0 +1,297 @@
+use super::owned::ApduResponseTrailer;
+
+/// Status word categories, as per ISO/IEC 7816-4 section 5.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+	/// SW1 = '90' or '61'
+	Normal,
+	/// SW1 = '62' or '63'
+	Warning,
+	/// SW1 = '64' to '66'
+	ExecutionError,
+	/// SW1 = '67' to '6F'
+	CheckingError,
+	/// Any other status word, including proprietary '9XXX'
+	Other,
+}
+
+/// Interindustry status words, as per ISO/IEC 7816-4 section 5.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+	/// '9000'
+	Ok,
+	/// '61XX', with the number of response bytes still available (0 meaning 256)
+	BytesAvailable(u8),
+	/// '6200'
+	WarningNoInformation,
+	/// '6281'
+	DataCorrupted,
+	/// '6282'
+	EndOfFile,
+	/// '6283'
+	FileDeactivated,
+	/// '6284'
+	InvalidFileControlInformation,
+	/// '6300', returned by the IC on failed BAC or PACE mutual authentication
+	AuthenticationFailed,
+	/// '6381'
+	FileFilledUp,
+	/// '63CX', with the number of remaining retries
+	RetryCounter(u8),
+	/// '6400'
+	ExecutionError,
+	/// '6500'
+	MemoryChanged,
+	/// '6581'
+	MemoryFailure,
+	/// '6700'
+	WrongLength,
+	/// '6881'
+	LogicalChannelNotSupported,
+	/// '6882'
+	SecureMessagingNotSupported,
+	/// '6883'
+	LastCommandOfChainExpected,
+	/// '6884'
+	ChainingNotSupported,
+	/// '6981'
+	IncompatibleFileStructure,
+	/// '6982'
+	SecurityStatusNotSatisfied,
+	/// '6983'
+	AuthenticationMethodBlocked,
+	/// '6984'
+	ReferenceDataNotUsable,
+	/// '6985'
+	ConditionsNotSatisfied,
+	/// '6986'
+	CommandNotAllowed,
+	/// '6987'
+	MissingSmObjects,
+	/// '6988'
+	IncorrectSmObjects,
+	/// '6A80'
+	IncorrectData,
+	/// '6A81'
+	FunctionNotSupported,
+	/// '6A82'
+	FileNotFound,
+	/// '6A83'
+	RecordNotFound,
+	/// '6A84'
+	NotEnoughMemory,
+	/// '6A86'
+	IncorrectP1P2,
+	/// '6A87'
+	LcInconsistentWithP1P2,
+	/// '6A88'
+	ReferencedDataNotFound,
+	/// '6B00'
+	WrongP1P2,
+	/// '6CXX', with the exact number of available response bytes (0 meaning 256)
+	WrongLe(u8),
+	/// '6D00'
+	InsNotSupported,
+	/// '6E00'
+	ClaNotSupported,
+	/// '6F00'
+	NoPreciseDiagnosis,
+	/// Any other status word
+	Unknown(ApduResponseTrailer),
+}
+
+impl Status {
+	pub fn category(&self) -> Category {
+		ApduResponseTrailer::from(*self).category()
+	}
+
+	pub fn is_success(&self) -> bool {
+		matches!(self, Self::Ok | Self::BytesAvailable(_))
+	}
+
+	/// Whether the status word reports a secure messaging error, after which
+	/// the IC has aborted the secure messaging session.
+	pub fn is_secure_messaging_error(&self) -> bool {
+		matches!(self, Self::MissingSmObjects | Self::IncorrectSmObjects)
+	}
+
+	/// Remaining retries of a password or key, as reported by '63CX'.
+	pub fn retry_counter(&self) -> Option<u8> {
+		match self {
+			Self::RetryCounter(retries) => Some(*retries),
+			_ => None,
+		}
+	}
+
+	pub fn description(&self) -> &'static str {
+		match self {
+			Self::Ok => "Normal processing",
+			Self::BytesAvailable(_) => "Response bytes still available",
+			Self::WarningNoInformation => "Warning, state of non-volatile memory unchanged",
+			Self::DataCorrupted => "Part of returned data may be corrupted",
+			Self::EndOfFile => "End of file or record reached before reading Ne bytes",
+			Self::FileDeactivated => "Selected file deactivated",
+			Self::InvalidFileControlInformation => "File control information not formatted correctly",
+			Self::AuthenticationFailed => "Authentication failed",
+			Self::FileFilledUp => "File filled up by the last write",
+			Self::RetryCounter(_) => "Verification failed, retries remaining",
+			Self::ExecutionError => "Execution error, state of non-volatile memory unchanged",
+			Self::MemoryChanged => "Execution error, state of non-volatile memory changed",
+			Self::MemoryFailure => "Memory failure",
+			Self::WrongLength => "Wrong length",
+			Self::LogicalChannelNotSupported => "Logical channel not supported",
+			Self::SecureMessagingNotSupported => "Secure messaging not supported",
+			Self::LastCommandOfChainExpected => "Last command of the chain expected",
+			Self::ChainingNotSupported => "Command chaining not supported",
+			Self::IncompatibleFileStructure => "Command incompatible with file structure",
+			Self::SecurityStatusNotSatisfied => "Security status not satisfied",
+			Self::AuthenticationMethodBlocked => "Authentication method blocked",
+			Self::ReferenceDataNotUsable => "Reference data not usable",
+			Self::ConditionsNotSatisfied => "Conditions of use not satisfied",
+			Self::CommandNotAllowed => "Command not allowed, no current EF",
+			Self::MissingSmObjects => "Expected secure messaging data objects missing",
+			Self::IncorrectSmObjects => "Incorrect secure messaging data objects",
+			Self::IncorrectData => "Incorrect parameters in the command data field",
+			Self::FunctionNotSupported => "Function not supported",
+			Self::FileNotFound => "File or application not found",
+			Self::RecordNotFound => "Record not found",
+			Self::NotEnoughMemory => "Not enough memory space in the file",
+			Self::IncorrectP1P2 => "Incorrect parameters P1-P2",
+			Self::LcInconsistentWithP1P2 => "Nc inconsistent with parameters P1-P2",
+			Self::ReferencedDataNotFound => "Referenced data or reference data not found",
+			Self::WrongP1P2 => "Wrong parameters P1-P2",
+			Self::WrongLe(_) => "Wrong Le field",
+			Self::InsNotSupported => "Instruction code not supported or invalid",
+			Self::ClaNotSupported => "Class not supported",
+			Self::NoPreciseDiagnosis => "No precise diagnosis",
+			Self::Unknown(_) => "Unknown status",
+		}
+	}
+}
+
+impl From<ApduResponseTrailer> for Status {
+	fn from(trailer: ApduResponseTrailer) -> Self {
+		match (trailer.sw1, trailer.sw2) {
+			(0x90, 0x00) => Self::Ok,
+			(0x61, n) => Self::BytesAvailable(n),
+			(0x62, 0x00) => Self::WarningNoInformation,
+			(0x62, 0x81) => Self::DataCorrupted,
+			(0x62, 0x82) => Self::EndOfFile,
+			(0x62, 0x83) => Self::FileDeactivated,
+			(0x62, 0x84) => Self::InvalidFileControlInformation,
+			(0x63, 0x00) => Self::AuthenticationFailed,
+			(0x63, 0x81) => Self::FileFilledUp,
+			(0x63, sw2) if sw2 & 0xF0 == 0xC0 => Self::RetryCounter(sw2 & 0x0F),
+			(0x64, 0x00) => Self::ExecutionError,
+			(0x65, 0x00) => Self::MemoryChanged,
+			(0x65, 0x81) => Self::MemoryFailure,
+			(0x67, 0x00) => Self::WrongLength,
+			(0x68, 0x81) => Self::LogicalChannelNotSupported,
+			(0x68, 0x82) => Self::SecureMessagingNotSupported,
+			(0x68, 0x83) => Self::LastCommandOfChainExpected,
+			(0x68, 0x84) => Self::ChainingNotSupported,
+			(0x69, 0x81) => Self::IncompatibleFileStructure,
+			(0x69, 0x82) => Self::SecurityStatusNotSatisfied,
+			(0x69, 0x83) => Self::AuthenticationMethodBlocked,
+			(0x69, 0x84) => Self::ReferenceDataNotUsable,
+			(0x69, 0x85) => Self::ConditionsNotSatisfied,
+			(0x69, 0x86) => Self::CommandNotAllowed,
+			(0x69, 0x87) => Self::MissingSmObjects,
+			(0x69, 0x88) => Self::IncorrectSmObjects,
+			(0x6A, 0x80) => Self::IncorrectData,
+			(0x6A, 0x81) => Self::FunctionNotSupported,
+			(0x6A, 0x82) => Self::FileNotFound,
+			(0x6A, 0x83) => Self::RecordNotFound,
+			(0x6A, 0x84) => Self::NotEnoughMemory,
+			(0x6A, 0x86) => Self::IncorrectP1P2,
+			(0x6A, 0x87) => Self::LcInconsistentWithP1P2,
+			(0x6A, 0x88) => Self::ReferencedDataNotFound,
+			(0x6B, 0x00) => Self::WrongP1P2,
+			(0x6C, n) => Self::WrongLe(n),
+			(0x6D, 0x00) => Self::InsNotSupported,
+			(0x6E, 0x00) => Self::ClaNotSupported,
+			(0x6F, 0x00) => Self::NoPreciseDiagnosis,
+			_ => Self::Unknown(trailer),
+		}
+	}
+}
+
+impl From<Status> for ApduResponseTrailer {
+	fn from(status: Status) -> Self {
+		let (sw1, sw2) = match status {
+			Status::Ok => (0x90, 0x00),
+			Status::BytesAvailable(n) => (0x61, n),
+			Status::WarningNoInformation => (0x62, 0x00),
+			Status::DataCorrupted => (0x62, 0x81),
+			Status::EndOfFile => (0x62, 0x82),
+			Status::FileDeactivated => (0x62, 0x83),
+			Status::InvalidFileControlInformation => (0x62, 0x84),
+			Status::AuthenticationFailed => (0x63, 0x00),
+			Status::FileFilledUp => (0x63, 0x81),
+			Status::RetryCounter(retries) => (0x63, 0xC0 | (retries & 0x0F)),
+			Status::ExecutionError => (0x64, 0x00),
+			Status::MemoryChanged => (0x65, 0x00),
+			Status::MemoryFailure => (0x65, 0x81),
+			Status::WrongLength => (0x67, 0x00),
+			Status::LogicalChannelNotSupported => (0x68, 0x81),
+			Status::SecureMessagingNotSupported => (0x68, 0x82),
+			Status::LastCommandOfChainExpected => (0x68, 0x83),
+			Status::ChainingNotSupported => (0x68, 0x84),
+			Status::IncompatibleFileStructure => (0x69, 0x81),
+			Status::SecurityStatusNotSatisfied => (0x69, 0x82),
+			Status::AuthenticationMethodBlocked => (0x69, 0x83),
+			Status::ReferenceDataNotUsable => (0x69, 0x84),
+			Status::ConditionsNotSatisfied => (0x69, 0x85),
+			Status::CommandNotAllowed => (0x69, 0x86),
+			Status::MissingSmObjects => (0x69, 0x87),
+			Status::IncorrectSmObjects => (0x69, 0x88),
+			Status::IncorrectData => (0x6A, 0x80),
+			Status::FunctionNotSupported => (0x6A, 0x81),
+			Status::FileNotFound => (0x6A, 0x82),
+			Status::RecordNotFound => (0x6A, 0x83),
+			Status::NotEnoughMemory => (0x6A, 0x84),
+			Status::IncorrectP1P2 => (0x6A, 0x86),
+			Status::LcInconsistentWithP1P2 => (0x6A, 0x87),
+			Status::ReferencedDataNotFound => (0x6A, 0x88),
+			Status::WrongP1P2 => (0x6B, 0x00),
+			Status::WrongLe(n) => (0x6C, n),
+			Status::InsNotSupported => (0x6D, 0x00),
+			Status::ClaNotSupported => (0x6E, 0x00),
+			Status::NoPreciseDiagnosis => (0x6F, 0x00),
+			Status::Unknown(trailer) => return trailer,
+		};
+		Self { sw1, sw2 }
+	}
+}
+
+impl std::fmt::Display for Status {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::BytesAvailable(n) => write!(f, "{} ({})", self.description(), if *n == 0 { 256 } else { *n as usize }),
+			Self::RetryCounter(retries) => write!(f, "{} ({})", self.description(), retries),
+			Self::WrongLe(n) => write!(f, "{} ({} bytes available)", self.description(), if *n == 0 { 256 } else { *n as usize }),
+			_ => f.write_str(self.description()),
+		}
+	}
+}
+
+impl ApduResponseTrailer {
+	pub fn status(&self) -> Status {
+		Status::from(*self)
+	}
+
+	pub fn category(&self) -> Category {
+		match self.sw1 {
+			0x90 if self.sw2 == 0x00 => Category::Normal,
+			0x61 => Category::Normal,
+			0x62 | 0x63 => Category::Warning,
+			0x64..=0x66 => Category::ExecutionError,
+			0x67..=0x6F => Category::CheckingError,
+			_ => Category::Other,
+		}
+	}
+
+	pub fn is_success(&self) -> bool {
+		self.category() == Category::Normal
+	}
+}
\ No newline at end of file
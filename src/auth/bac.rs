@@ -1,116 +1,196 @@
-use crate::error::BoxResult;
-use crate::apdu::command::borrowed::ApduCommand;
+use crate::error::{Error, Result};
+use crate::auth::error::BacError;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::commands::{select_aid, get_challenge, mutual_authenticate, AID_EMRTD};
 use crate::apdu::response::owned::{ApduResponse, TRAILER_OK};
-use std::time::Duration;
-use crate::crypto::derive_key;
+use crate::crypto::{kdf, Key, KeyAlgorithm, SecretKey, COUNTER_ENC, COUNTER_MAC};
 use crate::crypto::des::mac;
 use crate::crypto::tdes::{encrypt, decrypt};
 use crate::mrz::borrowed::{Mrz, MrzData};
+use crate::sm::Session;
+use crate::transport::{Transport, AsyncTransport, Protocol, Step, run, run_async};
 use rand::RngCore;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SessionKeys {
-	pub ks_enc: Vec<u8>,
-	pub ks_mac: Vec<u8>,
-	pub ssc: u64,
+pub fn handshake<T: Transport + ?Sized>(transport: &mut T, mrz: &Mrz) -> Result<Session> {
+	handshake_with_rng(transport, mrz, &mut rand::thread_rng())
 }
 
-#[cfg(feature = "nfc1")]
-pub fn handshake(device: &mut nfc1::Device, mrz: &Mrz) -> BoxResult<SessionKeys> {
-	let k_seed = mrz.derive_seed_key();
-	let k_enc = derive_key(&k_seed, 1);
-	let k_mac = derive_key(&k_seed, 2);
-
-	// The following Basic Access Control (BAC) handshake is performed as per
-	// ICAO 9303 MRTD v8 2021 Part 11, section 4.3.1 Protocol Specification
-	let mut rng = rand::thread_rng();
-
-	// Send initial select command
-	let apdu = APDU_INITIAL_SELECT.to_vec();
-	let initial_select_res = ApduResponse::from(device.initiator_transceive_bytes(&apdu, 2, nfc1::Timeout::Duration(Duration::from_millis(500)))?);
-	if initial_select_res.trailer != TRAILER_OK {
-		return Err("INITIAL SELECT failed".into());
-	}
+/// Performs the handshake with terminal nonces from the given random number
+/// generator, which allows replaying a recorded session.
+pub fn handshake_with_rng<T: Transport + ?Sized, R: RngCore + ?Sized>(transport: &mut T, mrz: &Mrz, rng: &mut R) -> Result<Session> {
+	run(transport, Handshake::new(mrz, rng))
+}
 
-	// 1) The IFD requests a challenge RND.IC by sending the GET CHALLENGE command.
-	// The IC generates and responds with a nonce RND.IC.
-	let apdu = APDU_GET_CHALLENGE.to_vec();
-	let get_challenge_res = ApduResponse::from(device.initiator_transceive_bytes(&apdu, 10, nfc1::Timeout::Duration(Duration::from_millis(500)))?);
-	if get_challenge_res.trailer != TRAILER_OK {
-		return Err("GET CHALLENGE failed".into());
-	}
+pub async fn handshake_async<T: AsyncTransport + ?Sized>(transport: &mut T, mrz: &Mrz<'_>) -> Result<Session> {
+	let handshake = Handshake::new(mrz, &mut rand::thread_rng());
+	run_async(transport, handshake).await
+}
 
-	// 2) The IFD performs the following operations:
-
-	// a) generate a nonce RND.IFD and keying material K.IFD.
-	let mut rnd_ifd = [0; 8];
-	rng.fill_bytes(&mut rnd_ifd);
-	let mut k_ifd = [0; 16];
-	rng.fill_bytes(&mut k_ifd);
-
-	// b) generate the concatenation S = RND.IFD || RND.IC || K.IFD
-	let mut s = Vec::with_capacity(32);
-	s.extend_from_slice(&rnd_ifd[0..8]);
-	s.extend_from_slice(&get_challenge_res.data[0..8]);
-	s.extend_from_slice(&k_ifd[0..16]);
-
-	// c) compute the cryptogram EIFD = E(KEnc, S).
-	let eifd = encrypt(&s, &k_enc)?;
-
-	// d) compute the checksum MIFD = MAC(KMAC, EIFD)
-	let mifd = mac(&eifd, &k_mac)?;
-
-	// e) send the EXTERNAL AUTHENTICATE command with mutual authenticate function using the data EIFD || MIFD
-	let mut eifd_mifd = Vec::with_capacity(eifd.len() + mifd.len());
-	eifd_mifd.extend_from_slice(&eifd);
-	eifd_mifd.extend_from_slice(&mifd);
-	let apdu = apdu_external_authenticate(&eifd_mifd).to_vec();
-	let auth_res = ApduResponse::from(device.initiator_transceive_bytes(&apdu, 42, nfc1::Timeout::None)?);
-	if auth_res.trailer != TRAILER_OK {
-		return Err("EXTERNAL AUTHENTICATE failed".into());
-	}
-	let (e_ic, m_ic) = auth_res.data.split_at(32);
+pub async fn handshake_with_rng_async<T: AsyncTransport + ?Sized, R: RngCore + ?Sized>(transport: &mut T, mrz: &Mrz<'_>, rng: &mut R) -> Result<Session> {
+	run_async(transport, Handshake::new(mrz, rng)).await
+}
+
+/// Former name of [`Session`], whose keys and SSC are now read with
+/// [`Session::ks_enc`], [`Session::ks_mac`] and [`Session::ssc`].
+#[deprecated(note = "use sm::Session")]
+pub type SessionKeys = Session;
 
-	// 4) The IFD performs the following operations:
+/// Former name of [`mutual_authenticate`].
+#[deprecated(note = "use apdu::commands::mutual_authenticate")]
+pub fn apdu_external_authenticate(data: &[u8]) -> ApduCommand<'_> {
+	mutual_authenticate(data)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HandshakeState {
+	Start,
+	InitialSelect,
+	GetChallenge,
+	ExternalAuthenticate,
+}
 
-	// a) check the checksum MIC of the cryptogram EIC
-	let expected_m_ic = mac(e_ic, &k_mac)?;
-	if m_ic != expected_m_ic {
-		return Err(format!("Invalid MAC of cryptogram EIC: Expected {:02x?}, got {:02x?}", expected_m_ic, m_ic).into());
+/// The Basic Access Control (BAC) handshake, performed as per
+/// ICAO 9303 MRTD v8 2021 Part 11, section 4.3.1 Protocol Specification
+#[derive(Debug, Clone)]
+pub struct Handshake {
+	state: HandshakeState,
+	k_enc: Key,
+	k_mac: Key,
+	rnd_ic: Vec<u8>,
+	rnd_ifd: [u8; 8],
+	k_ifd: SecretKey,
+	last: ApduCommand<'static>,
+}
+
+impl Handshake {
+	pub fn new<R: RngCore + ?Sized>(mrz: &Mrz, rng: &mut R) -> Self {
+		let k_seed = mrz.derive_seed_key();
+		let k_enc = kdf(&k_seed, COUNTER_ENC, KeyAlgorithm::Tdes);
+		let k_mac = kdf(&k_seed, COUNTER_MAC, KeyAlgorithm::Tdes);
+
+		// 2) a) generate a nonce RND.IFD and keying material K.IFD.
+		let mut rnd_ifd = [0; 8];
+		rng.fill_bytes(&mut rnd_ifd);
+		let mut k_ifd = vec![0; 16];
+		rng.fill_bytes(&mut k_ifd);
+
+		Self { state: HandshakeState::Start, k_enc, k_mac, rnd_ic: Vec::new(), rnd_ifd, k_ifd: k_ifd.into(), last: APDU_INITIAL_SELECT }
 	}
 
-	// b) decrypt the cryptogram EIC
-	let d_ic = decrypt(e_ic, &k_enc)?;
+	fn transmit(&mut self, state: HandshakeState, command: ApduCommand<'static>) -> Step<Session> {
+		self.state = state;
+		self.last = command.clone();
+		Step::Transmit(command)
+	}
 
-	// c) extract RND.IFD from R and check if IC returned the correct value
-	let (rnd_ic, rnd_ifd_k_ic) = d_ic.split_at(8);
-	let (rnd_ifd_ic, k_ic) = rnd_ifd_k_ic.split_at(8);
-	if rnd_ifd_ic != rnd_ifd {
-		return Err("Invalid RND.IFD value in cryptogram EIC".into());
+	/// Fails with the status word of the IC, unless the last command succeeded.
+	fn check(&self, res: &ApduResponse) -> Result<()> {
+		if res.trailer != TRAILER_OK {
+			return Err(Error::status(&self.last, res.trailer));
+		}
+		Ok(())
 	}
+}
+
+impl Protocol for Handshake {
+	type Output = Session;
+
+	fn step(&mut self, response: Option<ApduResponse>) -> Result<Step<Session>> {
+		match (self.state, response) {
+			(HandshakeState::Start, _) => {
+				// Send initial select command
+				Ok(self.transmit(HandshakeState::InitialSelect, APDU_INITIAL_SELECT))
+			}
+			(HandshakeState::InitialSelect, Some(initial_select_res)) => {
+				self.check(&initial_select_res)?;
+
+				// 1) The IFD requests a challenge RND.IC by sending the GET CHALLENGE command.
+				// The IC generates and responds with a nonce RND.IC.
+				Ok(self.transmit(HandshakeState::GetChallenge, APDU_GET_CHALLENGE))
+			}
+			(HandshakeState::GetChallenge, Some(get_challenge_res)) => {
+				self.check(&get_challenge_res)?;
+				if get_challenge_res.data.len() != 8 {
+					return Err(Error::ResponseLength { expected: 8, actual: get_challenge_res.data.len() });
+				}
+				self.rnd_ic = get_challenge_res.data;
+
+				// 2) The IFD performs the following operations:
+
+				// b) generate the concatenation S = RND.IFD || RND.IC || K.IFD
+				let mut s = Zeroizing::new(Vec::with_capacity(32));
+				s.extend_from_slice(&self.rnd_ifd[0..8]);
+				s.extend_from_slice(&self.rnd_ic[0..8]);
+				s.extend_from_slice(&self.k_ifd[0..16]);
+
+				// c) compute the cryptogram EIFD = E(KEnc, S).
+				let eifd = encrypt(&s, &self.k_enc)?;
+
+				// d) compute the checksum MIFD = MAC(KMAC, EIFD)
+				let mifd = mac(&eifd, &self.k_mac)?;
+
+				// e) send the EXTERNAL AUTHENTICATE command with mutual authenticate function using the data EIFD || MIFD
+				let mut eifd_mifd = Vec::with_capacity(eifd.len() + mifd.len());
+				eifd_mifd.extend_from_slice(&eifd);
+				eifd_mifd.extend_from_slice(&mifd);
+				Ok(self.transmit(HandshakeState::ExternalAuthenticate, mutual_authenticate(eifd_mifd)))
+			}
+			(HandshakeState::ExternalAuthenticate, Some(auth_res)) => {
+				self.check(&auth_res)?;
+				if auth_res.data.len() != 40 {
+					return Err(Error::ResponseLength { expected: 40, actual: auth_res.data.len() });
+				}
+				let (e_ic, m_ic) = auth_res.data.split_at(32);
+
+				// 4) The IFD performs the following operations:
+
+				// a) check the checksum MIC of the cryptogram EIC
+				let expected_m_ic = mac(e_ic, &self.k_mac)?;
+				if m_ic.ct_ne(&expected_m_ic).into() {
+					return Err(BacError::InvalidCryptogramMac.into());
+				}
+
+				// b) decrypt the cryptogram EIC
+				let d_ic = Zeroizing::new(decrypt(e_ic, &self.k_enc)?);
+
+				// c) extract RND.IFD from R and check if IC returned the correct value
+				let (_rnd_ic, rnd_ifd_k_ic) = d_ic.split_at(8);
+				let (rnd_ifd_ic, k_ic) = rnd_ifd_k_ic.split_at(8);
+				if rnd_ifd_ic != self.rnd_ifd {
+					return Err(BacError::NonceMismatch.into());
+				}
+
+				// 5) The IFD and the IC derive session keys KSEnc and KSMAC
+				Ok(Step::Done(derive_session_keys(&self.k_ifd, k_ic, &self.rnd_ic, &self.rnd_ifd)))
+			}
+			(_, None) => Err(Error::ProtocolState),
+		}
+	}
+}
 
-	// 5) The IFD and the IC derive session keys KSEnc and KSMAC using the key
-	// derivation mechanism described in Sections 9.7.1. and 9.7.4
-	// with (K.IC xor K.IFD) as shared secret
-	let mut k_ic_xor_k_ifd = vec![0; k_ifd.len()];
+/// Derives the session keys KSEnc and KSMAC and the initial send sequence
+/// counter from the keying material and nonces exchanged during the handshake.
+pub fn derive_session_keys(k_ifd: &[u8], k_ic: &[u8], rnd_ic: &[u8], rnd_ifd: &[u8]) -> Session {
+	// The key derivation mechanism described in Sections 9.7.1. and 9.7.4
+	// is used with (K.IC xor K.IFD) as shared secret
+	let mut k_ic_xor_k_ifd = Zeroizing::new(vec![0; k_ifd.len()]);
 	for (i, (a, b)) in k_ifd.iter().zip(k_ic.iter()).enumerate() {
 		k_ic_xor_k_ifd[i] = *a ^ *b;
 	}
 
-	let ks_enc = derive_key(&k_ic_xor_k_ifd, 1);
+	let ks_enc = kdf(&k_ic_xor_k_ifd, COUNTER_ENC, KeyAlgorithm::Tdes);
 
-	let ks_mac = derive_key(&k_ic_xor_k_ifd, 2);
+	let ks_mac = kdf(&k_ic_xor_k_ifd, COUNTER_MAC, KeyAlgorithm::Tdes);
 
 	let mut ssc = Vec::with_capacity(8);
-	ssc.extend_from_slice(&get_challenge_res.data[4..8]);
+	ssc.extend_from_slice(&rnd_ic[4..8]);
 	ssc.extend_from_slice(&rnd_ifd[4..8]);
 	let ssc = u64::from_be_bytes([ssc[0], ssc[1], ssc[2], ssc[3], ssc[4], ssc[5], ssc[6], ssc[7]]);
 
-	Ok(SessionKeys { ks_enc, ks_mac, ssc })
+	Session::from_keys(ks_enc, ks_mac, ssc)
 }
 
-const APDU_INITIAL_SELECT: ApduCommand = ApduCommand { cla: 0x00, ins: 0xA4, p1: 0x04, p2: 0x0C, data: &[0xa0, 0x00, 0x00, 0x02, 0x47, 0x10, 0x01], rx_len: 0 };
-const APDU_GET_CHALLENGE: ApduCommand = ApduCommand { cla: 0x00, ins: 0x84, p1: 0x00, p2: 0x00, data: &[], rx_len: 8 };
-pub fn apdu_external_authenticate(data: &[u8]) -> ApduCommand {
-	ApduCommand { cla: 0x00, ins: 0x82, p1: 0x00, p2: 0x00, data, rx_len: 40 }
-}
\ No newline at end of file
+const APDU_INITIAL_SELECT: ApduCommand = select_aid(AID_EMRTD);
+const APDU_GET_CHALLENGE: ApduCommand = get_challenge(8);
\ No newline at end of file
@@ -0,0 +1,62 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacError {
+	/// The checksum of the cryptogram EIC does not match
+	InvalidCryptogramMac,
+	/// The IC returned a different RND.IFD in the cryptogram EIC
+	NonceMismatch,
+}
+
+impl std::fmt::Display for BacError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::InvalidCryptogramMac => write!(f, "Invalid MAC of cryptogram EIC"),
+			Self::NonceMismatch => write!(f, "Invalid RND.IFD value in cryptogram EIC"),
+		}
+	}
+}
+
+impl std::error::Error for BacError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaceError {
+	/// The protocol or standardized domain parameters are not supported
+	UnsupportedParameters,
+	/// A public key received from the IC is invalid for the domain parameters
+	InvalidPublicKey,
+	/// The authentication token received from the IC does not match
+	InvalidAuthenticationToken,
+	/// A dynamic authentication data object received from the IC is malformed
+	InvalidResponse,
+	/// EF.CardSecurity is malformed or has no Chip Authentication public key
+	InvalidCardSecurity,
+	/// The encrypted chip authentication data does not match the public key of the IC
+	ChipAuthenticationFailed,
+	/// The IC rejected the password, with the remaining retries if known
+	AuthenticationFailed { retries: Option<u8> },
+	/// The PIN has one retry left, and must be resumed with the CAN first
+	PasswordSuspended,
+	/// The password has no retries left, and must be unblocked
+	PasswordBlocked,
+	/// The password has been deactivated
+	PasswordDeactivated,
+}
+
+impl std::fmt::Display for PaceError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnsupportedParameters => write!(f, "Unsupported protocol or domain parameters"),
+			Self::InvalidPublicKey => write!(f, "Invalid public key"),
+			Self::InvalidAuthenticationToken => write!(f, "Invalid authentication token"),
+			Self::InvalidResponse => write!(f, "Invalid dynamic authentication data"),
+			Self::InvalidCardSecurity => write!(f, "Invalid or missing Chip Authentication public key in EF.CardSecurity"),
+			Self::ChipAuthenticationFailed => write!(f, "Chip Authentication Mapping failed"),
+			Self::AuthenticationFailed { retries: Some(retries) } => write!(f, "Wrong password, {} retries left", retries),
+			Self::AuthenticationFailed { retries: None } => write!(f, "Wrong password"),
+			Self::PasswordSuspended => write!(f, "PIN suspended, resume with the CAN"),
+			Self::PasswordBlocked => write!(f, "Password blocked"),
+			Self::PasswordDeactivated => write!(f, "Password deactivated"),
+		}
+	}
+}
+
+impl std::error::Error for PaceError {}
\ No newline at end of file
@@ -1,2 +1,3 @@
 pub mod pace;
-pub mod bac;
\ No newline at end of file
+pub mod bac;
+pub mod error;
\ No newline at end of file
@@ -0,0 +1,80 @@
+use crate::error::Result;
+use crate::auth::error::PaceError;
+use iso7816_tlv::ber as tlv;
+
+/// Object identifier id-signedData of the CMS content type
+const ID_SIGNED_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+/// Object identifier id-PK-ECDH of a ChipAuthenticationPublicKeyInfo
+const ID_PK_ECDH: &[u8] = &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x01, 0x02];
+
+/// Returns the ECDH Chip Authentication public keys of the IC, as encoded
+/// points, from the ChipAuthenticationPublicKeyInfos in EF.CardSecurity.
+///
+/// EF.CardSecurity is a CMS SignedData structure with the SecurityInfos as
+/// its content. Its signature is not verified here, which is part of passive
+/// authentication.
+pub(super) fn chip_authentication_public_keys(card_security: &[u8]) -> Result<Vec<Vec<u8>>> {
+	let security_infos = security_infos(card_security).ok_or(PaceError::InvalidCardSecurity)?;
+	let public_keys: Vec<Vec<u8>> = security_infos.iter().filter_map(chip_authentication_public_key).collect();
+	if public_keys.is_empty() {
+		return Err(PaceError::InvalidCardSecurity.into());
+	}
+	Ok(public_keys)
+}
+
+/// Parses the SecurityInfos from the eContent of the SignedData in ContentInfo.
+fn security_infos(card_security: &[u8]) -> Option<Vec<tlv::Tlv>> {
+	// ContentInfo ::= SEQUENCE { contentType, content [0] EXPLICIT SignedData }
+	let content_info = tlv::Tlv::from_bytes(card_security).ok()?;
+	let [content_type, content] = children(&content_info, 0x30)?.as_slice() else {
+		return None;
+	};
+	if primitive(content_type, 0x06)? != ID_SIGNED_DATA {
+		return None;
+	}
+
+	// SignedData ::= SEQUENCE { version, digestAlgorithms, encapContentInfo, ... }
+	let signed_data = children(content, 0xA0)?.first()?;
+	let encap_content_info = children(signed_data, 0x30)?.get(2)?;
+
+	// EncapsulatedContentInfo ::= SEQUENCE { eContentType, eContent [0] EXPLICIT OCTET STRING }
+	let e_content = children(encap_content_info, 0x30)?.get(1)?;
+	let e_content = primitive(children(e_content, 0xA0)?.first()?, 0x04)?;
+
+	// SecurityInfos ::= SET OF SecurityInfo
+	let security_infos = tlv::Tlv::from_bytes(e_content).ok()?;
+	children(&security_infos, 0x31).cloned()
+}
+
+/// Returns the public key of a ChipAuthenticationPublicKeyInfo for ECDH, as
+/// SEQUENCE { protocol, chipAuthenticationPublicKey SubjectPublicKeyInfo, keyId OPTIONAL }
+fn chip_authentication_public_key(security_info: &tlv::Tlv) -> Option<Vec<u8>> {
+	let fields = children(security_info, 0x30)?;
+	if primitive(fields.first()?, 0x06)? != ID_PK_ECDH {
+		return None;
+	}
+
+	// SubjectPublicKeyInfo ::= SEQUENCE { algorithm, subjectPublicKey BIT STRING }
+	let subject_public_key = primitive(children(fields.get(1)?, 0x30)?.get(1)?, 0x03)?;
+	match subject_public_key.split_first() {
+		// The public key is a whole number of octets
+		Some((0x00, public_key)) => Some(public_key.to_vec()),
+		_ => None,
+	}
+}
+
+/// Returns the children of a constructed data object with the given tag.
+fn children(data_object: &tlv::Tlv, tag: u64) -> Option<&Vec<tlv::Tlv>> {
+	match data_object.value() {
+		tlv::Value::Constructed(children) if Into::<u64>::into(data_object.tag().clone()) == tag => Some(children),
+		_ => None,
+	}
+}
+
+/// Returns the value of a primitive data object with the given tag.
+fn primitive(data_object: &tlv::Tlv, tag: u64) -> Option<&[u8]> {
+	match data_object.value() {
+		tlv::Value::Primitive(value) if Into::<u64>::into(data_object.tag().clone()) == tag => Some(value),
+		_ => None,
+	}
+}
\ No newline at end of file
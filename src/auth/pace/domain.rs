@@ -0,0 +1,205 @@
+use crate::error::Result;
+use crate::auth::error::PaceError;
+use crate::crypto::{aes, tdes, KeyAlgorithm};
+use crate::crypto::dh::Group;
+use crate::crypto::ec::{Curve, Point};
+use super::PaceSdp;
+use num_bigint::BigUint;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+/// Constants c0 and c1 of the pseudo-random function for a block length of 128 bits
+const PRF_C_128: [&[u8]; 2] = [
+	&[0xA6, 0x68, 0x89, 0x2A, 0x7C, 0x41, 0xE3, 0xCA, 0x73, 0x9F, 0x40, 0xB0, 0x57, 0xD8, 0x59, 0x04],
+	&[0xA4, 0xE1, 0x36, 0xAC, 0x72, 0x5F, 0x73, 0x8B, 0x01, 0xC1, 0xF6, 0x02, 0x17, 0xC1, 0x88, 0xAD],
+];
+/// Constants c0 and c1 of the pseudo-random function for a block length of 256 bits
+const PRF_C_256: [&[u8]; 2] = [
+	&[0xD4, 0x63, 0xD6, 0x52, 0x34, 0x12, 0x4E, 0xF7, 0x89, 0x70, 0x54, 0x98, 0x6D, 0xCA, 0x0A, 0x17, 0x4E, 0x28, 0xDF, 0x75, 0x8C, 0xBA, 0xA0, 0x3F, 0x24, 0x06, 0x16, 0x41, 0x4D, 0x5A, 0x16, 0x76],
+	&[0x54, 0xBD, 0x72, 0x55, 0xF0, 0xAA, 0xF8, 0x31, 0xBE, 0xC3, 0x42, 0x3F, 0xCF, 0x39, 0xD6, 0x9B, 0x6C, 0xBF, 0x06, 0x66, 0x77, 0xD0, 0xFA, 0xAE, 0x5A, 0xAD, 0xD9, 0x9D, 0xF8, 0xE5, 0x35, 0x17],
+];
+
+/// Domain parameters of the key agreement, whose generator is replaced by
+/// the ephemeral generator once the nonce is mapped.
+#[derive(Debug, Clone)]
+pub(super) enum Domain {
+	Dh { group: Group, generator: BigUint },
+	Ec { curve: Curve, generator: Point },
+}
+
+/// An element of the group of the domain parameters, such as a public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Element {
+	Dh(BigUint),
+	Ec(Point),
+}
+
+impl Domain {
+	pub fn new(sdp: &PaceSdp) -> Option<Self> {
+		if let Some(group) = sdp.group() {
+			return Some(Self::Dh { group, generator: group.generator() });
+		}
+		sdp.curve().map(|curve| Self::Ec { curve, generator: curve.generator() })
+	}
+
+	pub fn is_ecdh(&self) -> bool {
+		matches!(self, Self::Ec { .. })
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Dh { group, .. } => group.name,
+			Self::Ec { curve, .. } => curve.name,
+		}
+	}
+
+	/// Generates an ephemeral private key in the range [1, q-1] or [1, n-1].
+	pub fn random_private_key<R: RngCore + ?Sized>(&self, rng: &mut R) -> BigUint {
+		match self {
+			Self::Dh { group, .. } => group.random_exponent(rng),
+			Self::Ec { curve, .. } => curve.random_scalar(rng),
+		}
+	}
+
+	/// Computes the public key PK = g^SK or PK = SK * G and encodes it, as an
+	/// unsigned integer of the length of p or as an uncompressed point.
+	pub fn public_key(&self, sk: &BigUint) -> Result<Vec<u8>> {
+		match self {
+			Self::Dh { group, generator } => Ok(group.encode(&generator.modpow(sk, &group.prime()))),
+			Self::Ec { curve, generator } => {
+				let pk = curve.mul(sk, generator).ok_or(PaceError::InvalidPublicKey)?;
+				Ok(curve.encode_point(&pk))
+			}
+		}
+	}
+
+	/// Decodes and validates a public key received from the IC.
+	pub fn decode_public_key(&self, data: &[u8]) -> Result<Element> {
+		let pk = match self {
+			Self::Dh { group, .. } => group.decode_public_key(data).map(Element::Dh),
+			Self::Ec { curve, .. } => curve.decode_point(data).map(Element::Ec),
+		};
+		Ok(pk.ok_or(PaceError::InvalidPublicKey)?)
+	}
+
+	/// Computes the shared element PK^SK or SK * PK.
+	pub fn agree(&self, sk: &BigUint, pk: &Element) -> Result<Element> {
+		let shared = match (self, pk) {
+			(Self::Dh { group, .. }, Element::Dh(pk)) => {
+				let shared = pk.modpow(sk, &group.prime());
+				(shared != BigUint::from(1u8)).then_some(Element::Dh(shared))
+			}
+			(Self::Ec { curve, .. }, Element::Ec(pk)) => curve.mul(sk, pk).map(Element::Ec),
+			_ => None,
+		};
+		Ok(shared.ok_or(PaceError::InvalidPublicKey)?)
+	}
+
+	/// Generic Mapping of the nonce s with the shared element H, which is
+	/// g~ = g^s * h for DH and G~ = s * G + H for ECDH.
+	pub fn map_generic(&self, s: &BigUint, h: &Element) -> Result<Self> {
+		let mapped = match (self, h) {
+			(Self::Dh { group, generator }, Element::Dh(h)) => {
+				let p = group.prime();
+				let generator = generator.modpow(s, &p) * h % &p;
+				(generator != BigUint::from(1u8)).then_some(Self::Dh { group: *group, generator })
+			}
+			(Self::Ec { curve, generator }, Element::Ec(h)) => {
+				curve.mul(s, generator)
+					.and_then(|s_g| curve.add(&s_g, h))
+					.map(|generator| Self::Ec { curve: *curve, generator })
+			}
+			_ => None,
+		};
+		Ok(mapped.ok_or(PaceError::InvalidPublicKey)?)
+	}
+
+	/// Integrated Mapping of the nonce s with the nonce t chosen by the
+	/// terminal, which maps R = Rp(s, t) to the generator g~ = R^((p-1)/q)
+	/// for DH and to the point G~ = fG(R) for ECDH.
+	pub fn map_integrated(&self, s: &[u8], t: &[u8], algorithm: KeyAlgorithm) -> Result<Self> {
+		let mapped = match self {
+			Self::Dh { group, .. } => {
+				let p = group.prime();
+				let r = pseudo_random_number(s, t, &p, algorithm)?;
+				let generator = r.modpow(&((&p - 1u8) / group.order()), &p);
+				(generator != BigUint::from(1u8)).then_some(Self::Dh { group: *group, generator })
+			}
+			Self::Ec { curve, .. } => {
+				let r = pseudo_random_number(s, t, &curve.prime(), algorithm)?;
+				curve.map_to_point(&r).map(|generator| Self::Ec { curve: *curve, generator })
+			}
+		};
+		Ok(mapped.ok_or(PaceError::InvalidPublicKey)?)
+	}
+
+	/// Whether the domain parameters support Integrated Mapping, which
+	/// requires p ≡ 3 mod 4 for ECDH.
+	pub fn supports_integrated_mapping(&self) -> bool {
+		match self {
+			Self::Dh { .. } => true,
+			Self::Ec { curve, .. } => {
+				let p = curve.prime();
+				p.bit(0) && p.bit(1)
+			}
+		}
+	}
+
+	/// Encodes the shared secret K, which is the x-coordinate of the shared
+	/// point for ECDH.
+	pub fn shared_secret(&self, k: &Element) -> Zeroizing<Vec<u8>> {
+		match (self, k) {
+			(Self::Dh { group, .. }, Element::Dh(k)) => Zeroizing::new(group.encode(k)),
+			(Self::Ec { curve, .. }, Element::Ec(k)) => Zeroizing::new(curve.encode_field_element(&k.x)),
+			_ => Zeroizing::new(Vec::new()),
+		}
+	}
+
+	/// Tag of the public value in the public key data object DO'7F49'
+	pub fn public_key_tag(&self) -> u8 {
+		match self {
+			Self::Dh { .. } => 0x84,
+			Self::Ec { .. } => 0x86,
+		}
+	}
+}
+
+/// Pseudo-random number mapping Rp(s, t) of Integrated Mapping, as per
+/// ICAO 9303 MRTD v8 2021 Part 11, section 4.4.3.3.2
+///
+/// The block cipher E in CBC mode with a zero IV is keyed with t and
+/// iterated over the constants c0 and c1 for the smallest n with
+/// n * l >= log2 p + 64, and x1 || ... || xn is then reduced modulo p. For a
+/// 256-bit p and l = 128 these are the three blocks of Appendix G.3.
+///
+/// AES-192 uses the constants for l = 256, as constants of 192 bits are not
+/// a multiple of the AES block size, and truncates the keys ki to 192 bits.
+fn pseudo_random_number(s: &[u8], t: &[u8], p: &BigUint, algorithm: KeyAlgorithm) -> Result<BigUint> {
+	let key_len = algorithm.key_len();
+	let [c0, c1] = match algorithm {
+		KeyAlgorithm::Tdes | KeyAlgorithm::Aes128 => PRF_C_128,
+		KeyAlgorithm::Aes192 | KeyAlgorithm::Aes256 => PRF_C_256,
+	};
+	let encrypt = |key: &[u8], data: &[u8]| match algorithm {
+		KeyAlgorithm::Tdes => tdes::encrypt(data, key),
+		_ => aes::encrypt(data, key, &aes::ZERO_IV),
+	};
+
+	// The nonce s has the length l of the constants
+	if s.len() != c0.len() {
+		return Err(PaceError::InvalidResponse.into());
+	}
+
+	// k0 = E(t, s)
+	let mut key = Zeroizing::new(encrypt(t, s)?);
+	key.truncate(key_len);
+	let n = (p.bits() as usize + 64).div_ceil(c0.len() * 8);
+	let mut x = Zeroizing::new(Vec::with_capacity(n * c0.len()));
+	for _ in 0..n {
+		// ki+1 = E(ki, c0), xi = E(ki, c1)
+		x.extend_from_slice(&encrypt(&key, c1)?);
+		key = Zeroizing::new(encrypt(&key, c0)?);
+		key.truncate(key_len);
+	}
+	Ok(BigUint::from_bytes_be(&x) % p)
+}
\ No newline at end of file
@@ -0,0 +1,476 @@
+use crate::error::{Error, Result};
+use crate::auth::error::PaceError;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::commands::{mse_set_at_pace, general_authenticate, select_aid, AID_EMRTD};
+use crate::apdu::response::owned::{ApduResponse, TRAILER_OK};
+use crate::apdu::response::status::Status;
+use crate::crypto::{aes, des, kdf, tdes, Key, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC};
+use crate::files::{ReadFile, EF_CARDSECURITY};
+use crate::sm::{SecureMessaging, Session};
+use crate::transport::{Transport, AsyncTransport, Protocol, Step, run, run_async};
+use super::{Mapping, PaceAlg, PaceSdp, Password, PASSWORD_PIN};
+use super::card_security::chip_authentication_public_keys;
+use super::domain::{Domain, Element};
+use iso7816_tlv::ber as tlv;
+use num_bigint::BigUint;
+use rand::RngCore;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// Performs the handshake with the given password, such as `&Mrz` or [`Password::can`].
+pub fn handshake<T: Transport + ?Sized>(transport: &mut T, password: impl Into<Password>, alg: &PaceAlg, sdp: &PaceSdp) -> Result<PaceSession> {
+	handshake_with_rng(transport, password, alg, sdp, &mut rand::thread_rng())
+}
+
+/// Performs the handshake with ephemeral keys from the given random number
+/// generator, which allows replaying a recorded session.
+pub fn handshake_with_rng<T: Transport + ?Sized, R: RngCore + ?Sized>(transport: &mut T, password: impl Into<Password>, alg: &PaceAlg, sdp: &PaceSdp, rng: &mut R) -> Result<PaceSession> {
+	run(transport, Handshake::new(password, alg, sdp, rng)?)
+}
+
+pub async fn handshake_async<T: AsyncTransport + ?Sized>(transport: &mut T, password: impl Into<Password>, alg: &PaceAlg, sdp: &PaceSdp) -> Result<PaceSession> {
+	let handshake = Handshake::new(password, alg, sdp, &mut rand::thread_rng())?;
+	run_async(transport, handshake).await
+}
+
+pub async fn handshake_with_rng_async<T: AsyncTransport + ?Sized, R: RngCore + ?Sized>(transport: &mut T, password: impl Into<Password>, alg: &PaceAlg, sdp: &PaceSdp, rng: &mut R) -> Result<PaceSession> {
+	run_async(transport, Handshake::new(password, alg, sdp, rng)?).await
+}
+
+/// Resumes a suspended PIN, which the IC reports as [`PaceError::PasswordSuspended`].
+///
+/// PACE is performed with the CAN, followed by PACE with the PIN in the
+/// secure messaging channel of the first, which resets the retry counter of
+/// the PIN once it succeeds. The session of the PIN is returned.
+pub fn resume<T: Transport + ?Sized>(transport: &mut T, can: impl Into<Password>, pin: impl Into<Password>, alg: &PaceAlg, sdp: &PaceSdp) -> Result<PaceSession> {
+	let mut rng = rand::thread_rng();
+	let can = run(transport, Handshake::new(can, alg, sdp, &mut rng)?.without_application())?;
+	run(transport, Handshake::new(pin, alg, sdp, &mut rng)?.within(can.session))
+}
+
+pub async fn resume_async<T: AsyncTransport + ?Sized>(transport: &mut T, can: impl Into<Password>, pin: impl Into<Password>, alg: &PaceAlg, sdp: &PaceSdp) -> Result<PaceSession> {
+	let (can, pin) = {
+		let mut rng = rand::thread_rng();
+		(Handshake::new(can, alg, sdp, &mut rng)?.without_application(), Handshake::new(pin, alg, sdp, &mut rng)?)
+	};
+	let can = run_async(transport, can).await?;
+	run_async(transport, pin.within(can.session)).await
+}
+
+/// The session established by PACE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaceSession {
+	pub session: Session,
+	/// The Chip Authentication public key PKIC from EF.CardSecurity, if the IC
+	/// proved possession of its private key with Chip Authentication Mapping
+	pub chip_authentication_public_key: Option<Vec<u8>>,
+}
+
+impl PaceSession {
+	/// Whether the IC was authenticated with Chip Authentication Mapping.
+	pub fn chip_authenticated(&self) -> bool {
+		self.chip_authentication_public_key.is_some()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HandshakeState {
+	Start,
+	SetAt,
+	EncryptedNonce,
+	MapNonce,
+	KeyAgreement,
+	MutualAuthentication,
+	ReadCardSecurity,
+	SelectApplication,
+}
+
+/// Ephemeral key of the mapping chosen by the terminal
+#[derive(Clone)]
+enum MappingKey {
+	/// Private key SKMap,IFD of the Generic Mapping key agreement
+	Generic(BigUint),
+	/// Nonce t of Integrated Mapping, which is sent in the clear
+	Integrated(Vec<u8>),
+}
+
+/// The PACE handshake with DH or ECDH Generic or Integrated Mapping, performed as per
+/// ICAO 9303 MRTD v8 2021 Part 11, section 4.4 PACE
+///
+/// After mutual authentication, the eMRTD application is selected with
+/// secure messaging under the new session keys.
+///
+/// With Chip Authentication Mapping, EF.CardSecurity is read first, and the
+/// handshake only succeeds if the IC proves possession of the private key
+/// of its Chip Authentication public key, so that the IC is authenticated
+/// without a separate Chip Authentication. The verified public key is
+/// returned in [`PaceSession`]. The signature of EF.CardSecurity is left to
+/// passive authentication.
+///
+/// A '63CX' retry counter reported by the IC is a warning, except when the
+/// PIN is suspended or blocked, and is reported when the password is rejected
+/// with [`PaceError::AuthenticationFailed`].
+///
+/// DH public keys of the 2048-bit groups do not fit in a short command APDU,
+/// so these require a transport which supports extended length. Integrated
+/// Mapping is not supported over curves with p ≡ 1 mod 4, such as secp224r1.
+#[derive(Clone)]
+pub struct Handshake {
+	state: HandshakeState,
+	password_reference: u8,
+	oid: &'static [u8],
+	parameter_id: u8,
+	algorithm: KeyAlgorithm,
+	domain: Domain,
+	k_pi: Key,
+	mapping_key: MappingKey,
+	sk: BigUint,
+	nonce: Zeroizing<Vec<u8>>,
+	pk_ifd: Vec<u8>,
+	t_ic: Vec<u8>,
+	chip_authentication: bool,
+	pk_map_ic: Option<Element>,
+	ca_ic: Option<BigUint>,
+	pk_ic: Option<Vec<u8>>,
+	read_card_security: ReadFile,
+	retries: Option<u8>,
+	outer: Option<Session>,
+	select_application: bool,
+	session: Option<Session>,
+	last: ApduCommand<'static>,
+}
+
+impl Handshake {
+	pub fn new<R: RngCore + ?Sized>(password: impl Into<Password>, alg: &PaceAlg, sdp: &PaceSdp, rng: &mut R) -> Result<Self> {
+		let password = password.into();
+		let domain = match Domain::new(sdp) {
+			Some(domain) if domain.is_ecdh() == alg.is_ecdh() && (alg.mapping() == Mapping::Generic || domain.supports_integrated_mapping()) => domain,
+			_ => return Err(PaceError::UnsupportedParameters.into()),
+		};
+		let algorithm = alg.key_algorithm();
+
+		// Kπ = KDF(f(π), 3)
+		let k_pi = password.derive_key(algorithm);
+
+		// The ephemeral keys of the mapping and the key agreement
+		let mapping_key = match alg.mapping() {
+			Mapping::Generic => MappingKey::Generic(domain.random_private_key(rng)),
+			Mapping::Integrated => {
+				let mut t = vec![0; algorithm.key_len()];
+				rng.fill_bytes(&mut t);
+				MappingKey::Integrated(t)
+			}
+		};
+		let sk = domain.random_private_key(rng);
+
+		Ok(Self {
+			state: HandshakeState::Start,
+			password_reference: password.reference(),
+			oid: alg.descriptor,
+			parameter_id: sdp.id,
+			algorithm,
+			domain,
+			k_pi,
+			mapping_key,
+			sk,
+			nonce: Zeroizing::new(Vec::new()),
+			pk_ifd: Vec::new(),
+			t_ic: Vec::new(),
+			chip_authentication: alg.is_chip_authentication_mapping(),
+			pk_map_ic: None,
+			ca_ic: None,
+			pk_ic: None,
+			read_card_security: ReadFile::by_sfi(&EF_CARDSECURITY),
+			retries: None,
+			outer: None,
+			select_application: true,
+			session: None,
+			last: ApduCommand::new(0x00, 0x00, 0x00, 0x00),
+		})
+	}
+
+	/// Performs the handshake in the secure messaging channel of a previous
+	/// handshake, until the new session is established.
+	fn within(mut self, session: Session) -> Self {
+		self.outer = Some(session);
+		self
+	}
+
+	/// Completes the handshake without selecting the eMRTD application.
+	fn without_application(mut self) -> Self {
+		self.select_application = false;
+		self
+	}
+
+	fn transmit(&mut self, state: HandshakeState, command: ApduCommand<'static>) -> Result<Step<PaceSession>> {
+		self.state = state;
+		self.last = command.clone();
+		match &mut self.outer {
+			Some(outer) => Ok(Step::Transmit(outer.wrap_command(&command)?)),
+			None => Ok(Step::Transmit(command)),
+		}
+	}
+
+	/// Checks the status of the password reported by MSE:Set AT, where '63CX'
+	/// is a warning with the remaining retries of the password.
+	fn check_password(&mut self, res: &ApduResponse) -> Result<()> {
+		match res.trailer.status() {
+			Status::RetryCounter(0) => Err(PaceError::PasswordBlocked.into()),
+			// The PIN may only be used after PACE with the CAN
+			Status::RetryCounter(1) if self.password_reference == PASSWORD_PIN && self.outer.is_none() => Err(PaceError::PasswordSuspended.into()),
+			Status::RetryCounter(retries) => {
+				self.retries = Some(retries);
+				Ok(())
+			}
+			Status::FileDeactivated => Err(PaceError::PasswordDeactivated.into()),
+			_ => self.check(res),
+		}
+	}
+
+	/// Fails with the status word of the IC, unless the last command succeeded.
+	fn check(&self, res: &ApduResponse) -> Result<()> {
+		if res.trailer != TRAILER_OK {
+			return Err(Error::status(&self.last, res.trailer));
+		}
+		Ok(())
+	}
+
+	/// Decrypts the nonce z with Kπ in CBC mode with a zero IV.
+	fn decrypt_nonce(&self, z: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+		let s = match self.algorithm {
+			KeyAlgorithm::Tdes => tdes::decrypt(z, &self.k_pi)?,
+			_ => aes::decrypt(z, &self.k_pi, &aes::ZERO_IV)?,
+		};
+		Ok(Zeroizing::new(s))
+	}
+
+	/// Decrypts the chip authentication data CAIC = D(KSEnc, AIC), with AES in
+	/// CBC mode with IV = E(KSEnc, -1).
+	fn decrypt_chip_authentication_data(&self, a_ic: &[u8]) -> Result<BigUint> {
+		let session = self.session.as_ref().ok_or(Error::ProtocolState)?;
+		let iv = aes::encrypt(&[0xFF; aes::BLOCK_SIZE], session.ks_enc(), &aes::ZERO_IV)?;
+		let ca_ic = aes::decrypt_unpad(a_ic, session.ks_enc(), &iv).map_err(|_| PaceError::ChipAuthenticationFailed)?;
+		Ok(BigUint::from_bytes_be(&ca_ic))
+	}
+
+	/// Reads EF.CardSecurity with secure messaging, and verifies the chip
+	/// authentication data once it has been read.
+	fn read_card_security(&mut self, response: Option<ApduResponse>) -> Result<Step<PaceSession>> {
+		// An error sent without secure messaging aborts the session, whose SSC
+		// no longer matches that of the IC
+		if let Some(response) = &response {
+			if response.data.is_empty() && response.trailer != TRAILER_OK {
+				return Err(Error::status(&self.last, response.trailer));
+			}
+		}
+		let session = self.session.as_mut().ok_or(Error::ProtocolState)?;
+		let response = match response {
+			Some(response) => Some(session.unwrap_response(response)?),
+			None => None,
+		};
+		match self.read_card_security.step(response)? {
+			Step::Transmit(command) => {
+				let command = session.wrap_command(&command)?;
+				self.transmit(HandshakeState::ReadCardSecurity, command)
+			}
+			Step::Done(card_security) => {
+				self.pk_ic = Some(self.verify_chip_authentication(&card_security)?);
+				self.select_application()
+			}
+		}
+	}
+
+	/// The terminal verifies that PKMap,IC = KA(CAIC, PKIC, DIC) for the
+	/// Chip Authentication public key PKIC from EF.CardSecurity, and returns PKIC.
+	fn verify_chip_authentication(&self, card_security: &[u8]) -> Result<Vec<u8>> {
+		let ca_ic = self.ca_ic.as_ref().ok_or(Error::ProtocolState)?;
+		let pk_map_ic = self.pk_map_ic.as_ref().ok_or(Error::ProtocolState)?;
+		for public_key in chip_authentication_public_keys(card_security)? {
+			// Public keys over other domain parameters are skipped
+			let Ok(pk_ic) = self.domain.decode_public_key(&public_key) else {
+				continue;
+			};
+			if self.domain.agree(ca_ic, &pk_ic).is_ok_and(|pk| &pk == pk_map_ic) {
+				return Ok(public_key);
+			}
+		}
+		Err(PaceError::ChipAuthenticationFailed.into())
+	}
+
+	/// Selects the eMRTD application with secure messaging.
+	fn select_application(&mut self) -> Result<Step<PaceSession>> {
+		if !self.select_application {
+			let session = self.session.take().ok_or(Error::ProtocolState)?;
+			return self.done(session);
+		}
+		let session = self.session.as_mut().ok_or(Error::ProtocolState)?;
+		let command = session.wrap_command(&select_aid(AID_EMRTD))?;
+		self.transmit(HandshakeState::SelectApplication, command)
+	}
+
+	fn done(&mut self, session: Session) -> Result<Step<PaceSession>> {
+		Ok(Step::Done(PaceSession { session, chip_authentication_public_key: self.pk_ic.take() }))
+	}
+
+	/// Computes the authentication token T = MAC(KSMAC, PK) over the public key
+	/// data object of the other party.
+	fn authentication_token(&self, ks_mac: &Key, public_key: &[u8]) -> Result<Vec<u8>> {
+		let data = public_key_data_object(self.oid, self.domain.public_key_tag(), public_key)?;
+		match self.algorithm {
+			KeyAlgorithm::Tdes => des::mac(&data, ks_mac),
+			_ => aes::mac(&data, ks_mac),
+		}
+	}
+}
+
+impl Protocol for Handshake {
+	type Output = PaceSession;
+
+	fn step(&mut self, response: Option<ApduResponse>) -> Result<Step<PaceSession>> {
+		let response = match (&mut self.outer, response) {
+			// Errors may be sent without secure messaging
+			(Some(outer), Some(response)) if !response.data.is_empty() || response.trailer == TRAILER_OK => Some(outer.unwrap_response(response)?),
+			(_, response) => response,
+		};
+		match (self.state, response) {
+			(HandshakeState::Start, _) => {
+				// The terminal selects the PACE protocol, the password and the domain parameters
+				let command = mse_set_at_pace(self.oid, self.password_reference, Some(self.parameter_id))?;
+				self.transmit(HandshakeState::SetAt, command)
+			}
+			(HandshakeState::SetAt, Some(set_at_res)) => {
+				self.check_password(&set_at_res)?;
+
+				// 1) The IC randomly and uniformly chooses a nonce s, encrypts it to
+				// z = E(Kπ, s) and sends the ciphertext z to the terminal.
+				self.transmit(HandshakeState::EncryptedNonce, general_authenticate(&[], false)?)
+			}
+			(HandshakeState::EncryptedNonce, Some(nonce_res)) => {
+				self.check(&nonce_res)?;
+				let z = dynamic_authentication_data(&nonce_res, 0x80)?;
+
+				// 2) The terminal recovers the plaintext s = D(Kπ, z) with the help of the shared password π.
+				self.nonce = self.decrypt_nonce(&z)?;
+
+				// 3) Both the IC and the terminal perform the following steps:
+				// a) They compute the ephemeral domain parameters D = Map(DIC, s):
+				// the terminal sends its public mapping key PKMap,IFD, or its nonce t
+				let command = match &self.mapping_key {
+					MappingKey::Generic(sk_map) => general_authenticate(&[(0x81, &self.domain.public_key(sk_map)?)], false)?,
+					MappingKey::Integrated(t) => general_authenticate(&[(0x81, t)], false)?,
+				};
+				self.transmit(HandshakeState::MapNonce, command)
+			}
+			(HandshakeState::MapNonce, Some(map_res)) => {
+				self.check(&map_res)?;
+				self.domain = match &self.mapping_key {
+					MappingKey::Generic(sk_map) => {
+						// Generic Mapping with the shared secret H of an anonymous key agreement
+						let pk_map_ic = self.domain.decode_public_key(&dynamic_authentication_data(&map_res, 0x82)?)?;
+						let h = self.domain.agree(sk_map, &pk_map_ic)?;
+						let domain = self.domain.map_generic(&BigUint::from_bytes_be(&self.nonce), &h)?;
+						self.pk_map_ic = Some(pk_map_ic);
+						domain
+					}
+					// Integrated Mapping of the nonces s and t, where the IC responds
+					// with an empty dynamic authentication data template
+					MappingKey::Integrated(t) => self.domain.map_integrated(&self.nonce, t, self.algorithm)?,
+				};
+
+				// b) They perform an anonymous Diffie-Hellman key agreement based on the
+				// ephemeral domain parameters
+				self.pk_ifd = self.domain.public_key(&self.sk)?;
+				let command = general_authenticate(&[(0x83, &self.pk_ifd)], false)?;
+				self.transmit(HandshakeState::KeyAgreement, command)
+			}
+			(HandshakeState::KeyAgreement, Some(agreement_res)) => {
+				self.check(&agreement_res)?;
+				let pk_ic_data = dynamic_authentication_data(&agreement_res, 0x84)?;
+
+				// The terminal checks that PKDH,IC is different from PKDH,IFD
+				if pk_ic_data == self.pk_ifd {
+					return Err(PaceError::InvalidPublicKey.into());
+				}
+				let pk_ic = self.domain.decode_public_key(&pk_ic_data)?;
+				let k = self.domain.shared_secret(&self.domain.agree(&self.sk, &pk_ic)?);
+
+				// c) They derive session keys KSMAC = KDF(K, 2) and KSEnc = KDF(K, 1)
+				let ks_enc = kdf(&k, COUNTER_ENC, self.algorithm);
+				let ks_mac = kdf(&k, COUNTER_MAC, self.algorithm);
+
+				// d) They exchange and verify the authentication token TIFD = MAC(KSMAC, PKDH,IC)
+				let t_ifd = self.authentication_token(&ks_mac, &pk_ic_data)?;
+				self.t_ic = self.authentication_token(&ks_mac, &self.pk_ifd)?;
+				self.session = Some(Session::from_keys(ks_enc, ks_mac, 0));
+				self.transmit(HandshakeState::MutualAuthentication, general_authenticate(&[(0x85, &t_ifd)], true)?)
+			}
+			(HandshakeState::MutualAuthentication, Some(auth_res)) => {
+				// The IC rejects the authentication token of a wrong password
+				match auth_res.trailer.status() {
+					// Without a retry counter, the failed attempt is deducted from that of MSE:Set AT
+					Status::AuthenticationFailed => return Err(PaceError::AuthenticationFailed { retries: self.retries.map(|retries| retries.saturating_sub(1)) }.into()),
+					Status::RetryCounter(retries) => return Err(PaceError::AuthenticationFailed { retries: Some(retries) }.into()),
+					_ => self.check(&auth_res)?,
+				}
+
+				// The terminal verifies the authentication token TIC = MAC(KSMAC, PKDH,IFD)
+				let t_ic = dynamic_authentication_data(&auth_res, 0x86)?;
+				if t_ic.ct_ne(&self.t_ic).into() {
+					return Err(PaceError::InvalidAuthenticationToken.into());
+				}
+
+				// Secure messaging is started, replacing that of a previous handshake
+				self.outer = None;
+				if self.chip_authentication {
+					// The IC sends its encrypted chip authentication data AIC = E(KSEnc, CAIC),
+					// which the terminal decrypts and verifies against EF.CardSecurity
+					let a_ic = dynamic_authentication_data(&auth_res, 0x8A)?;
+					self.ca_ic = Some(self.decrypt_chip_authentication_data(&a_ic)?);
+					return self.read_card_security(None);
+				}
+				self.select_application()
+			}
+			(HandshakeState::ReadCardSecurity, Some(read_res)) => self.read_card_security(Some(read_res)),
+			(HandshakeState::SelectApplication, Some(select_res)) => {
+				let mut session = self.session.take().ok_or(Error::ProtocolState)?;
+				if select_res.data.is_empty() {
+					// Errors may be sent without secure messaging
+					self.check(&select_res)?;
+				}
+				let select_res = session.unwrap_response(select_res)?;
+				self.check(&select_res)?;
+				self.done(session)
+			}
+			(_, None) => Err(Error::ProtocolState),
+		}
+	}
+}
+
+impl std::fmt::Debug for Handshake {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		// The ephemeral private keys are not shown
+		f.debug_struct("Handshake").field("state", &self.state).field("domain", &self.domain.name()).field("algorithm", &self.algorithm).finish_non_exhaustive()
+	}
+}
+
+/// Returns the value of a data object in the dynamic authentication data
+/// template DO'7C' of a GENERAL AUTHENTICATE response.
+fn dynamic_authentication_data(res: &ApduResponse, tag: u8) -> Result<Vec<u8>> {
+	let template = tlv::Tlv::from_bytes(&res.data).map_err(|_| PaceError::InvalidResponse)?;
+	let data_objects = match template.value() {
+		tlv::Value::Constructed(data_objects) if Into::<u64>::into(template.tag().clone()) == 0x7C => data_objects,
+		_ => return Err(PaceError::InvalidResponse.into()),
+	};
+	match data_objects.iter().find(|data_object| Into::<u64>::into(data_object.tag().clone()) == tag as u64).map(|data_object| data_object.value()) {
+		Some(tlv::Value::Primitive(value)) => Ok(value.clone()),
+		_ => Err(Error::MissingDataObject(tag)),
+	}
+}
+
+/// Encodes the public key data object DO'7F49' with the protocol OID and the
+/// public value, as input to the authentication token.
+fn public_key_data_object(oid: &[u8], tag: u8, public_key: &[u8]) -> Result<Vec<u8>> {
+	let oid = tlv::Tlv::new(tlv::Tag::try_from(0x06)?, tlv::Value::Primitive(oid.to_vec()))?;
+	let public_key = tlv::Tlv::new(tlv::Tag::try_from(tag)?, tlv::Value::Primitive(public_key.to_vec()))?;
+	Ok(tlv::Tlv::new(tlv::Tag::try_from(0x7F49)?, tlv::Value::Constructed(vec![oid, public_key]))?.to_vec())
+}
\ No newline at end of file
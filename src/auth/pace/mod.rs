@@ -1,6 +1,16 @@
+use crate::crypto::KeyAlgorithm;
+use crate::crypto::dh::{Group, GROUP_RFC5114_1024_160, GROUP_RFC5114_2048_224, GROUP_RFC5114_2048_256};
+use crate::crypto::ec::{Curve, CURVE_SECP192R1, CURVE_SECP224R1, CURVE_SECP256R1, CURVE_SECP384R1, CURVE_SECP521R1, CURVE_BRAINPOOLP192R1, CURVE_BRAINPOOLP224R1, CURVE_BRAINPOOLP256R1, CURVE_BRAINPOOLP320R1, CURVE_BRAINPOOLP384R1, CURVE_BRAINPOOLP512R1};
 use sha1::{Sha1, Digest};
 use sha2::{Sha256, Sha512};
 
+mod card_security;
+mod domain;
+mod handshake;
+mod password;
+pub use handshake::{handshake, handshake_with_rng, handshake_async, handshake_with_rng_async, resume, resume_async, Handshake, PaceSession};
+pub use password::{Password, PASSWORD_MRZ, PASSWORD_CAN, PASSWORD_PIN, PASSWORD_PUK};
+
 pub struct HashAlg {
 	pub name: &'static str,
 	pub hash: for<'a> fn(&'a [u8]) -> Vec<u8>,
@@ -14,10 +24,46 @@ pub const HASHES: [HashAlg; 3] = [ HASH_SHA1, HASH_SHA256, HASH_SHA512 ];
 
 pub struct PaceAlg {
 	pub name: &'static str,
-	// pub keygen: for<'a> fn(&'a [u8]) -> Vec<u8>,
 	pub descriptor: &'static [u8],
 }
 
+/// Mapping of the nonce to ephemeral domain parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapping {
+	Generic,
+	Integrated,
+}
+
+impl PaceAlg {
+	pub fn mapping(&self) -> Mapping {
+		match self.descriptor[8] {
+			0x01 | 0x02 | 0x06 => Mapping::Generic,
+			_ => Mapping::Integrated,
+		}
+	}
+
+	/// Whether the key agreement is ECDH rather than DH
+	pub fn is_ecdh(&self) -> bool {
+		matches!(self.descriptor[8], 0x02 | 0x04 | 0x06)
+	}
+
+	/// Whether the IC is authenticated with Chip Authentication Mapping
+	/// during the handshake, which replaces a separate Chip Authentication
+	pub fn is_chip_authentication_mapping(&self) -> bool {
+		self.descriptor[8] == 0x06
+	}
+
+	/// The algorithm of Kπ and the session keys
+	pub fn key_algorithm(&self) -> KeyAlgorithm {
+		match self.descriptor[9] {
+			0x01 => KeyAlgorithm::Tdes,
+			0x02 => KeyAlgorithm::Aes128,
+			0x03 => KeyAlgorithm::Aes192,
+			_ => KeyAlgorithm::Aes256,
+		}
+	}
+}
+
 pub const PACEALG_DH_GM_3DES_CBC_CBC: PaceAlg = PaceAlg { name: "DH, Generic Mapping, 3DES-CBC-CBC", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x01, 0x01] };
 pub const PACEALG_DH_GM_AES_CMAC_128: PaceAlg = PaceAlg { name: "DH, Generic Mapping, AES-CMAC-128", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x01, 0x02] };
 pub const PACEALG_DH_GM_AES_CMAC_192: PaceAlg = PaceAlg { name: "DH, Generic Mapping, AES-CMAC-192", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x01, 0x03] };
@@ -34,7 +80,10 @@ pub const PACEALG_ECDH_IM_3DES_CBC_CBC: PaceAlg = PaceAlg { name: "ECDH, Integra
 pub const PACEALG_ECDH_IM_AES_CMAC_128: PaceAlg = PaceAlg { name: "ECDH, Integrated Mapping, AES-CMAC-128", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x04, 0x02] };
 pub const PACEALG_ECDH_IM_AES_CMAC_192: PaceAlg = PaceAlg { name: "ECDH, Integrated Mapping, AES-CMAC-192", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x04, 0x03] };
 pub const PACEALG_ECDH_IM_AES_CMAC_256: PaceAlg = PaceAlg { name: "ECDH, Integrated Mapping, AES-CMAC-256", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x04, 0x04] };
-pub const PACEALGS: [PaceAlg; 16] = [ PACEALG_DH_GM_3DES_CBC_CBC, PACEALG_DH_GM_AES_CMAC_128, PACEALG_DH_GM_AES_CMAC_192, PACEALG_DH_GM_AES_CMAC_256, PACEALG_ECDH_GM_3DES_CBC_CBC, PACEALG_ECDH_GM_AES_CMAC_128, PACEALG_ECDH_GM_AES_CMAC_192, PACEALG_ECDH_GM_AES_CMAC_256, PACEALG_DH_IM_3DES_CBC_CBC, PACEALG_DH_IM_AES_CMAC_128, PACEALG_DH_IM_AES_CMAC_192, PACEALG_DH_IM_AES_CMAC_256, PACEALG_ECDH_IM_3DES_CBC_CBC, PACEALG_ECDH_IM_AES_CMAC_128, PACEALG_ECDH_IM_AES_CMAC_192, PACEALG_ECDH_IM_AES_CMAC_256 ];
+pub const PACEALG_ECDH_CAM_AES_CMAC_128: PaceAlg = PaceAlg { name: "ECDH, Chip Authentication Mapping, AES-CMAC-128", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x06, 0x02] };
+pub const PACEALG_ECDH_CAM_AES_CMAC_192: PaceAlg = PaceAlg { name: "ECDH, Chip Authentication Mapping, AES-CMAC-192", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x06, 0x03] };
+pub const PACEALG_ECDH_CAM_AES_CMAC_256: PaceAlg = PaceAlg { name: "ECDH, Chip Authentication Mapping, AES-CMAC-256", descriptor: &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x04, 0x06, 0x04] };
+pub const PACEALGS: [PaceAlg; 19] = [ PACEALG_DH_GM_3DES_CBC_CBC, PACEALG_DH_GM_AES_CMAC_128, PACEALG_DH_GM_AES_CMAC_192, PACEALG_DH_GM_AES_CMAC_256, PACEALG_ECDH_GM_3DES_CBC_CBC, PACEALG_ECDH_GM_AES_CMAC_128, PACEALG_ECDH_GM_AES_CMAC_192, PACEALG_ECDH_GM_AES_CMAC_256, PACEALG_DH_IM_3DES_CBC_CBC, PACEALG_DH_IM_AES_CMAC_128, PACEALG_DH_IM_AES_CMAC_192, PACEALG_DH_IM_AES_CMAC_256, PACEALG_ECDH_IM_3DES_CBC_CBC, PACEALG_ECDH_IM_AES_CMAC_128, PACEALG_ECDH_IM_AES_CMAC_192, PACEALG_ECDH_IM_AES_CMAC_256, PACEALG_ECDH_CAM_AES_CMAC_128, PACEALG_ECDH_CAM_AES_CMAC_192, PACEALG_ECDH_CAM_AES_CMAC_256 ];
 
 pub struct PaceSdp {
 	pub id: u8,
@@ -55,5 +104,38 @@ pub const PACESDP_BRAINPOOLP224R1: PaceSdp = PaceSdp { id: 11, name: "BrainpoolP
 pub const PACESDP_BRAINPOOLP256R1: PaceSdp = PaceSdp { id: 13, name: "BrainpoolP256r1", size: 256 };
 pub const PACESDP_BRAINPOOLP320R1: PaceSdp = PaceSdp { id: 14, name: "BrainpoolP320r1", size: 320 };
 pub const PACESDP_BRAINPOOLP384R1: PaceSdp = PaceSdp { id: 16, name: "BrainpoolP384r1", size: 384 };
-pub const PACESDP_BRAINPOOLP521R1: PaceSdp = PaceSdp { id: 17, name: "BrainpoolP521r1", size: 521 };
-pub const PACESDPS: [PaceSdp; 14] = [ PACESDP_DH_GROUP22, PACESDP_DH_GROUP23, PACESDP_DH_GROUP24, PACESDP_SECP192R1, PACESDP_SECP224R1, PACESDP_SECP256R1, PACESDP_SECP384R1, PACESDP_SECP521R1, PACESDP_BRAINPOOLP192R1, PACESDP_BRAINPOOLP224R1, PACESDP_BRAINPOOLP256R1, PACESDP_BRAINPOOLP320R1, PACESDP_BRAINPOOLP384R1, PACESDP_BRAINPOOLP521R1 ];
\ No newline at end of file
+pub const PACESDP_BRAINPOOLP512R1: PaceSdp = PaceSdp { id: 17, name: "BrainpoolP512r1", size: 512 };
+/// Former name of [`PACESDP_BRAINPOOLP512R1`], the curve of parameter ID 17.
+#[deprecated(note = "there is no BrainpoolP521r1, use PACESDP_BRAINPOOLP512R1")]
+pub const PACESDP_BRAINPOOLP521R1: PaceSdp = PACESDP_BRAINPOOLP512R1;
+pub const PACESDPS: [PaceSdp; 14] = [ PACESDP_DH_GROUP22, PACESDP_DH_GROUP23, PACESDP_DH_GROUP24, PACESDP_SECP192R1, PACESDP_SECP224R1, PACESDP_SECP256R1, PACESDP_SECP384R1, PACESDP_SECP521R1, PACESDP_BRAINPOOLP192R1, PACESDP_BRAINPOOLP224R1, PACESDP_BRAINPOOLP256R1, PACESDP_BRAINPOOLP320R1, PACESDP_BRAINPOOLP384R1, PACESDP_BRAINPOOLP512R1 ];
+
+impl PaceSdp {
+	/// The MODP group of standardized domain parameters for DH
+	pub fn group(&self) -> Option<Group> {
+		match self.id {
+			0 => Some(GROUP_RFC5114_1024_160),
+			1 => Some(GROUP_RFC5114_2048_224),
+			2 => Some(GROUP_RFC5114_2048_256),
+			_ => None,
+		}
+	}
+
+	/// The elliptic curve of standardized domain parameters for ECDH
+	pub fn curve(&self) -> Option<Curve> {
+		match self.id {
+			8 => Some(CURVE_SECP192R1),
+			9 => Some(CURVE_BRAINPOOLP192R1),
+			10 => Some(CURVE_SECP224R1),
+			11 => Some(CURVE_BRAINPOOLP224R1),
+			12 => Some(CURVE_SECP256R1),
+			13 => Some(CURVE_BRAINPOOLP256R1),
+			14 => Some(CURVE_BRAINPOOLP320R1),
+			15 => Some(CURVE_SECP384R1),
+			16 => Some(CURVE_BRAINPOOLP384R1),
+			17 => Some(CURVE_BRAINPOOLP512R1),
+			18 => Some(CURVE_SECP521R1),
+			_ => None,
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,79 @@
+use crate::crypto::{kdf, Key, KeyAlgorithm, SecretKey, COUNTER_PASSWORD};
+use crate::mrz::borrowed::{Mrz, MrzData};
+
+/// Password reference of the MRZ in MSE:Set AT
+pub const PASSWORD_MRZ: u8 = 0x01;
+/// Password reference of the CAN in MSE:Set AT
+pub const PASSWORD_CAN: u8 = 0x02;
+/// Password reference of the PIN in MSE:Set AT
+pub const PASSWORD_PIN: u8 = 0x03;
+/// Password reference of the PUK in MSE:Set AT
+pub const PASSWORD_PUK: u8 = 0x04;
+
+/// A PACE password, encoded as f(π), as per
+/// ICAO 9303 MRTD v8 2021 Part 11, section 9.7.3 Encoding of Passwords
+///
+/// The MRZ is encoded as the SHA-1 hash of the MRZ information, and the
+/// CAN, PIN and PUK as the ISO/IEC 8859-1 characters of the password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Password {
+	Mrz(SecretKey),
+	/// Card access number, printed on the document
+	Can(SecretKey),
+	/// Secret PIN, which is suspended after the second failed attempt
+	Pin(SecretKey),
+	/// PIN unblock key
+	Puk(SecretKey),
+}
+
+impl Password {
+	pub fn mrz<'a, M: MrzData<'a>>(mrz: &M) -> Self {
+		Self::Mrz(mrz.derive_password())
+	}
+
+	pub fn can(can: &str) -> Self {
+		Self::Can(SecretKey::from(can.as_bytes()))
+	}
+
+	pub fn pin(pin: &str) -> Self {
+		Self::Pin(SecretKey::from(pin.as_bytes()))
+	}
+
+	pub fn puk(puk: &str) -> Self {
+		Self::Puk(SecretKey::from(puk.as_bytes()))
+	}
+
+	/// Password reference in MSE:Set AT
+	pub fn reference(&self) -> u8 {
+		match self {
+			Self::Mrz(_) => PASSWORD_MRZ,
+			Self::Can(_) => PASSWORD_CAN,
+			Self::Pin(_) => PASSWORD_PIN,
+			Self::Puk(_) => PASSWORD_PUK,
+		}
+	}
+
+	/// The encoded password f(π)
+	pub fn encoded(&self) -> &[u8] {
+		match self {
+			Self::Mrz(password) | Self::Can(password) | Self::Pin(password) | Self::Puk(password) => password,
+		}
+	}
+
+	/// Derives Kπ = KDF(f(π), 3)
+	pub fn derive_key(&self, algorithm: KeyAlgorithm) -> Key {
+		kdf(self.encoded(), COUNTER_PASSWORD, algorithm)
+	}
+}
+
+impl From<&Mrz<'_>> for Password {
+	fn from(mrz: &Mrz<'_>) -> Self {
+		Self::mrz(mrz)
+	}
+}
+
+impl From<&Password> for Password {
+	fn from(password: &Password) -> Self {
+		password.clone()
+	}
+}
\ No newline at end of file
@@ -0,0 +1,71 @@
+use crate::error::Result;
+use super::error::Error;
+use super::padding::{pad_block, unpad};
+use aes::{Aes128, Aes192, Aes256};
+use block_padding::NoPadding;
+use cbc::cipher::{BlockCipher, BlockEncryptMut, BlockDecryptMut, KeyInit, KeyIvInit};
+use cmac::{Cmac, Mac};
+
+pub const BLOCK_SIZE: usize = 16;
+pub const ZERO_IV: [u8; 16] = [0x00; 16];
+
+/// AES in CBC mode, where the key length selects AES-128, AES-192 or AES-256
+pub fn encrypt(input: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+	match key.len() {
+		16 => cbc_encrypt::<Aes128>(input, key, iv),
+		24 => cbc_encrypt::<Aes192>(input, key, iv),
+		32 => cbc_encrypt::<Aes256>(input, key, iv),
+		_ => Err(Error::InvalidLength.into()),
+	}
+}
+
+pub fn decrypt(input: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+	match key.len() {
+		16 => cbc_decrypt::<Aes128>(input, key, iv),
+		24 => cbc_decrypt::<Aes192>(input, key, iv),
+		32 => cbc_decrypt::<Aes256>(input, key, iv),
+		_ => Err(Error::InvalidLength.into()),
+	}
+}
+
+pub fn encrypt_pad(input: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+	let input_padded = pad_block(input, BLOCK_SIZE);
+	encrypt(&input_padded, key, iv)
+}
+
+pub fn decrypt_unpad(input: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+	let decrypted = decrypt(input, key, iv)?;
+	Ok(unpad(decrypted)?)
+}
+
+/// AES-CMAC as per NIST SP 800-38B, truncated to 8 bytes
+pub fn mac(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+	let mut mac = match key.len() {
+		16 => cmac::<Cmac<Aes128>>(data, key)?,
+		24 => cmac::<Cmac<Aes192>>(data, key)?,
+		32 => cmac::<Cmac<Aes256>>(data, key)?,
+		_ => return Err(Error::InvalidLength.into()),
+	};
+	mac.truncate(8);
+	Ok(mac)
+}
+
+fn cbc_encrypt<C: BlockCipher + BlockEncryptMut + KeyInit>(input: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+	let mut output = input.to_vec();
+	cbc::Encryptor::<C>::new_from_slices(key, iv).map_err(|_| Error::InvalidLength)?
+		.encrypt_padded_mut::<NoPadding>(&mut output, input.len()).map_err(|_| Error::InvalidLength)?;
+	Ok(output)
+}
+
+fn cbc_decrypt<C: BlockCipher + BlockDecryptMut + KeyInit>(input: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+	let mut output = input.to_vec();
+	cbc::Decryptor::<C>::new_from_slices(key, iv).map_err(|_| Error::InvalidLength)?
+		.decrypt_padded_mut::<NoPadding>(&mut output).map_err(|_| Error::InvalidLength)?;
+	Ok(output)
+}
+
+fn cmac<M: Mac + KeyInit>(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+	let mut mac = <M as Mac>::new_from_slice(key).map_err(|_| Error::InvalidLength)?;
+	mac.update(data);
+	Ok(mac.finalize().into_bytes().to_vec())
+}
\ No newline at end of file
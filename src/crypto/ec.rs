@@ -0,0 +1,555 @@
+use num_bigint::BigUint;
+use rand::RngCore;
+use subtle::{Choice, ConditionallySelectable};
+use zeroize::Zeroizing;
+
+/// Domain parameters of an elliptic curve y² = x³ + ax + b over the prime
+/// field of order p, with base point (x, y) of prime order n and cofactor 1.
+///
+/// All values are hex encoded, as in the standards they are taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Curve {
+	pub name: &'static str,
+	pub p: &'static str,
+	pub a: &'static str,
+	pub b: &'static str,
+	pub x: &'static str,
+	pub y: &'static str,
+	pub n: &'static str,
+}
+
+pub const CURVE_SECP192R1: Curve = Curve {
+	name: "NIST P-192 (secp192r1)",
+	p: "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFFFFFFFFFFFF",
+	a: "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFFFFFFFFFFFC",
+	b: "64210519E59C80E70FA7E9AB72243049FEB8DEECC146B9B1",
+	x: "188DA80EB03090F67CBF20EB43A18800F4FF0AFD82FF1012",
+	y: "07192B95FFC8DA78631011ED6B24CDD573F977A11E794811",
+	n: "FFFFFFFFFFFFFFFFFFFFFFFF99DEF836146BC9B1B4D22831",
+};
+pub const CURVE_SECP224R1: Curve = Curve {
+	name: "NIST P-224 (secp224r1)",
+	p: "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF000000000000000000000001",
+	a: "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFFFFFFFFFFFFFFFFFFFE",
+	b: "B4050A850C04B3ABF54132565044B0B7D7BFD8BA270B39432355FFB4",
+	x: "B70E0CBD6BB4BF7F321390B94A03C1D356C21122343280D6115C1D21",
+	y: "BD376388B5F723FB4C22DFE6CD4375A05A07476444D5819985007E34",
+	n: "FFFFFFFFFFFFFFFFFFFFFFFFFFFF16A2E0B8F03E13DD29455C5C2A3D",
+};
+pub const CURVE_SECP256R1: Curve = Curve {
+	name: "NIST P-256 (secp256r1)",
+	p: "FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFF",
+	a: "FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFC",
+	b: "5AC635D8AA3A93E7B3EBBD55769886BC651D06B0CC53B0F63BCE3C3E27D2604B",
+	x: "6B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296",
+	y: "4FE342E2FE1A7F9B8EE7EB4A7C0F9E162BCE33576B315ECECBB6406837BF51F5",
+	n: "FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551",
+};
+pub const CURVE_SECP384R1: Curve = Curve {
+	name: "NIST P-384 (secp384r1)",
+	p: "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFFFF0000000000000000FFFFFFFF",
+	a: "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFFFF0000000000000000FFFFFFFC",
+	b: "B3312FA7E23EE7E4988E056BE3F82D19181D9C6EFE8141120314088F5013875AC656398D8A2ED19D2A85C8EDD3EC2AEF",
+	x: "AA87CA22BE8B05378EB1C71EF320AD746E1D3B628BA79B9859F741E082542A385502F25DBF55296C3A545E3872760AB7",
+	y: "3617DE4A96262C6F5D9E98BF9292DC29F8F41DBD289A147CE9DA3113B5F0B8C00A60B1CE1D7E819D7A431D7C90EA0E5F",
+	n: "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFC7634D81F4372DDF581A0DB248B0A77AECEC196ACCC52973",
+};
+pub const CURVE_SECP521R1: Curve = Curve {
+	name: "NIST P-521 (secp521r1)",
+	p: "01FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+	a: "01FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFC",
+	b: "0051953EB9618E1C9A1F929A21A0B68540EEA2DA725B99B315F3B8B489918EF109E156193951EC7E937B1652C0BD3BB1BF073573DF883D2C34F1EF451FD46B503F00",
+	x: "00C6858E06B70404E9CD9E3ECB662395B4429C648139053FB521F828AF606B4D3DBAA14B5E77EFE75928FE1DC127A2FFA8DE3348B3C1856A429BF97E7E31C2E5BD66",
+	y: "011839296A789A3BC0045C8A5FB42C7D1BD998F54449579B446817AFBD17273E662C97EE72995EF42640C550B9013FAD0761353C7086A272C24088BE94769FD16650",
+	n: "01FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFA51868783BF2F966B7FCC0148F709A5D03BB5C9B8899C47AEBB6FB71E91386409",
+};
+pub const CURVE_BRAINPOOLP192R1: Curve = Curve {
+	name: "BrainpoolP192r1",
+	p: "C302F41D932A36CDA7A3463093D18DB78FCE476DE1A86297",
+	a: "6A91174076B1E0E19C39C031FE8685C1CAE040E5C69A28EF",
+	b: "469A28EF7C28CCA3DC721D044F4496BCCA7EF4146FBF25C9",
+	x: "C0A0647EAAB6A48753B033C56CB0F0900A2F5C4853375FD6",
+	y: "14B690866ABD5BB88B5F4828C1490002E6773FA2FA299B8F",
+	n: "C302F41D932A36CDA7A3462F9E9E916B5BE8F1029AC4ACC1",
+};
+pub const CURVE_BRAINPOOLP224R1: Curve = Curve {
+	name: "BrainpoolP224r1",
+	p: "D7C134AA264366862A18302575D1D787B09F075797DA89F57EC8C0FF",
+	a: "68A5E62CA9CE6C1C299803A6C1530B514E182AD8B0042A59CAD29F43",
+	b: "2580F63CCFE44138870713B1A92369E33E2135D266DBB372386C400B",
+	x: "0D9029AD2C7E5CF4340823B2A87DC68C9E4CE3174C1E6EFDEE12C07D",
+	y: "58AA56F772C0726F24C6B89E4ECDAC24354B9E99CAA3F6D3761402CD",
+	n: "D7C134AA264366862A18302575D0FB98D116BC4B6DDEBCA3A5A7939F",
+};
+pub const CURVE_BRAINPOOLP256R1: Curve = Curve {
+	name: "BrainpoolP256r1",
+	p: "A9FB57DBA1EEA9BC3E660A909D838D726E3BF623D52620282013481D1F6E5377",
+	a: "7D5A0975FC2C3057EEF67530417AFFE7FB8055C126DC5C6CE94A4B44F330B5D9",
+	b: "26DC5C6CE94A4B44F330B5D9BBD77CBF958416295CF7E1CE6BCCDC18FF8C07B6",
+	x: "8BD2AEB9CB7E57CB2C4B482FFC81B7AFB9DE27E1E3BD23C23A4453BD9ACE3262",
+	y: "547EF835C3DAC4FD97F8461A14611DC9C27745132DED8E545C1D54C72F046997",
+	n: "A9FB57DBA1EEA9BC3E660A909D838D718C397AA3B561A6F7901E0E82974856A7",
+};
+pub const CURVE_BRAINPOOLP320R1: Curve = Curve {
+	name: "BrainpoolP320r1",
+	p: "D35E472036BC4FB7E13C785ED201E065F98FCFA6F6F40DEF4F92B9EC7893EC28FCD412B1F1B32E27",
+	a: "3EE30B568FBAB0F883CCEBD46D3F3BB8A2A73513F5EB79DA66190EB085FFA9F492F375A97D860EB4",
+	b: "520883949DFDBC42D3AD198640688A6FE13F41349554B49ACC31DCCD884539816F5EB4AC8FB1F1A6",
+	x: "43BD7E9AFB53D8B85289BCC48EE5BFE6F20137D10A087EB6E7871E2A10A599C710AF8D0D39E20611",
+	y: "14FDD05545EC1CC8AB4093247F77275E0743FFED117182EAA9C77877AAAC6AC7D35245D1692E8EE1",
+	n: "D35E472036BC4FB7E13C785ED201E065F98FCFA5B68F12A32D482EC7EE8658E98691555B44C59311",
+};
+pub const CURVE_BRAINPOOLP384R1: Curve = Curve {
+	name: "BrainpoolP384r1",
+	p: "8CB91E82A3386D280F5D6F7E50E641DF152F7109ED5456B412B1DA197FB71123ACD3A729901D1A71874700133107EC53",
+	a: "7BC382C63D8C150C3C72080ACE05AFA0C2BEA28E4FB22787139165EFBA91F90F8AA5814A503AD4EB04A8C7DD22CE2826",
+	b: "04A8C7DD22CE28268B39B55416F0447C2FB77DE107DCD2A62E880EA53EEB62D57CB4390295DBC9943AB78696FA504C11",
+	x: "1D1C64F068CF45FFA2A63A81B7C13F6B8847A3E77EF14FE3DB7FCAFE0CBD10E8E826E03436D646AAEF87B2E247D4AF1E",
+	y: "8ABE1D7520F9C2A45CB1EB8E95CFD55262B70B29FEEC5864E19C054FF99129280E4646217791811142820341263C5315",
+	n: "8CB91E82A3386D280F5D6F7E50E641DF152F7109ED5456B31F166E6CAC0425A7CF3AB6AF6B7FC3103B883202E9046565",
+};
+pub const CURVE_BRAINPOOLP512R1: Curve = Curve {
+	name: "BrainpoolP512r1",
+	p: "AADD9DB8DBE9C48B3FD4E6AE33C9FC07CB308DB3B3C9D20ED6639CCA703308717D4D9B009BC66842AECDA12AE6A380E62881FF2F2D82C68528AA6056583A48F3",
+	a: "7830A3318B603B89E2327145AC234CC594CBDD8D3DF91610A83441CAEA9863BC2DED5D5AA8253AA10A2EF1C98B9AC8B57F1117A72BF2C7B9E7C1AC4D77FC94CA",
+	b: "3DF91610A83441CAEA9863BC2DED5D5AA8253AA10A2EF1C98B9AC8B57F1117A72BF2C7B9E7C1AC4D77FC94CADC083E67984050B75EBAE5DD2809BD638016F723",
+	x: "81AEE4BDD82ED9645A21322E9C4C6A9385ED9F70B5D916C1B43B62EEF4D0098EFF3B1F78E2D0D48D50D1687B93B97D5F7C6D5047406A5E688B352209BCB9F822",
+	y: "7DDE385D566332ECC0EABFA9CF7822FDF209F70024A57B1AA000C55B881F8111B2DCDE494A5F485E5BCA4BD88A2763AED1CA2B2FA8F0540678CD1E0F3AD80892",
+	n: "AADD9DB8DBE9C48B3FD4E6AE33C9FC07CB308DB3B3C9D20ED6639CCA70330870553E5C414CA92619418661197FAC10471DB1D381085DDADDB58796829CA90069",
+};
+
+/// A point on a curve in affine coordinates. The point at infinity is
+/// represented as `None` where it can occur.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Point {
+	pub x: BigUint,
+	pub y: BigUint,
+}
+
+impl Curve {
+	pub fn prime(&self) -> BigUint {
+		hex(self.p)
+	}
+
+	pub fn order(&self) -> BigUint {
+		hex(self.n)
+	}
+
+	pub fn generator(&self) -> Point {
+		Point { x: hex(self.x), y: hex(self.y) }
+	}
+
+	/// Length of an encoded field element in bytes
+	pub fn field_len(&self) -> usize {
+		self.p.len() / 2
+	}
+
+	/// Returns true if the point satisfies the curve equation.
+	pub fn contains(&self, point: &Point) -> bool {
+		let p = self.prime();
+		if point.x >= p || point.y >= p {
+			return false;
+		}
+		let lhs = &point.y * &point.y % &p;
+		let rhs = (&point.x * &point.x * &point.x + hex(self.a) * &point.x + hex(self.b)) % &p;
+		lhs == rhs
+	}
+
+	/// Adds two points, which returns `None` for the point at infinity.
+	pub fn add(&self, a: &Point, b: &Point) -> Option<Point> {
+		let field = Field::new(self);
+		field.to_affine(&field.add(&field.projective(a), &field.projective(b)))
+	}
+
+	/// Multiplies a point by a scalar, which returns `None` for the point at infinity.
+	///
+	/// The scalar is reduced modulo n and processed with a Montgomery ladder
+	/// over a fixed number of bits, with complete addition formulas and
+	/// conditional swaps, so the sequence of field operations does not depend
+	/// on the scalar.
+	pub fn mul(&self, k: &BigUint, point: &Point) -> Option<Point> {
+		let field = Field::new(self);
+		let n = self.order();
+		let bits = n.bits() as usize;
+
+		// Add n or 2n to the scalar, whichever sets bit `bits`, which hides the
+		// length of the scalar without changing the result
+		let k = Zeroizing::new(limbs(&(k % &n)));
+		let n = limbs(&n);
+		let k_n = Zeroizing::new(add_limbs(&k, &n));
+		let k_2n = Zeroizing::new(add_limbs(&k_n, &n));
+		let k = Zeroizing::new(select_limbs(&k_2n, &k_n, Choice::from(bit(&k_n, bits))));
+
+		let mut r0 = field.identity();
+		let mut r1 = field.projective(point);
+		let mut swap = Choice::from(0);
+		for i in (0..=bits).rev() {
+			let bit = Choice::from(bit(&k, i));
+			ProjectivePoint::conditional_swap(&mut r0, &mut r1, swap ^ bit);
+			swap = bit;
+			r1 = field.add(&r0, &r1);
+			r0 = field.add(&r0, &r0);
+		}
+		ProjectivePoint::conditional_swap(&mut r0, &mut r1, swap);
+		field.to_affine(&r0)
+	}
+
+	/// Encodes a field element, such as the x-coordinate of a shared secret,
+	/// with leading zeros as per BSI TR-03111.
+	pub fn encode_field_element(&self, value: &BigUint) -> Vec<u8> {
+		let bytes = value.to_bytes_be();
+		let mut output = vec![0; self.field_len().saturating_sub(bytes.len())];
+		output.extend_from_slice(&bytes);
+		output
+	}
+
+	/// Encodes a point in uncompressed form 04 || x || y.
+	pub fn encode_point(&self, point: &Point) -> Vec<u8> {
+		let mut output = Vec::with_capacity(1 + 2 * self.field_len());
+		output.push(0x04);
+		output.extend_from_slice(&self.encode_field_element(&point.x));
+		output.extend_from_slice(&self.encode_field_element(&point.y));
+		output
+	}
+
+	/// Decodes a point in uncompressed form, which must lie on the curve.
+	pub fn decode_point(&self, data: &[u8]) -> Option<Point> {
+		let len = self.field_len();
+		if data.len() != 1 + 2 * len || data[0] != 0x04 {
+			return None;
+		}
+		let point = Point { x: BigUint::from_bytes_be(&data[1..1+len]), y: BigUint::from_bytes_be(&data[1+len..]) };
+		self.contains(&point).then_some(point)
+	}
+
+	/// Encodes a field element to a point on the curve, as per ICAO 9303 MRTD
+	/// v8 2021 Part 11, section 4.4.3.3.2 Integrated Mapping, which is the
+	/// simplified SWU algorithm for curves with p ≡ 3 mod 4.
+	///
+	/// Returns `None` for other curves, or if the encoding is undefined for t.
+	pub fn map_to_point(&self, t: &BigUint) -> Option<Point> {
+		let p = self.prime();
+		if p.bit(0) && p.bit(1) {
+			let field = Field::new(self);
+			let b = field.element(&hex(self.b));
+			let t = field.element(t);
+			let inv = |value: &Limbs| (!field.is_zero(value)).then(|| field.invert(value));
+
+			// 1. α = -t^2 mod p
+			let alpha = field.sub(&field.zero(), &field.mul(&t, &t));
+			// 2. X2 = -ba^-1 (1 + (α + α^2)^-1) mod p
+			let x2 = field.sub(&field.zero(), &field.mul(&field.mul(&b, &inv(&field.a)?), &field.add_elements(&field.one, &inv(&field.add_elements(&alpha, &field.mul(&alpha, &alpha)))?)));
+			// 3. X3 = α X2 mod p
+			let x3 = field.mul(&alpha, &x2);
+			// 4. h2 = (X2)^3 + a X2 + b mod p
+			let h2 = field.add_elements(&field.add_elements(&field.mul(&field.mul(&x2, &x2), &x2), &field.mul(&field.a, &x2)), &b);
+			// 5. h3 = (X3)^3 + a X3 + b mod p (implied by the choice of y below)
+			// 6. U = t^3 h2 mod p
+			let u = field.mul(&field.mul(&field.mul(&t, &t), &t), &h2);
+			// 7. A = (h2)^(p - 1 - (p + 1) / 4) mod p
+			let a = field.pow(&h2, &(&p - 1u8 - (&p + 1u8) / 4u8));
+			// 8. If A^2 h2 mod p = 1 define (x, y) = (X2, A h2 mod p)
+			// 9. Otherwise define (x, y) = (X3, A U mod p)
+			let is_square = field.eq(&field.mul(&field.mul(&a, &a), &h2), &field.one);
+			let x = select_limbs(&x3, &x2, is_square);
+			let y = select_limbs(&field.mul(&a, &u), &field.mul(&a, &h2), is_square);
+			let point = Point { x: field.value(&x), y: field.value(&y) };
+			return self.contains(&point).then_some(point);
+		}
+		None
+	}
+
+	/// Generates a private key in the range [1, n-1].
+	pub fn random_scalar<R: RngCore + ?Sized>(&self, rng: &mut R) -> BigUint {
+		random_below(&self.order(), rng)
+	}
+}
+
+/// Generates a uniformly random number in the range [1, n-1] by rejection sampling.
+pub(crate) fn random_below<R: RngCore + ?Sized>(n: &BigUint, rng: &mut R) -> BigUint {
+	let bits = n.bits() as usize;
+	let mut bytes = Zeroizing::new(vec![0; bits.div_ceil(8)]);
+	loop {
+		rng.fill_bytes(&mut bytes);
+		if !bits.is_multiple_of(8) {
+			bytes[0] &= 0xFF >> (8 - bits % 8);
+		}
+		let k = BigUint::from_bytes_be(&bytes);
+		if k != BigUint::ZERO && &k < n {
+			return k;
+		}
+	}
+}
+
+fn hex(value: &str) -> BigUint {
+	BigUint::parse_bytes(value.as_bytes(), 16).expect("Invalid curve parameter")
+}
+
+/// The maximum number of 64-bit limbs of a field element or scalar, which is
+/// enough for P-521 and for scalars up to 2n.
+const LIMBS: usize = 9;
+
+/// An unsigned integer of 64-bit limbs in little endian order.
+type Limbs = [u64; LIMBS];
+
+fn limbs(value: &BigUint) -> Limbs {
+	let mut output = [0; LIMBS];
+	for (limb, digit) in output.iter_mut().zip(value.iter_u64_digits()) {
+		*limb = digit;
+	}
+	output
+}
+
+fn bit(value: &Limbs, i: usize) -> u8 {
+	(value[i / 64] >> (i % 64)) as u8 & 1
+}
+
+fn add_limbs(a: &Limbs, b: &Limbs) -> Limbs {
+	let mut output = [0; LIMBS];
+	let mut carry = 0;
+	for i in 0..LIMBS {
+		let sum = a[i] as u128 + b[i] as u128 + carry;
+		output[i] = sum as u64;
+		carry = sum >> 64;
+	}
+	output
+}
+
+/// Returns `b` if `choice` is set and `a` otherwise.
+fn select_limbs(a: &Limbs, b: &Limbs, choice: Choice) -> Limbs {
+	let mut output = [0; LIMBS];
+	for i in 0..LIMBS {
+		output[i] = u64::conditional_select(&a[i], &b[i], choice);
+	}
+	output
+}
+
+/// A point in projective coordinates (X, Y, Z), which is (X/Z, Y/Z) in
+/// affine coordinates, and the point at infinity for Z = 0. Coordinates are
+/// field elements in Montgomery form.
+#[derive(Debug, Clone, Copy)]
+struct ProjectivePoint {
+	x: Limbs,
+	y: Limbs,
+	z: Limbs,
+}
+
+impl ProjectivePoint {
+	fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+		for i in 0..LIMBS {
+			u64::conditional_swap(&mut a.x[i], &mut b.x[i], choice);
+			u64::conditional_swap(&mut a.y[i], &mut b.y[i], choice);
+			u64::conditional_swap(&mut a.z[i], &mut b.z[i], choice);
+		}
+	}
+}
+
+/// Arithmetic in the prime field of a curve, on elements in Montgomery form
+/// aR mod p with R = 2^(64 len). Operations process all limbs and reduce with
+/// masks instead of branches, so their timing does not depend on the values.
+struct Field {
+	p: Limbs,
+	/// The number of limbs of p
+	len: usize,
+	/// -p^-1 mod 2^64
+	p_inv: u64,
+	/// R^2 mod p
+	r2: Limbs,
+	/// R mod p, which is 1 in Montgomery form
+	one: Limbs,
+	a: Limbs,
+	/// 3b, as used by the addition formulas
+	b3: Limbs,
+}
+
+impl Field {
+	fn new(curve: &Curve) -> Self {
+		let p = curve.prime();
+		let len = p.bits().div_ceil(64) as usize;
+		let r = (BigUint::from(1u8) << (64 * len)) % &p;
+
+		// Newton iteration doubles the number of correct low bits of p^-1
+		let p0 = limbs(&p)[0];
+		let mut inv = 1u64;
+		for _ in 0..6 {
+			inv = inv.wrapping_mul(2u64.wrapping_sub(p0.wrapping_mul(inv)));
+		}
+
+		let mut field = Self { p: limbs(&p), len, p_inv: inv.wrapping_neg(), r2: limbs(&(&r * &r % &p)), one: limbs(&r), a: [0; LIMBS], b3: [0; LIMBS] };
+		field.a = field.element(&hex(curve.a));
+		field.b3 = field.element(&(3u8 * hex(curve.b)));
+		field
+	}
+
+	fn zero(&self) -> Limbs {
+		[0; LIMBS]
+	}
+
+	/// Converts a value to Montgomery form.
+	fn element(&self, value: &BigUint) -> Limbs {
+		self.mul(&limbs(&(value % self.value_of(&self.p))), &self.r2)
+	}
+
+	/// Converts an element from Montgomery form.
+	fn value(&self, element: &Limbs) -> BigUint {
+		let mut one = [0; LIMBS];
+		one[0] = 1;
+		self.value_of(&self.mul(element, &one))
+	}
+
+	fn value_of(&self, limbs: &Limbs) -> BigUint {
+		BigUint::from_bytes_le(&limbs.iter().flat_map(|limb| limb.to_le_bytes()).collect::<Vec<_>>())
+	}
+
+	fn eq(&self, a: &Limbs, b: &Limbs) -> Choice {
+		let mut diff = 0;
+		for i in 0..self.len {
+			diff |= a[i] ^ b[i];
+		}
+		Choice::from((diff == 0) as u8)
+	}
+
+	fn is_zero(&self, a: &Limbs) -> bool {
+		self.eq(a, &self.zero()).into()
+	}
+
+	/// Subtracts p from the value `high` || `value` if it is at least p, for
+	/// values below 2p.
+	fn reduce(&self, value: &Limbs, high: u64) -> Limbs {
+		let mut output = [0; LIMBS];
+		let mut borrow = 0;
+		for i in 0..self.len {
+			let diff = (value[i] as u128).wrapping_sub(self.p[i] as u128 + borrow);
+			output[i] = diff as u64;
+			borrow = diff >> 127;
+		}
+		let (_, underflow) = high.overflowing_sub(borrow as u64);
+		select_limbs(&output, value, Choice::from(underflow as u8))
+	}
+
+	fn add_elements(&self, a: &Limbs, b: &Limbs) -> Limbs {
+		let mut output = [0; LIMBS];
+		let mut carry = 0;
+		for i in 0..self.len {
+			let sum = a[i] as u128 + b[i] as u128 + carry;
+			output[i] = sum as u64;
+			carry = sum >> 64;
+		}
+		self.reduce(&output, carry as u64)
+	}
+
+	fn sub(&self, a: &Limbs, b: &Limbs) -> Limbs {
+		let mut output = [0; LIMBS];
+		let mut borrow = 0;
+		for i in 0..self.len {
+			let diff = (a[i] as u128).wrapping_sub(b[i] as u128 + borrow);
+			output[i] = diff as u64;
+			borrow = diff >> 127;
+		}
+
+		// Add p back if the subtraction underflowed
+		let mask = (borrow as u64).wrapping_neg();
+		let mut carry = 0;
+		for (limb, p) in output.iter_mut().zip(&self.p).take(self.len) {
+			let sum = *limb as u128 + (p & mask) as u128 + carry;
+			*limb = sum as u64;
+			carry = sum >> 64;
+		}
+		output
+	}
+
+	/// Montgomery multiplication abR^-1 mod p (CIOS method).
+	fn mul(&self, a: &Limbs, b: &Limbs) -> Limbs {
+		let len = self.len;
+		let mut t = [0u64; LIMBS + 2];
+		for &b_i in &b[..len] {
+			let mut carry = 0;
+			for j in 0..len {
+				let product = t[j] as u128 + a[j] as u128 * b_i as u128 + carry;
+				t[j] = product as u64;
+				carry = product >> 64;
+			}
+			let sum = t[len] as u128 + carry;
+			t[len] = sum as u64;
+			t[len + 1] = (sum >> 64) as u64;
+
+			let m = t[0].wrapping_mul(self.p_inv);
+			let mut carry = (t[0] as u128 + m as u128 * self.p[0] as u128) >> 64;
+			for j in 1..len {
+				let product = t[j] as u128 + m as u128 * self.p[j] as u128 + carry;
+				t[j - 1] = product as u64;
+				carry = product >> 64;
+			}
+			let sum = t[len] as u128 + carry;
+			t[len - 1] = sum as u64;
+			t[len] = t[len + 1] + (sum >> 64) as u64;
+		}
+		let mut output = [0; LIMBS];
+		output[..len].copy_from_slice(&t[..len]);
+		self.reduce(&output, t[len])
+	}
+
+	/// Raises an element to a public exponent.
+	fn pow(&self, a: &Limbs, exponent: &BigUint) -> Limbs {
+		let mut output = self.one;
+		for i in (0..exponent.bits()).rev() {
+			output = self.mul(&output, &output);
+			if exponent.bit(i) {
+				output = self.mul(&output, a);
+			}
+		}
+		output
+	}
+
+	/// a^-1 = a^(p-2), since p is prime
+	fn invert(&self, a: &Limbs) -> Limbs {
+		self.pow(a, &(self.value_of(&self.p) - 2u8))
+	}
+
+	fn identity(&self) -> ProjectivePoint {
+		ProjectivePoint { x: self.zero(), y: self.one, z: self.zero() }
+	}
+
+	fn projective(&self, point: &Point) -> ProjectivePoint {
+		ProjectivePoint { x: self.element(&point.x), y: self.element(&point.y), z: self.one }
+	}
+
+	/// Complete addition of any two points, including doubling and the point
+	/// at infinity, as per Renes, Costello and Batina, "Complete addition
+	/// formulas for prime order elliptic curves", Algorithm 1.
+	fn add(&self, p: &ProjectivePoint, q: &ProjectivePoint) -> ProjectivePoint {
+		let (x1, y1, z1) = (&p.x, &p.y, &p.z);
+		let (x2, y2, z2) = (&q.x, &q.y, &q.z);
+		let t0 = self.mul(x1, x2);
+		let t1 = self.mul(y1, y2);
+		let t2 = self.mul(z1, z2);
+		let t3 = self.mul(&self.add_elements(x1, y1), &self.add_elements(x2, y2));
+		let t3 = self.sub(&t3, &self.add_elements(&t0, &t1));
+		let t4 = self.mul(&self.add_elements(x1, z1), &self.add_elements(x2, z2));
+		let t4 = self.sub(&t4, &self.add_elements(&t0, &t2));
+		let t5 = self.mul(&self.add_elements(y1, z1), &self.add_elements(y2, z2));
+		let t5 = self.sub(&t5, &self.add_elements(&t1, &t2));
+		let z3 = self.add_elements(&self.mul(&self.b3, &t2), &self.mul(&self.a, &t4));
+		let x3 = self.sub(&t1, &z3);
+		let z3 = self.add_elements(&t1, &z3);
+		let y3 = self.mul(&x3, &z3);
+		let t1 = self.add_elements(&self.add_elements(&t0, &t0), &t0);
+		let t2 = self.mul(&self.a, &t2);
+		let t4 = self.mul(&self.b3, &t4);
+		let t1 = self.add_elements(&t1, &t2);
+		let t2 = self.mul(&self.a, &self.sub(&t0, &t2));
+		let t4 = self.add_elements(&t4, &t2);
+		let y3 = self.add_elements(&y3, &self.mul(&t1, &t4));
+		let x3 = self.sub(&self.mul(&t3, &x3), &self.mul(&t5, &t4));
+		let z3 = self.add_elements(&self.mul(&t5, &z3), &self.mul(&t3, &t1));
+		ProjectivePoint { x: x3, y: y3, z: z3 }
+	}
+
+	fn to_affine(&self, point: &ProjectivePoint) -> Option<Point> {
+		if self.is_zero(&point.z) {
+			return None;
+		}
+		let z_inv = self.invert(&point.z);
+		Some(Point { x: self.value(&self.mul(&point.x, &z_inv)), y: self.value(&self.mul(&point.y, &z_inv)) })
+	}
+}
\ No newline at end of file
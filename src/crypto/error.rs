@@ -0,0 +1,18 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The input is not a multiple of the cipher block size
+	InvalidLength,
+	/// ISO/IEC 9797-1 padding method 2 is missing or malformed
+	InvalidPadding,
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::InvalidLength => write!(f, "Input is not a multiple of the block size"),
+			Self::InvalidPadding => write!(f, "Invalid padding"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
\ No newline at end of file
@@ -1,24 +1,161 @@
+use crate::sm::CipherSuite;
+use super::SecretKey;
 use sha1::{Sha1, Digest};
+use sha2::Sha256;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
-pub fn derive_key(key: &[u8], counter: u32) -> Vec<u8> {
-	let mut hasher = Sha1::new();
-	hasher.update(key);
-	hasher.update(counter.to_be_bytes());
+/// Counter for the encryption key KEnc or KSEnc
+pub const COUNTER_ENC: u32 = 1;
+/// Counter for the MAC key KMAC or KSMAC
+pub const COUNTER_MAC: u32 = 2;
+/// Counter for the PACE password key Kπ
+pub const COUNTER_PASSWORD: u32 = 3;
 
-	let mut key = hasher.finalize().to_vec();
-	key.resize(16, 0);
+/// Block cipher and key length of a derived key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAlgorithm {
+	/// Two-key 3DES with adjusted parity bits
+	Tdes,
+	Aes128,
+	Aes192,
+	Aes256,
+}
 
-	for i in 0..key.len() {
-		let mut parity = false;
-		for j in 0..64usize {
-			if key[i] & (0x01usize << j) as u8 == 0x00 {
-				parity = !parity;
-			}
+impl KeyAlgorithm {
+	pub fn key_len(&self) -> usize {
+		match self {
+			Self::Tdes | Self::Aes128 => 16,
+			Self::Aes192 => 24,
+			Self::Aes256 => 32,
 		}
-		if !parity {
-			key[i] ^= 0x01;
+	}
+
+	pub fn cipher_suite(&self) -> CipherSuite {
+		match self {
+			Self::Tdes => CipherSuite::Tdes,
+			Self::Aes128 | Self::Aes192 | Self::Aes256 => CipherSuite::Aes,
+		}
+	}
+}
+
+/// A key derived with [`kdf`], which dereferences to its key bytes.
+///
+/// The key is zeroized on drop, redacted in `Debug` and compared in constant time.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub enum Key {
+	Tdes([u8; 16]),
+	Aes128([u8; 16]),
+	Aes192([u8; 24]),
+	Aes256([u8; 32]),
+}
+
+impl Key {
+	pub fn algorithm(&self) -> KeyAlgorithm {
+		match self {
+			Self::Tdes(_) => KeyAlgorithm::Tdes,
+			Self::Aes128(_) => KeyAlgorithm::Aes128,
+			Self::Aes192(_) => KeyAlgorithm::Aes192,
+			Self::Aes256(_) => KeyAlgorithm::Aes256,
+		}
+	}
+}
+
+impl std::ops::Deref for Key {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		match self {
+			Self::Tdes(key) | Self::Aes128(key) => key,
+			Self::Aes192(key) => key,
+			Self::Aes256(key) => key,
 		}
 	}
+}
+
+impl AsRef<[u8]> for Key {
+	fn as_ref(&self) -> &[u8] {
+		self
+	}
+}
+
+impl ConstantTimeEq for Key {
+	fn ct_eq(&self, other: &Self) -> Choice {
+		// The algorithm is not secret
+		Choice::from((self.algorithm() == other.algorithm()) as u8) & (**self).ct_eq(&**other)
+	}
+}
 
+impl PartialEq for Key {
+	fn eq(&self, other: &Self) -> bool {
+		self.ct_eq(other).into()
+	}
+}
+
+impl Eq for Key {}
+
+impl std::fmt::Debug for Key {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Key::{:?}([REDACTED])", self.algorithm())
+	}
+}
+
+/// Key derivation function, as per
+/// ICAO 9303 MRTD v8 2021 Part 11, section 9.7.1 Key Derivation Function
+///
+/// KDF(K, c) = H(K || c), where H is SHA-1 for 3DES and AES-128, and
+/// SHA-256 for AES-192 and AES-256.
+pub fn kdf(shared_secret: &[u8], counter: u32, algorithm: KeyAlgorithm) -> Key {
+	let input = keydata_input(shared_secret, counter);
+	let mut keydata = match algorithm {
+		KeyAlgorithm::Tdes | KeyAlgorithm::Aes128 => Sha1::digest(&*input).to_vec(),
+		KeyAlgorithm::Aes192 | KeyAlgorithm::Aes256 => Sha256::digest(&*input).to_vec(),
+	};
+	let key = match algorithm {
+		KeyAlgorithm::Tdes => {
+			// Ka and Kb are the first 16 octets of keydata, with adjusted parity bits
+			let mut key = [0; 16];
+			key.copy_from_slice(&keydata[..16]);
+			adjust_parity(&mut key);
+			Key::Tdes(key)
+		}
+		KeyAlgorithm::Aes128 => {
+			let mut key = [0; 16];
+			key.copy_from_slice(&keydata[..16]);
+			Key::Aes128(key)
+		}
+		KeyAlgorithm::Aes192 => {
+			let mut key = [0; 24];
+			key.copy_from_slice(&keydata[..24]);
+			Key::Aes192(key)
+		}
+		KeyAlgorithm::Aes256 => {
+			let mut key = [0; 32];
+			key.copy_from_slice(&keydata[..32]);
+			Key::Aes256(key)
+		}
+	};
+	keydata.zeroize();
 	key
+}
+
+/// Derives a two-key 3DES key, as used by BAC.
+pub fn derive_key(key: &[u8], counter: u32) -> SecretKey {
+	kdf(key, counter, KeyAlgorithm::Tdes).into()
+}
+
+fn keydata_input(shared_secret: &[u8], counter: u32) -> Zeroizing<Vec<u8>> {
+	let mut input = Zeroizing::new(Vec::with_capacity(shared_secret.len() + 4));
+	input.extend_from_slice(shared_secret);
+	input.extend_from_slice(&counter.to_be_bytes());
+	input
+}
+
+/// Sets the least significant bit of every byte for odd parity, as per FIPS 46-3
+fn adjust_parity(key: &mut [u8]) {
+	for byte in key.iter_mut() {
+		if byte.count_ones() % 2 == 0 {
+			*byte ^= 0x01;
+		}
+	}
 }
\ No newline at end of file
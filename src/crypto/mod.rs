@@ -1,6 +1,13 @@
 mod kdf;
-pub use kdf::derive_key;
+mod secret;
+pub use kdf::{derive_key, kdf, Key, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC, COUNTER_PASSWORD};
+pub use secret::SecretKey;
 
+pub mod aes;
 pub mod des;
+pub mod dh;
+pub mod ec;
+pub mod error;
 pub mod padding;
+pub mod retail_mac;
 pub mod tdes;
\ No newline at end of file
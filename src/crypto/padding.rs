@@ -1,14 +1,19 @@
-use crate::error::BoxResult;
+use super::error::Error;
 
 pub fn pad(input: &[u8]) -> Vec<u8> {
-	let new_len = ((input.len()+8)/8)*8;
+	pad_block(input, 8)
+}
+
+/// ISO/IEC 9797-1 padding method 2 to a multiple of the given block size
+pub fn pad_block(input: &[u8], block_size: usize) -> Vec<u8> {
+	let new_len = ((input.len()+block_size)/block_size)*block_size;
 	let mut output = input.to_vec();
 	output.resize(new_len, 0);
 	output[input.len()] = 0x80;
 	output
 }
 
-pub fn unpad(mut input: Vec<u8>) -> BoxResult<Vec<u8>> {
+pub fn unpad(mut input: Vec<u8>) -> Result<Vec<u8>, Error> {
 	for (i, c) in input.iter().enumerate().rev() {
 		if *c == 0x00 {
 			continue
@@ -16,7 +21,7 @@ pub fn unpad(mut input: Vec<u8>) -> BoxResult<Vec<u8>> {
 			input.resize(i, 0);
 			return Ok(input);
 		} else {
-			return Err(format!("Unexpected data {:?} at position {} of padded data", c, i).into())
+			return Err(Error::InvalidPadding)
 		}
 	}
 	Ok(input)
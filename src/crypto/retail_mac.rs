@@ -0,0 +1,112 @@
+use des::Des;
+use des::cipher::{BlockEncrypt, BlockDecrypt};
+use digest::{FixedOutput, FixedOutputReset, KeyInit, MacMarker, Output, OutputSizeUser, Reset, Update};
+use digest::crypto_common::{Key, KeySizeUser};
+use digest::generic_array::GenericArray;
+use digest::consts::{U8, U16};
+
+const BLOCK_SIZE: usize = 8;
+
+/// ISO/IEC 9797-1:2011 MAC Algorithm 3 (retail MAC) with DES and padding
+/// method 2, as used by BAC and 3DES secure messaging.
+///
+/// The key is K1 || K2. Blocks are chained as the data is passed to
+/// [`Update::update`], so data of any length is processed without allocating.
+#[derive(Clone)]
+pub struct RetailMac {
+	k1: Des,
+	k2: Des,
+	h: GenericArray<u8, U8>,
+	buffer: [u8; BLOCK_SIZE],
+	pos: usize,
+}
+
+impl RetailMac {
+	/// Chains the buffered block into H.
+	fn process_block(&mut self) {
+		for (h, d) in self.h.iter_mut().zip(self.buffer.iter()) {
+			*h ^= d;
+		}
+		self.k1.encrypt_block(&mut self.h);
+		self.pos = 0;
+	}
+
+	fn finalize_into_inner(&mut self, out: &mut Output<Self>) {
+		// Padding method 2
+		self.buffer[self.pos] = 0x80;
+		self.buffer[self.pos+1..].fill(0x00);
+		self.process_block();
+
+		// Output transformation 3
+		let mut g = self.h;
+		self.k2.decrypt_block(&mut g);
+		self.k1.encrypt_block(&mut g);
+		out.copy_from_slice(&g);
+	}
+}
+
+impl KeySizeUser for RetailMac {
+	type KeySize = U16;
+}
+
+impl KeyInit for RetailMac {
+	fn new(key: &Key<Self>) -> Self {
+		let (k1, k2) = key.split_at(BLOCK_SIZE);
+		Self {
+			k1: Des::new(GenericArray::from_slice(k1)),
+			k2: Des::new(GenericArray::from_slice(k2)),
+			h: GenericArray::default(),
+			buffer: [0; BLOCK_SIZE],
+			pos: 0,
+		}
+	}
+}
+
+impl OutputSizeUser for RetailMac {
+	type OutputSize = U8;
+}
+
+impl MacMarker for RetailMac {}
+
+impl Update for RetailMac {
+	fn update(&mut self, mut data: &[u8]) {
+		while !data.is_empty() {
+			let n = (BLOCK_SIZE - self.pos).min(data.len());
+			self.buffer[self.pos..self.pos+n].copy_from_slice(&data[..n]);
+			self.pos += n;
+			data = &data[n..];
+
+			// A full block is never the last one, since padding always adds a byte
+			if self.pos == BLOCK_SIZE {
+				self.process_block();
+			}
+		}
+	}
+}
+
+impl FixedOutput for RetailMac {
+	fn finalize_into(mut self, out: &mut Output<Self>) {
+		self.finalize_into_inner(out);
+	}
+}
+
+impl Reset for RetailMac {
+	fn reset(&mut self) {
+		self.h = GenericArray::default();
+		self.buffer = [0; BLOCK_SIZE];
+		self.pos = 0;
+	}
+}
+
+impl FixedOutputReset for RetailMac {
+	fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+		self.finalize_into_inner(out);
+		Reset::reset(self);
+	}
+}
+
+impl std::fmt::Debug for RetailMac {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("RetailMac").finish_non_exhaustive()
+	}
+}
\ No newline at end of file
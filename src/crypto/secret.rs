@@ -0,0 +1,69 @@
+use super::Key;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Key material of any length, such as the BAC key seed or secure messaging
+/// session keys.
+///
+/// The bytes are zeroized on drop, redacted in `Debug` and compared in
+/// constant time.
+#[derive(Clone, Default, Zeroize, ZeroizeOnDrop)]
+pub struct SecretKey(Vec<u8>);
+
+impl SecretKey {
+	pub fn new(key: impl Into<Vec<u8>>) -> Self {
+		Self(key.into())
+	}
+}
+
+impl From<Vec<u8>> for SecretKey {
+	fn from(key: Vec<u8>) -> Self {
+		Self(key)
+	}
+}
+
+impl From<&[u8]> for SecretKey {
+	fn from(key: &[u8]) -> Self {
+		Self(key.to_vec())
+	}
+}
+
+impl From<Key> for SecretKey {
+	fn from(key: Key) -> Self {
+		Self(key.to_vec())
+	}
+}
+
+impl std::ops::Deref for SecretKey {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		&self.0
+	}
+}
+
+impl AsRef<[u8]> for SecretKey {
+	fn as_ref(&self) -> &[u8] {
+		&self.0
+	}
+}
+
+impl ConstantTimeEq for SecretKey {
+	fn ct_eq(&self, other: &Self) -> Choice {
+		self.0.as_slice().ct_eq(other.0.as_slice())
+	}
+}
+
+impl PartialEq for SecretKey {
+	fn eq(&self, other: &Self) -> bool {
+		self.ct_eq(other).into()
+	}
+}
+
+impl Eq for SecretKey {}
+
+impl std::fmt::Debug for SecretKey {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "SecretKey([REDACTED; {}])", self.0.len())
+	}
+}
\ No newline at end of file
@@ -1,3 +1,145 @@
-// TODO: Implement a proper error type
-pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
-pub type BoxResult<T> = Result<T, BoxError>;
\ No newline at end of file
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::ApduResponseTrailer;
+use crate::apdu::response::status::Status;
+use iso7816_tlv::TlvError;
+
+#[derive(Debug)]
+pub enum Error {
+	/// The reader failed to exchange an APDU with the IC
+	Transport(Box<dyn std::error::Error + Send + Sync>),
+	/// The IC processed a command with an unexpected status word
+	Status { command: ApduCommand<'static>, trailer: ApduResponseTrailer },
+	/// The IC responded with an unexpected amount of response data
+	ResponseLength { expected: usize, actual: usize },
+	/// Verification of a secure messaging checksum failed
+	MacMismatch,
+	SecureMessaging(crate::sm::error::Error),
+	Tlv(TlvError),
+	Crypto(crate::crypto::error::Error),
+	Apdu(crate::apdu::error::Error),
+	Bac(crate::auth::error::BacError),
+	Pace(crate::auth::error::PaceError),
+	Mrz(crate::mrz::error::Error),
+	Trace(crate::trace::error::Error),
+	/// The length encoded in the header of an elementary file is invalid
+	InvalidFileLength,
+	/// The response data lacks a data object with the given tag
+	MissingDataObject(u8),
+	/// A protocol was advanced without the response to its last command
+	ProtocolState,
+	Io(std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+	pub(crate) fn status(command: &ApduCommand, trailer: ApduResponseTrailer) -> Self {
+		Self::Status { command: command.clone().into_owned(), trailer }
+	}
+
+	/// The status word returned by the IC, if the error was caused by one.
+	pub fn trailer(&self) -> Option<ApduResponseTrailer> {
+		match self {
+			Self::Status { trailer, .. } => Some(*trailer),
+			_ => None,
+		}
+	}
+
+	pub fn status_word(&self) -> Option<Status> {
+		self.trailer().map(Status::from)
+	}
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Transport(e) => write!(f, "Transport error: {}", e),
+			Self::Status { command, trailer } => write!(f, "Command with INS {:02X} failed {}", command.ins, trailer),
+			Self::ResponseLength { expected, actual } => write!(f, "Invalid response data length {}, expected {}", actual, expected),
+			Self::MacMismatch => write!(f, "Invalid MAC"),
+			Self::SecureMessaging(e) => write!(f, "Secure messaging error: {}", e),
+			Self::Tlv(e) => write!(f, "TLV error: {}", e),
+			Self::Crypto(e) => write!(f, "Cryptographic error: {}", e),
+			Self::Apdu(e) => write!(f, "APDU error: {}", e),
+			Self::Bac(e) => write!(f, "BAC failed: {}", e),
+			Self::Pace(e) => write!(f, "PACE failed: {}", e),
+			Self::Mrz(e) => write!(f, "MRZ error: {}", e),
+			Self::Trace(e) => write!(f, "Trace error: {}", e),
+			Self::InvalidFileLength => write!(f, "Invalid file length"),
+			Self::MissingDataObject(tag) => write!(f, "Missing DO'{:02X}' in response data", tag),
+			Self::ProtocolState => write!(f, "Protocol advanced without the response APDU to its last command"),
+			Self::Io(e) => write!(f, "I/O error: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Transport(e) => Some(e.as_ref()),
+			Self::SecureMessaging(e) => Some(e),
+			Self::Crypto(e) => Some(e),
+			Self::Apdu(e) => Some(e),
+			Self::Bac(e) => Some(e),
+			Self::Pace(e) => Some(e),
+			Self::Mrz(e) => Some(e),
+			Self::Trace(e) => Some(e),
+			Self::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<crate::sm::error::Error> for Error {
+	fn from(e: crate::sm::error::Error) -> Self {
+		Self::SecureMessaging(e)
+	}
+}
+
+impl From<TlvError> for Error {
+	fn from(e: TlvError) -> Self {
+		Self::Tlv(e)
+	}
+}
+
+impl From<crate::crypto::error::Error> for Error {
+	fn from(e: crate::crypto::error::Error) -> Self {
+		Self::Crypto(e)
+	}
+}
+
+impl From<crate::apdu::error::Error> for Error {
+	fn from(e: crate::apdu::error::Error) -> Self {
+		Self::Apdu(e)
+	}
+}
+
+impl From<crate::auth::error::BacError> for Error {
+	fn from(e: crate::auth::error::BacError) -> Self {
+		Self::Bac(e)
+	}
+}
+
+impl From<crate::auth::error::PaceError> for Error {
+	fn from(e: crate::auth::error::PaceError) -> Self {
+		Self::Pace(e)
+	}
+}
+
+impl From<crate::mrz::error::Error> for Error {
+	fn from(e: crate::mrz::error::Error) -> Self {
+		Self::Mrz(e)
+	}
+}
+
+impl From<crate::trace::error::Error> for Error {
+	fn from(e: crate::trace::error::Error) -> Self {
+		Self::Trace(e)
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e)
+	}
+}
\ No newline at end of file
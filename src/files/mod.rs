@@ -1,16 +1,33 @@
-use crate::error::BoxResult;
-use crate::apdu::command::borrowed::ApduCommand;
-use crate::apdu::response::owned::ApduResponse;
+use crate::error::{Error, Result};
+use crate::apdu::command::ApduCommand;
+use crate::apdu::commands::{select_fid, read_binary, read_binary_sfi, MAX_EVEN_INS_OFFSET};
+use crate::apdu::response::owned::{ApduResponse, ApduResponseTrailer, TRAILER_OK, TRAILER_FUNCTION_NOT_SUPPORTED, TRAILER_FILE_NOT_FOUND, TRAILER_INCORRECT_P1_P2, TRAILER_INCOMPATIBLE_FILE_STRUCTURE, TRAILER_NO_CURRENT_EF};
+use crate::sm::{Protected, SecureMessaging};
+use crate::transport::{Transport, AsyncTransport, Protocol, Step, run, run_async};
+use iso7816_tlv::ber as tlv;
+
+/// Status words with which an IC rejects reading a file by SFI, after which
+/// the file is selected by its file identifier instead
+const SFI_UNSUPPORTED_TRAILERS: [ApduResponseTrailer; 5] = [
+	TRAILER_FUNCTION_NOT_SUPPORTED,
+	TRAILER_FILE_NOT_FOUND,
+	TRAILER_INCORRECT_P1_P2,
+	TRAILER_INCOMPATIBLE_FILE_STRUCTURE,
+	TRAILER_NO_CURRENT_EF,
+];
 
 pub type DataGroup = u8;
 pub type Tag = u8;
 pub type FileId = u16;
+pub type Sfi = u8;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct File {
 	pub tag: Tag,
 	pub dg: DataGroup,
 	pub fileid: FileId,
+	/// Short EF identifier, which can be used to read the file without selecting it
+	pub sfi: Option<Sfi>,
 	pub name: &'static str,
 	pub desc: &'static str,
 	pub pace: bool,
@@ -19,89 +36,226 @@ pub struct File {
 	pub fast: bool,
 }
 
-pub const EF_COM: File = File { tag: 0x60, dg: 0, fileid: 0x011E, name: "EF_COM", desc: "Header and Data Group Presence Information", pace: false, eac: false, req: true, fast: true };
-pub const EF_SOD: File = File { tag: 0x77, dg: 0, fileid: 0x011D, name: "EF_SOD", desc: "Document Security Object", pace: false, eac: false, req: false, fast: false };
-pub const EF_CARDACCESS: File = File { tag: 0xFF, dg: 0, fileid: 0x011C, name: "EF_CardAccess", desc: "PACE SecurityInfos", pace: true, eac: false, req: true, fast: true };
-pub const EF_CARDSECURITY: File = File { tag: 0xFF, dg: 0, fileid: 0x011D, name: "EF_CardSecurity", desc: "PACE SecurityInfos for Chip Authentication Mapping", pace: true, eac: false, req: false, fast: true };
-pub const EF_DG14: File = File { tag: 0x6E, dg: 14, fileid: 0x010E, name: "EF_DG14", desc: "Security Options", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG15: File = File { tag: 0x6F, dg: 15, fileid: 0x010F, name: "EF_DG15", desc: "Active Authentication Public Key Info", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG1: File = File { tag: 0x61, dg: 1, fileid: 0x0101, name: "EF_DG1", desc: "Details recorded in MRZ", pace: false, eac: false, req: true, fast: true };
-pub const EF_DG2: File = File { tag: 0x75, dg: 2, fileid: 0x0102, name: "EF_DG2", desc: "Encoded Face", pace: false, eac: false, req: true, fast: false };
-pub const EF_DG3: File = File { tag: 0x63, dg: 3, fileid: 0x0103, name: "EF_DG3", desc: "Encoded Finger(s)", pace: false, eac: true, req: false, fast: false };
-pub const EF_DG4: File = File { tag: 0x76, dg: 4, fileid: 0x0104, name: "EF_DG4", desc: "Encoded Eye(s)", pace: false, eac: true, req: false, fast: false };
-pub const EF_DG5: File = File { tag: 0x65, dg: 5, fileid: 0x0105, name: "EF_DG5", desc: "Displayed Portrait", pace: false, eac: false, req: false, fast: false };
-pub const EF_DG6: File = File { tag: 0x66, dg: 6, fileid: 0x0106, name: "EF_DG6", desc: "Reserved for Future Use", pace: false, eac: false, req: false, fast: false };
-pub const EF_DG7: File = File { tag: 0x67, dg: 7, fileid: 0x0107, name: "EF_DG7", desc: "Displayed Signature or Usual Mark", pace: false, eac: false, req: false, fast: false };
-pub const EF_DG8: File = File { tag: 0x68, dg: 8, fileid: 0x0108, name: "EF_DG8", desc: "Data Feature(s)", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG9: File = File { tag: 0x69, dg: 9, fileid: 0x0109, name: "EF_DG9", desc: "Structure Feature(s)", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG10: File = File { tag: 0x6A, dg: 10, fileid: 0x010A, name: "EF_DG10", desc: "Substance Feature(s)", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG11: File = File { tag: 0x6B, dg: 11, fileid: 0x010B, name: "EF_DG11", desc: "Additional Personal Detail(s)", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG12: File = File { tag: 0x6C, dg: 12, fileid: 0x010C, name: "EF_DG12", desc: "Additional Document Detail(s)", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG13: File = File { tag: 0x6D, dg: 13, fileid: 0x010D, name: "EF_DG13", desc: "Optional Detail(s)", pace: false, eac: false, req: false, fast: true };
-pub const EF_DG16: File = File { tag: 0x70, dg: 16, fileid: 0x0110, name: "EF_DG16", desc: "Person(s) to Notify", pace: false, eac: false, req: false, fast: true };
+pub const EF_COM: File = File { tag: 0x60, dg: 0, fileid: 0x011E, sfi: Some(0x1E), name: "EF_COM", desc: "Header and Data Group Presence Information", pace: false, eac: false, req: true, fast: true };
+pub const EF_SOD: File = File { tag: 0x77, dg: 0, fileid: 0x011D, sfi: Some(0x1D), name: "EF_SOD", desc: "Document Security Object", pace: false, eac: false, req: false, fast: false };
+pub const EF_CARDACCESS: File = File { tag: 0xFF, dg: 0, fileid: 0x011C, sfi: Some(0x1C), name: "EF_CardAccess", desc: "PACE SecurityInfos", pace: true, eac: false, req: true, fast: true };
+pub const EF_CARDSECURITY: File = File { tag: 0xFF, dg: 0, fileid: 0x011D, sfi: Some(0x1D), name: "EF_CardSecurity", desc: "PACE SecurityInfos for Chip Authentication Mapping", pace: true, eac: false, req: false, fast: true };
+pub const EF_DG14: File = File { tag: 0x6E, dg: 14, fileid: 0x010E, sfi: Some(0x0E), name: "EF_DG14", desc: "Security Options", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG15: File = File { tag: 0x6F, dg: 15, fileid: 0x010F, sfi: Some(0x0F), name: "EF_DG15", desc: "Active Authentication Public Key Info", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG1: File = File { tag: 0x61, dg: 1, fileid: 0x0101, sfi: Some(0x01), name: "EF_DG1", desc: "Details recorded in MRZ", pace: false, eac: false, req: true, fast: true };
+pub const EF_DG2: File = File { tag: 0x75, dg: 2, fileid: 0x0102, sfi: Some(0x02), name: "EF_DG2", desc: "Encoded Face", pace: false, eac: false, req: true, fast: false };
+pub const EF_DG3: File = File { tag: 0x63, dg: 3, fileid: 0x0103, sfi: Some(0x03), name: "EF_DG3", desc: "Encoded Finger(s)", pace: false, eac: true, req: false, fast: false };
+pub const EF_DG4: File = File { tag: 0x76, dg: 4, fileid: 0x0104, sfi: Some(0x04), name: "EF_DG4", desc: "Encoded Eye(s)", pace: false, eac: true, req: false, fast: false };
+pub const EF_DG5: File = File { tag: 0x65, dg: 5, fileid: 0x0105, sfi: Some(0x05), name: "EF_DG5", desc: "Displayed Portrait", pace: false, eac: false, req: false, fast: false };
+pub const EF_DG6: File = File { tag: 0x66, dg: 6, fileid: 0x0106, sfi: Some(0x06), name: "EF_DG6", desc: "Reserved for Future Use", pace: false, eac: false, req: false, fast: false };
+pub const EF_DG7: File = File { tag: 0x67, dg: 7, fileid: 0x0107, sfi: Some(0x07), name: "EF_DG7", desc: "Displayed Signature or Usual Mark", pace: false, eac: false, req: false, fast: false };
+pub const EF_DG8: File = File { tag: 0x68, dg: 8, fileid: 0x0108, sfi: Some(0x08), name: "EF_DG8", desc: "Data Feature(s)", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG9: File = File { tag: 0x69, dg: 9, fileid: 0x0109, sfi: Some(0x09), name: "EF_DG9", desc: "Structure Feature(s)", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG10: File = File { tag: 0x6A, dg: 10, fileid: 0x010A, sfi: Some(0x0A), name: "EF_DG10", desc: "Substance Feature(s)", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG11: File = File { tag: 0x6B, dg: 11, fileid: 0x010B, sfi: Some(0x0B), name: "EF_DG11", desc: "Additional Personal Detail(s)", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG12: File = File { tag: 0x6C, dg: 12, fileid: 0x010C, sfi: Some(0x0C), name: "EF_DG12", desc: "Additional Document Detail(s)", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG13: File = File { tag: 0x6D, dg: 13, fileid: 0x010D, sfi: Some(0x0D), name: "EF_DG13", desc: "Optional Detail(s)", pace: false, eac: false, req: false, fast: true };
+pub const EF_DG16: File = File { tag: 0x70, dg: 16, fileid: 0x0110, sfi: Some(0x10), name: "EF_DG16", desc: "Person(s) to Notify", pace: false, eac: false, req: false, fast: true };
 pub const FILES: [File; 20] = [ EF_COM, EF_SOD, EF_CARDACCESS, EF_CARDSECURITY, EF_DG14, EF_DG15, EF_DG1, EF_DG2, EF_DG3, EF_DG4, EF_DG5, EF_DG6, EF_DG7, EF_DG8, EF_DG9, EF_DG10, EF_DG11, EF_DG12, EF_DG13, EF_DG16 ];
 
 const HEADER_LEN: usize = 4;
+/// Maximum number of length bytes of the data object of a file
+const MAX_LENGTH_BYTES: usize = 3;
 const MAX_READ: usize = 100;
-#[cfg(feature = "nfc1")]
-pub fn read_file(device: &mut nfc1::Device, ks_mac: &[u8], ks_enc: &[u8], ssc: &mut u64, file: &File) -> BoxResult<Vec<u8>> {
-	let mut offset = 0;
-
-	// 1. Select EF.COM
-	let fileid = file.fileid.to_be_bytes();
-	let apdu = ApduCommand { cla: 0x00, ins: 0xA4, p1: 0x02, p2: 0x0C, data: &fileid, rx_len: 0 }.to_protected(ks_enc, ks_mac, ssc)?.to_vec();
-	let res = ApduResponse::from_protected(device.initiator_transceive_bytes(&apdu, 16, nfc1::Timeout::None)?, ks_mac, ks_enc, ssc)?;
-
-	// 2. Read Binary of first four bytes
-	let apdu = apdu_read_binary(HEADER_LEN, offset).to_protected(ks_enc, ks_mac, ssc)?.to_vec();
-	let res = ApduResponse::from_protected(device.initiator_transceive_bytes(&apdu, 27, nfc1::Timeout::None)?, ks_mac, ks_enc, ssc)?;
-	if res.data.len() != HEADER_LEN {
-		return Err(format!("Invalid response data length {}, expected {}", res.data.len(), HEADER_LEN).into());
+pub fn read_file<T: Transport + ?Sized, S: SecureMessaging + ?Sized>(transport: &mut T, sm: &mut S, file: &File) -> Result<Vec<u8>> {
+	run(transport, Protected::new(ReadFile::new(file), sm))
+}
+
+pub async fn read_file_async<T: AsyncTransport + ?Sized, S: SecureMessaging + ?Sized>(transport: &mut T, sm: &mut S, file: &File) -> Result<Vec<u8>> {
+	run_async(transport, Protected::new(ReadFile::new(file), sm)).await
+}
+
+/// Reads a file by its short EF identifier, which saves the SELECT command.
+/// Falls back to SELECT if the file has no SFI or the IC rejects it, see
+/// [`ReadFile::by_sfi`].
+pub fn read_file_by_sfi<T: Transport + ?Sized, S: SecureMessaging + ?Sized>(transport: &mut T, sm: &mut S, file: &File) -> Result<Vec<u8>> {
+	run(transport, Protected::new(ReadFile::by_sfi(file), sm))
+}
+
+pub async fn read_file_by_sfi_async<T: AsyncTransport + ?Sized, S: SecureMessaging + ?Sized>(transport: &mut T, sm: &mut S, file: &File) -> Result<Vec<u8>> {
+	run_async(transport, Protected::new(ReadFile::by_sfi(file), sm)).await
+}
+
+/// Former name of [`read_binary`].
+#[deprecated(note = "use apdu::commands::read_binary")]
+pub fn apdu_read_binary<'a>(rx_len: usize, offset: usize) -> ApduCommand<'a> {
+	read_binary(rx_len, offset)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadFileState {
+	Start,
+	Select,
+	Header,
+	HeaderSfi,
+	HeaderLength,
+	Body { chunk_len: usize, odd_ins: bool },
+}
+
+/// Reads a complete elementary file. The commands of this protocol are
+/// plain, and should be run through [`Protected`] once secure messaging is
+/// established.
+#[derive(Debug, Clone)]
+pub struct ReadFile {
+	state: ReadFileState,
+	fileid: FileId,
+	sfi: Option<Sfi>,
+	data: Vec<u8>,
+	len: usize,
+	last: Option<ApduCommand<'static>>,
+}
+
+impl ReadFile {
+	pub fn new(file: &File) -> Self {
+		Self { state: ReadFileState::Start, fileid: file.fileid, sfi: None, data: Vec::new(), len: 0, last: None }
 	}
 
-	// j) Determine length of structure
-	let mut len = 0usize;
-	let x = res.data[1];
-	if x & 0x80 == 0 {
-		len = x as usize;
-	} else {
-		let n_bytes = x as usize & 0x7f;
-		if n_bytes > HEADER_LEN {
-			return Err("Invalid file length".into());
-		}
-		for n in 0..n_bytes {
-			let x = res.data[2+n];
-			len = len << 8 | x as usize;
+	/// Reads the file by its short EF identifier instead of selecting it first,
+	/// falling back to SELECT if the IC rejects the SFI.
+	///
+	/// Within [`Protected`], the fallback requires the status word to be
+	/// protected by secure messaging. An unprotected error fails with
+	/// [`Error::Status`], after which the session has to be re-established.
+	pub fn by_sfi(file: &File) -> Self {
+		Self { sfi: file.sfi, ..Self::new(file) }
+	}
+
+	fn select(&mut self) -> Step<Vec<u8>> {
+		self.transmit(ReadFileState::Select, select_fid(self.fileid))
+	}
+
+	fn transmit(&mut self, state: ReadFileState, command: ApduCommand<'static>) -> Step<Vec<u8>> {
+		self.state = state;
+		self.last = Some(command.clone());
+		Step::Transmit(command)
+	}
+
+	/// Fails with the status word of the IC, unless the last command succeeded.
+	fn check(&self, res: &ApduResponse) -> Result<()> {
+		match &self.last {
+			Some(last) if res.trailer != TRAILER_OK => Err(Error::status(last, res.trailer)),
+			_ => Ok(()),
 		}
 	}
 
-	// create buffer to store all data
-	let mut data = Vec::with_capacity(len);
-	len -= HEADER_LEN;
-	offset += HEADER_LEN;
-	data.extend_from_slice(&res.data);
+	/// Determines the length of the file from the header read so far, and
+	/// reads the rest of the file.
+	fn read_body(&mut self) -> Result<Step<Vec<u8>>> {
+		// j) Determine length of structure, including the tag and length bytes
+		let mut len = 0usize;
+		let x = self.data[1];
+		if x & 0x80 == 0 {
+			len = 2 + x as usize;
+		} else {
+			let n_bytes = x as usize & 0x7f;
+			if n_bytes > MAX_LENGTH_BYTES || self.data.len() < 2 + n_bytes {
+				return Err(Error::InvalidFileLength);
+			}
+			for n in 0..n_bytes {
+				let x = self.data[2+n];
+				len = len << 8 | x as usize;
+			}
+			len += 2 + n_bytes;
+		}
+		if len < self.data.len() {
+			return Err(Error::InvalidFileLength);
+		}
+
+		// 3. Read Binary of remaining (tlv_size)-4 bytes from offset 4
+		self.data.reserve_exact(len - self.data.len());
+		self.len = len - self.data.len();
+		Ok(self.next_chunk())
+	}
 
-	// 3. Read Binary of remaining (tlv_size)-4 bytes from offset 4
-	while len > 0 {
-		let chunk_len = if len > MAX_READ {
+	/// Reads the next chunk of the file, or completes the protocol once the
+	/// whole file has been read.
+	fn next_chunk(&mut self) -> Step<Vec<u8>> {
+		if self.len == 0 {
+			return Step::Done(std::mem::take(&mut self.data));
+		}
+		let chunk_len = if self.len > MAX_READ {
 			MAX_READ
 		} else {
-			len
+			self.len
 		};
-		let apdu = apdu_read_binary(chunk_len, offset).to_protected(ks_enc, ks_mac, ssc)?.to_vec();
-		let res = ApduResponse::from_protected(device.initiator_transceive_bytes(&apdu, 123, nfc1::Timeout::None)?, ks_mac, ks_enc, ssc)?;
-		if res.data.len() != chunk_len {
-			return Err(format!("Invalid response data length {}, expected {}", res.data.len(), chunk_len).into());
-		}
-		len -= chunk_len;
-		offset += chunk_len;
-		data.extend_from_slice(&res.data);
+		let offset = self.data.len();
+		let command = read_binary(chunk_len, offset);
+		self.transmit(ReadFileState::Body { chunk_len, odd_ins: offset > MAX_EVEN_INS_OFFSET }, command)
 	}
+}
+
+impl Protocol for ReadFile {
+	type Output = Vec<u8>;
+
+	fn step(&mut self, response: Option<ApduResponse>) -> Result<Step<Vec<u8>>> {
+		match (self.state, response) {
+			(ReadFileState::Start, _) => match self.sfi {
+				// 1. Read Binary of first four bytes by SFI, which also selects the file
+				Some(sfi) => Ok(self.transmit(ReadFileState::HeaderSfi, read_binary_sfi(HEADER_LEN, sfi, 0))),
+				// 1. Select EF.COM
+				None => Ok(self.select()),
+			},
+			(ReadFileState::Select, Some(res)) => {
+				self.check(&res)?;
+
+				// 2. Read Binary of first four bytes
+				Ok(self.transmit(ReadFileState::Header, read_binary(HEADER_LEN, 0)))
+			}
+			(ReadFileState::HeaderSfi, Some(res)) if SFI_UNSUPPORTED_TRAILERS.contains(&res.trailer) => {
+				// The IC does not support reading this file by SFI
+				Ok(self.select())
+			}
+			(ReadFileState::Header | ReadFileState::HeaderSfi, Some(res)) => {
+				self.check(&res)?;
+				if res.data.len() != HEADER_LEN {
+					return Err(Error::ResponseLength { expected: HEADER_LEN, actual: res.data.len() });
+				}
+				self.data = res.data;
 
-	Ok(data)
+				// A length of three bytes ends after the first four bytes, so
+				// its last byte is read separately
+				if self.data[1] == 0x83 {
+					return Ok(self.transmit(ReadFileState::HeaderLength, read_binary(1, HEADER_LEN)));
+				}
+				self.read_body()
+			}
+			(ReadFileState::HeaderLength, Some(res)) => {
+				self.check(&res)?;
+				if res.data.len() != 1 {
+					return Err(Error::ResponseLength { expected: 1, actual: res.data.len() });
+				}
+				self.data.extend_from_slice(&res.data);
+				self.read_body()
+			}
+			(ReadFileState::Body { chunk_len, odd_ins }, Some(res)) => {
+				self.check(&res)?;
+				let data = if odd_ins {
+					discretionary_data(&res.data)?
+				} else {
+					res.data
+				};
+				if data.len() != chunk_len {
+					return Err(Error::ResponseLength { expected: chunk_len, actual: data.len() });
+				}
+				self.len -= chunk_len;
+				self.data.extend_from_slice(&data);
+				Ok(self.next_chunk())
+			}
+			(_, None) => Err(Error::ProtocolState),
+		}
+	}
 }
 
-pub fn apdu_read_binary<'a>(rx_len: usize, offset: usize) -> ApduCommand<'a> {
-	let offset = (offset as u16).to_be_bytes();
-	ApduCommand { cla: 0x00, ins: 0xB0, p1: offset[0], p2: offset[1], data: &[], rx_len }
+/// Extracts the data of DO'53' from the response to READ BINARY with odd INS.
+fn discretionary_data(data: &[u8]) -> Result<Vec<u8>> {
+	let tlv = tlv::Tlv::from_bytes(data)?;
+	match tlv.value() {
+		tlv::Value::Primitive(value) if Into::<u64>::into(tlv.tag().clone()) == 0x53 => Ok(value.clone()),
+		_ => Err(Error::MissingDataObject(0x53)),
+	}
 }
\ No newline at end of file
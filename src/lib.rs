@@ -3,4 +3,10 @@ pub mod auth;
 pub mod crypto;
 pub mod error;
 pub mod mrz;
-pub mod files;
\ No newline at end of file
+pub mod simulator;
+pub mod sm;
+pub mod files;
+pub mod trace;
+pub mod transport;
+
+pub use error::{Error, Result};
\ No newline at end of file
@@ -2,10 +2,11 @@ use super::{FILL_CHAR, MrzFormat};
 use super::iter::{CompositeDataChars, DocumentNumberChars, DocumentNumberCharsWithCheckDigit, DataWithCheckDigit, MrzInformation};
 use super::error::Error;
 use super::check_digit::sum;
-use crate::crypto::derive_key;
+use crate::crypto::{derive_key, SecretKey};
 
 use chrono::NaiveDate;
 use sha1::{Sha1, Digest};
+use zeroize::Zeroize;
 
 pub trait MrzData<'a>: ToString + TryFrom<&'a str, Error = Error> {
 	fn format(&self) -> MrzFormat;
@@ -28,7 +29,9 @@ pub trait MrzData<'a>: ToString + TryFrom<&'a str, Error = Error> {
 	fn composite_check_digit(&self) -> &str;
 	fn composite_data(&self) -> CompositeDataChars;
 
-	fn derive_seed_key(&self) -> Vec<u8> {
+	/// The SHA-1 hash of the MRZ information, which is the PACE password
+	/// for the MRZ and the source of the BAC key seed.
+	fn derive_password(&self) -> SecretKey {
 		let mut hasher = Sha1::new();
 		hasher.update(self.document_number());
 
@@ -52,12 +55,17 @@ pub trait MrzData<'a>: ToString + TryFrom<&'a str, Error = Error> {
 		hasher.update(self.date_of_expiry());
 		hasher.update(self.date_of_expiry_check_digit());
 
-		let mut key_seed = hasher.finalize().to_vec();
-		key_seed.resize(16, 0);
-		key_seed
+		let mut hash = hasher.finalize();
+		let password = SecretKey::from(&hash[..]);
+		hash.as_mut_slice().zeroize();
+		password
 	}
 
-	fn derive_key(&self, counter: u32) -> Vec<u8> {
+	fn derive_seed_key(&self) -> SecretKey {
+		SecretKey::from(&self.derive_password()[..16])
+	}
+
+	fn derive_key(&self, counter: u32) -> SecretKey {
 		if counter == 0 {
 			return self.derive_seed_key()
 		}
@@ -4,7 +4,7 @@ use super::{FILL_CHAR, MrzFormat};
 use super::iter::CompositeDataChars;
 use super::borrowed::{MrzData, Mrz as BorrowedMrz};
 use super::check_digit::sum;
-use crate::crypto::derive_key;
+use crate::crypto::{derive_key, SecretKey};
 
 use chrono::NaiveDate;
 
@@ -98,9 +98,9 @@ pub struct Mrz {
 	pub nationality: String,
 	pub optional_data_1: Option<String>,
 	pub optional_data_2: Option<String>,
-	pub key_seed: Vec<u8>,
-    pub key_enc: Vec<u8>,
-    pub key_mac: Vec<u8>,
+	pub key_seed: SecretKey,
+    pub key_enc: SecretKey,
+    pub key_mac: SecretKey,
 }
 
 impl<'a> TryFrom<BorrowedMrz<'a>> for Mrz {
@@ -0,0 +1,282 @@
+use crate::error::Result;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::commands::AID_EMRTD;
+use crate::apdu::response::owned::{ApduResponse, ApduResponseTrailer, TRAILER_OK, TRAILER_WRONG_LEN, TRAILER_WRONG_P1_P2, TRAILER_WRONG_SM_OBJECTS, TRAILER_AUTHENTICATION_FAILED, TRAILER_SECURITY_STATUS_NOT_SATISFIED, TRAILER_CONDITIONS_NOT_SATISFIED, TRAILER_MISSING_SM_OBJECTS, TRAILER_FILE_NOT_FOUND, TRAILER_INS_NOT_SUPPORTED, TRAILER_CLA_NOT_SUPPORTED};
+use crate::auth::bac::derive_session_keys;
+use crate::crypto::SecretKey;
+use crate::crypto::des::mac;
+use crate::crypto::tdes::{encrypt, decrypt};
+use crate::files::{FileId, FILES};
+use crate::mrz::borrowed::MrzData;
+use crate::sm::Session;
+use crate::transport::{Transport, AsyncTransport};
+use iso7816_tlv::ber as tlv;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// An in-process simulated eMRTD chip.
+///
+/// Implements the chip side of Basic Access Control, 3DES secure messaging,
+/// SELECT and READ BINARY over a file system served from memory. Since it
+/// implements [`Transport`], it can be used in place of a reader to drive the
+/// full read path without any hardware.
+///
+/// Nonces and keying material of the chip are generated with `R`, which can
+/// be replaced with [`VirtualChip::with_rng`] for reproducible runs.
+#[derive(Debug, Clone)]
+pub struct VirtualChip<R = StdRng> {
+	k_enc: SecretKey,
+	k_mac: SecretKey,
+	files: HashMap<FileId, Vec<u8>>,
+	applet_selected: bool,
+	selected_file: Option<FileId>,
+	rnd_ic: Option<[u8; 8]>,
+	session: Option<Session>,
+	rng: R,
+}
+
+impl VirtualChip {
+	/// Creates a chip with BAC keys derived from the MRZ printed on the document.
+	pub fn new<'a, M: MrzData<'a>>(mrz: &M) -> Self {
+		Self::with_rng(mrz, StdRng::from_entropy())
+	}
+}
+
+impl<R: RngCore> VirtualChip<R> {
+	/// Creates a chip which generates RND.IC and K.IC with the given RNG.
+	pub fn with_rng<'a, M: MrzData<'a>>(mrz: &M, rng: R) -> Self {
+		Self {
+			k_enc: mrz.derive_key(1),
+			k_mac: mrz.derive_key(2),
+			files: HashMap::new(),
+			applet_selected: false,
+			selected_file: None,
+			rnd_ic: None,
+			session: None,
+			rng,
+		}
+	}
+
+	/// Adds a file with the given contents to the file system of the chip.
+	pub fn with_file(mut self, fileid: FileId, data: impl Into<Vec<u8>>) -> Self {
+		self.insert_file(fileid, data);
+		self
+	}
+
+	/// Adds or replaces a file with the given contents in the file system of the chip.
+	pub fn insert_file(&mut self, fileid: FileId, data: impl Into<Vec<u8>>) {
+		self.files.insert(fileid, data.into());
+	}
+
+	/// Simulates removing the chip from the field, discarding all session state.
+	pub fn reset(&mut self) {
+		self.applet_selected = false;
+		self.selected_file = None;
+		self.rnd_ic = None;
+		self.session = None;
+	}
+
+	fn process(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		if command.cla & 0x0C == 0x0C {
+			return self.process_protected(command);
+		}
+		if command.cla != 0x00 {
+			return Ok(status(TRAILER_CLA_NOT_SUPPORTED));
+		}
+		match command.ins {
+			0xA4 if command.p1 == 0x04 => Ok(self.select_application(command)),
+			0x84 => Ok(self.get_challenge(command)),
+			0x82 => self.external_authenticate(command),
+			0xA4 | 0xB0 | 0xB1 => {
+				if self.session.is_some() {
+					// Plain commands abort secure messaging
+					self.session = None;
+					Ok(status(TRAILER_MISSING_SM_OBJECTS))
+				} else {
+					Ok(status(TRAILER_SECURITY_STATUS_NOT_SATISFIED))
+				}
+			}
+			_ => Ok(status(TRAILER_INS_NOT_SUPPORTED)),
+		}
+	}
+
+	fn process_protected(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		let mut session = match self.session.take() {
+			Some(session) => session,
+			None => return Ok(status(TRAILER_CONDITIONS_NOT_SATISFIED)),
+		};
+
+		// Secure messaging errors abort the session and are sent unprotected
+		let command = match session.unwrap_command(command) {
+			Ok(command) => command,
+			Err(_) => return Ok(status(TRAILER_WRONG_SM_OBJECTS)),
+		};
+
+		let res = match command.ins {
+			0xA4 => self.select_file(&command),
+			0xB0 => self.read_binary(&command),
+			0xB1 => self.read_binary_odd(&command),
+			_ => status(TRAILER_INS_NOT_SUPPORTED),
+		};
+
+		let res = session.wrap_response(command.ins, &res)?;
+		self.session = Some(session);
+		Ok(res)
+	}
+
+	fn select_application(&mut self, command: &ApduCommand) -> ApduResponse {
+		if command.data != AID_EMRTD {
+			return status(TRAILER_FILE_NOT_FOUND);
+		}
+		self.applet_selected = true;
+		self.selected_file = None;
+		self.session = None;
+		status(TRAILER_OK)
+	}
+
+	fn get_challenge(&mut self, command: &ApduCommand) -> ApduResponse {
+		if command.rx_len != 8 {
+			return status(TRAILER_WRONG_LEN);
+		}
+		let mut rnd_ic = [0; 8];
+		self.rng.fill_bytes(&mut rnd_ic);
+		self.rnd_ic = Some(rnd_ic);
+		ApduResponse { data: rnd_ic.to_vec(), trailer: TRAILER_OK }
+	}
+
+	fn external_authenticate(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		// The challenge can only be used once
+		let rnd_ic = match self.rnd_ic.take() {
+			Some(rnd_ic) if self.applet_selected => rnd_ic,
+			_ => return Ok(status(TRAILER_CONDITIONS_NOT_SATISFIED)),
+		};
+		if command.data.len() != 40 {
+			return Ok(status(TRAILER_WRONG_LEN));
+		}
+
+		// 3) The IC performs the following operations:
+
+		// a) check the checksum MIFD of the cryptogram EIFD
+		let (e_ifd, m_ifd) = command.data.split_at(32);
+		if mac(e_ifd, &self.k_mac)?.ct_ne(m_ifd).into() {
+			return Ok(status(TRAILER_AUTHENTICATION_FAILED));
+		}
+
+		// b) decrypt the cryptogram EIFD
+		let s = Zeroizing::new(decrypt(e_ifd, &self.k_enc)?);
+
+		// c) extract RND.IC from S and check if IFD returned the correct value
+		let (rnd_ifd, rnd_ic_k_ifd) = s.split_at(8);
+		let (rnd_ic_ifd, k_ifd) = rnd_ic_k_ifd.split_at(8);
+		if rnd_ic_ifd != rnd_ic {
+			return Ok(status(TRAILER_AUTHENTICATION_FAILED));
+		}
+
+		// d) generate keying material K.IC
+		let mut k_ic = Zeroizing::new([0; 16]);
+		self.rng.fill_bytes(&mut *k_ic);
+
+		// e) generate the concatenation R = RND.IC || RND.IFD || K.IC
+		let mut r = Zeroizing::new(Vec::with_capacity(32));
+		r.extend_from_slice(&rnd_ic);
+		r.extend_from_slice(rnd_ifd);
+		r.extend_from_slice(&*k_ic);
+
+		// f) compute the cryptogram EIC = E(KEnc, R)
+		let e_ic = encrypt(&r, &self.k_enc)?;
+
+		// g) compute the checksum MIC = MAC(KMAC, EIC)
+		let m_ic = mac(&e_ic, &self.k_mac)?;
+
+		// Derive session keys with (K.IC xor K.IFD) as shared secret
+		self.session = Some(derive_session_keys(k_ifd, &*k_ic, &rnd_ic, rnd_ifd));
+
+		// h) send the response using the data EIC || MIC
+		let mut data = e_ic;
+		data.extend_from_slice(&m_ic);
+		Ok(ApduResponse { data, trailer: TRAILER_OK })
+	}
+
+	fn select_file(&mut self, command: &ApduCommand) -> ApduResponse {
+		if command.p1 != 0x02 || command.data.len() != 2 {
+			return status(TRAILER_WRONG_P1_P2);
+		}
+		let fileid = u16::from_be_bytes([command.data[0], command.data[1]]);
+		if !self.files.contains_key(&fileid) {
+			return status(TRAILER_FILE_NOT_FOUND);
+		}
+		self.selected_file = Some(fileid);
+		status(TRAILER_OK)
+	}
+
+	fn read_binary(&mut self, command: &ApduCommand) -> ApduResponse {
+		let offset = if command.p1 & 0x80 != 0 {
+			// Select the file by its short EF identifier, with the offset in P2
+			let sfi = command.p1 & 0x1F;
+			match FILES.iter().find(|file| file.sfi == Some(sfi) && self.files.contains_key(&file.fileid)) {
+				Some(file) => self.selected_file = Some(file.fileid),
+				None => return status(TRAILER_FILE_NOT_FOUND),
+			}
+			command.p2 as usize
+		} else {
+			u16::from_be_bytes([command.p1, command.p2]) as usize
+		};
+		let file = match self.selected_file.and_then(|fileid| self.files.get(&fileid)) {
+			Some(file) => file,
+			None => return status(TRAILER_CONDITIONS_NOT_SATISFIED),
+		};
+		if offset > file.len() {
+			return status(TRAILER_WRONG_P1_P2);
+		}
+		let end = file.len().min(offset + command.rx_len);
+		ApduResponse { data: file[offset..end].to_vec(), trailer: TRAILER_OK }
+	}
+
+	fn read_binary_odd(&mut self, command: &ApduCommand) -> ApduResponse {
+		// Only the currently selected EF is supported, with the offset in DO'54'
+		let offset = match command.data.as_ref() {
+			[0x54, len, offset @ ..] if command.p1 == 0 && command.p2 == 0 && *len as usize == offset.len() && offset.len() <= 3 => {
+				offset.iter().fold(0usize, |acc, b| acc << 8 | *b as usize)
+			}
+			_ => return status(TRAILER_WRONG_P1_P2),
+		};
+		let file = match self.selected_file.and_then(|fileid| self.files.get(&fileid)) {
+			Some(file) => file,
+			None => return status(TRAILER_CONDITIONS_NOT_SATISFIED),
+		};
+		if offset > file.len() {
+			return status(TRAILER_WRONG_P1_P2);
+		}
+
+		// Return the data in DO'53', within the expected response length
+		let max_len = match command.rx_len {
+			0..=0x81 => command.rx_len.saturating_sub(2),
+			0x82..=0x102 => command.rx_len - 3,
+			rx_len => rx_len - 4,
+		};
+		let end = file.len().min(offset + max_len);
+		match tlv::Tlv::new(tlv::Tag::try_from(0x53).unwrap(), tlv::Value::Primitive(file[offset..end].to_vec())) {
+			Ok(tlv) => ApduResponse { data: tlv.to_vec(), trailer: TRAILER_OK },
+			Err(_) => status(TRAILER_WRONG_LEN),
+		}
+	}
+}
+
+impl<R: RngCore> Transport for VirtualChip<R> {
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		self.process(command)
+	}
+}
+
+impl<R: RngCore> AsyncTransport for VirtualChip<R> {
+	fn transmit(&mut self, command: &ApduCommand) -> impl std::future::Future<Output = Result<ApduResponse>> + Send {
+		std::future::ready(Transport::transmit(self, command))
+	}
+}
+
+fn status(trailer: ApduResponseTrailer) -> ApduResponse {
+	ApduResponse { data: Vec::new(), trailer }
+}
@@ -0,0 +1,74 @@
+use crate::error::Result;
+use crate::crypto::{aes, des, tdes};
+use crate::crypto::padding::pad_block;
+
+/// Cipher suite used to protect APDUs, as per
+/// ICAO 9303 MRTD v8 2021 Part 11, section 9.8.6 Cryptographic Algorithms
+///
+/// The send sequence counter is encoded with the block size of the cipher,
+/// so the same `u64` counter is used for both suites. This holds the 8 byte
+/// SSC of 3DES, which wraps around like the counter of the IC. The 16 byte
+/// SSC of AES starts at zero after PACE or Chip Authentication, so its upper
+/// 8 bytes stay zero for any number of APDUs a session can exchange.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CipherSuite {
+	/// 3DES in CBC mode with zero IV and ISO/IEC 9797-1 MAC algorithm 3 (section 9.8.6.1)
+	#[default]
+	Tdes,
+	/// AES in CBC mode with IV = E(KSEnc, SSC) and AES-CMAC truncated to 8 bytes (section 9.8.6.2).
+	/// The length of the session keys selects AES-128, AES-192 or AES-256.
+	Aes,
+}
+
+impl CipherSuite {
+	pub fn block_size(&self) -> usize {
+		match self {
+			Self::Tdes => 8,
+			Self::Aes => aes::BLOCK_SIZE,
+		}
+	}
+
+	/// Encodes the send sequence counter as 8 bytes for 3DES and 16 bytes for
+	/// AES, where the counter is zero-extended to the block size.
+	pub fn ssc_bytes(&self, ssc: u64) -> Vec<u8> {
+		let mut ssc_bytes = vec![0; self.block_size() - 8];
+		ssc_bytes.extend_from_slice(&ssc.to_be_bytes());
+		ssc_bytes
+	}
+
+	/// Pads the input to the block size of the cipher, as per ISO/IEC 9797-1 padding method 2
+	pub fn pad(&self, input: &[u8]) -> Vec<u8> {
+		pad_block(input, self.block_size())
+	}
+
+	/// Pads and encrypts data of a command or response APDU, using the
+	/// send sequence counter of that APDU.
+	pub fn encrypt(&self, input: &[u8], ks_enc: &[u8], ssc: u64) -> Result<Vec<u8>> {
+		match self {
+			Self::Tdes => tdes::encrypt_pad(input, ks_enc),
+			Self::Aes => aes::encrypt_pad(input, ks_enc, &self.iv(ks_enc, ssc)?),
+		}
+	}
+
+	/// Decrypts and unpads data of a command or response APDU, using the
+	/// send sequence counter of that APDU.
+	pub fn decrypt(&self, input: &[u8], ks_enc: &[u8], ssc: u64) -> Result<Vec<u8>> {
+		match self {
+			Self::Tdes => tdes::decrypt_unpad(input, ks_enc),
+			Self::Aes => aes::decrypt_unpad(input, ks_enc, &self.iv(ks_enc, ssc)?),
+		}
+	}
+
+	/// Computes the cryptographic checksum over the padded input.
+	pub fn mac(&self, input: &[u8], ks_mac: &[u8]) -> Result<Vec<u8>> {
+		match self {
+			Self::Tdes => des::mac(input, ks_mac),
+			Self::Aes => aes::mac(&self.pad(input), ks_mac),
+		}
+	}
+
+	/// For AES, IV = E(KSEnc, SSC)
+	fn iv(&self, ks_enc: &[u8], ssc: u64) -> Result<Vec<u8>> {
+		aes::encrypt(&self.ssc_bytes(ssc), ks_enc, &aes::ZERO_IV)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,29 @@
+use crate::apdu::response::owned::ApduResponseTrailer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The command APDU does not indicate secure messaging in its class byte
+	NotProtected,
+	/// The IC responded without secure messaging data objects
+	UnprotectedResponse(ApduResponseTrailer),
+	/// A mandatory data object with the given tag is missing
+	MissingDataObject(u8),
+	/// A data object with the given tag has an invalid value
+	InvalidDataObject(u8),
+	/// The padding-content indicator of DO'87' is not '01'
+	InvalidPaddingIndicator,
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::NotProtected => write!(f, "Command APDU is not protected"),
+			Self::UnprotectedResponse(trailer) => write!(f, "Response APDU is not protected {}", trailer),
+			Self::MissingDataObject(tag) => write!(f, "Missing DO'{:02X}'", tag),
+			Self::InvalidDataObject(tag) => write!(f, "Invalid DO'{:02X}'", tag),
+			Self::InvalidPaddingIndicator => write!(f, "Invalid padding indicator in DO'87'"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
\ No newline at end of file
@@ -0,0 +1,108 @@
+use crate::error::{Error, Result};
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::{ApduResponse, ApduResponseTrailer, TRAILER_OK};
+use crate::transport::{Transport, AsyncTransport, Protocol, Step, run, run_async};
+
+mod cipher;
+pub mod error;
+mod session;
+pub use cipher::CipherSuite;
+pub use session::Session;
+
+/// Protection of command and response APDUs, as per
+/// ICAO 9303 MRTD v8 2021 Part 11, section 9.8 Secure Messaging
+///
+/// Implemented by [`Session`] for the cipher suites of ICAO 9303, and can be
+/// implemented to protect APDUs elsewhere, such as in a secure element.
+pub trait SecureMessaging {
+	/// Protects a command APDU, incrementing the send sequence counter.
+	fn wrap_command(&mut self, command: &ApduCommand) -> Result<ApduCommand<'static>>;
+
+	/// Verifies and decrypts a protected response APDU, incrementing the send sequence counter.
+	fn unwrap_response(&mut self, response: ApduResponse) -> Result<ApduResponse>;
+}
+
+/// Runs a protocol over secure messaging.
+///
+/// Every command of the inner protocol is protected before it is sent, and
+/// every response is verified and decrypted before it is passed back.
+///
+/// An error status word sent without secure messaging means the IC has ended
+/// secure messaging, or the send sequence counters are out of step. It is
+/// passed on to the inner protocol, but if the inner protocol sends another
+/// command, the protocol fails with [`Error::Status`] instead, as the session
+/// has to be re-established first.
+#[derive(Debug)]
+pub struct Protected<'a, P: Protocol, S: SecureMessaging + ?Sized> {
+	inner: P,
+	sm: &'a mut S,
+	last: Option<ApduCommand<'static>>,
+	aborted: Option<ApduResponseTrailer>,
+}
+
+impl<'a, P: Protocol, S: SecureMessaging + ?Sized> Protected<'a, P, S> {
+	pub fn new(inner: P, sm: &'a mut S) -> Self {
+		Self { inner, sm, last: None, aborted: None }
+	}
+}
+
+impl<'a, P: Protocol, S: SecureMessaging + ?Sized> Protocol for Protected<'a, P, S> {
+	type Output = P::Output;
+
+	fn step(&mut self, response: Option<ApduResponse>) -> Result<Step<P::Output>> {
+		let response = match response {
+			// Errors detected by the IC in secure messaging are not protected,
+			// and are passed on for the inner protocol to fail on
+			Some(response) if response.data.is_empty() && response.trailer != TRAILER_OK => {
+				self.aborted = Some(response.trailer);
+				Some(response)
+			}
+			Some(response) => Some(self.sm.unwrap_response(response)?),
+			None => None,
+		};
+		match self.inner.step(response)? {
+			Step::Transmit(command) => {
+				if let (Some(trailer), Some(last)) = (self.aborted, &self.last) {
+					return Err(Error::status(last, trailer));
+				}
+				let protected = self.sm.wrap_command(&command)?;
+				self.last = Some(command);
+				Ok(Step::Transmit(protected))
+			}
+			Step::Done(output) => Ok(Step::Done(output)),
+		}
+	}
+}
+
+/// A protocol consisting of a single command APDU.
+#[derive(Debug, Clone)]
+pub struct Single(Option<ApduCommand<'static>>);
+
+impl Single {
+	pub fn new(command: &ApduCommand) -> Self {
+		Self(Some(command.clone().into_owned()))
+	}
+}
+
+impl Protocol for Single {
+	type Output = ApduResponse;
+
+	fn step(&mut self, response: Option<ApduResponse>) -> Result<Step<ApduResponse>> {
+		match (self.0.take(), response) {
+			(Some(command), None) => Ok(Step::Transmit(command)),
+			(None, Some(response)) => Ok(Step::Done(response)),
+			_ => Err(Error::ProtocolState),
+		}
+	}
+}
+
+/// Transmits a command APDU protected by secure messaging, and returns the
+/// verified and decrypted response APDU. Unprotected error status words are
+/// returned as is.
+pub fn transmit<T: Transport + ?Sized, S: SecureMessaging + ?Sized>(transport: &mut T, command: &ApduCommand, sm: &mut S) -> Result<ApduResponse> {
+	run(transport, Protected::new(Single::new(command), sm))
+}
+
+pub async fn transmit_async<T: AsyncTransport + ?Sized, S: SecureMessaging + ?Sized>(transport: &mut T, command: &ApduCommand<'_>, sm: &mut S) -> Result<ApduResponse> {
+	run_async(transport, Protected::new(Single::new(command), sm)).await
+}
\ No newline at end of file
@@ -0,0 +1,75 @@
+use crate::error::Result;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::ApduResponse;
+use crate::crypto::{Key, SecretKey};
+use super::{CipherSuite, SecureMessaging};
+
+/// A secure messaging session, owning the session keys KSEnc and KSMAC and
+/// the send sequence counter (SSC).
+///
+/// Sessions are established by an authentication protocol such as BAC or
+/// PACE, and can be restarted with new keys after Chip Authentication.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+	cipher: CipherSuite,
+	ks_enc: SecretKey,
+	ks_mac: SecretKey,
+	ssc: u64,
+}
+
+impl Session {
+	pub fn new(cipher: CipherSuite, ks_enc: impl Into<SecretKey>, ks_mac: impl Into<SecretKey>, ssc: u64) -> Self {
+		Self { cipher, ks_enc: ks_enc.into(), ks_mac: ks_mac.into(), ssc }
+	}
+
+	/// Creates a session from keys derived with [`crate::crypto::kdf`], using
+	/// the cipher suite of their algorithm.
+	pub fn from_keys(ks_enc: Key, ks_mac: Key, ssc: u64) -> Self {
+		Self::new(ks_enc.algorithm().cipher_suite(), ks_enc, ks_mac, ssc)
+	}
+
+	pub fn cipher(&self) -> CipherSuite {
+		self.cipher
+	}
+
+	pub fn ks_enc(&self) -> &[u8] {
+		&self.ks_enc
+	}
+
+	pub fn ks_mac(&self) -> &[u8] {
+		&self.ks_mac
+	}
+
+	/// The current send sequence counter, which is incremented before every
+	/// command and response is protected or verified.
+	pub fn ssc(&self) -> u64 {
+		self.ssc
+	}
+
+	/// Restarts secure messaging with new session keys and the SSC set to zero,
+	/// as per ICAO 9303 MRTD v8 2021 Part 11, section 6.2 Chip Authentication
+	pub fn swap_keys(&mut self, cipher: CipherSuite, ks_enc: impl Into<SecretKey>, ks_mac: impl Into<SecretKey>) {
+		*self = Self::new(cipher, ks_enc, ks_mac, 0);
+	}
+
+	/// Card side of [`SecureMessaging::wrap_command`]: verifies and decrypts a protected command APDU.
+	pub fn unwrap_command(&mut self, command: &ApduCommand) -> Result<ApduCommand<'static>> {
+		ApduCommand::from_protected(command, self.cipher, &self.ks_enc, &self.ks_mac, &mut self.ssc)
+	}
+
+	/// Card side of [`SecureMessaging::unwrap_response`]: builds a protected
+	/// response APDU to the command with the given INS.
+	pub fn wrap_response(&mut self, ins: u8, response: &ApduResponse) -> Result<ApduResponse> {
+		response.to_protected(ins, self.cipher, &self.ks_enc, &self.ks_mac, &mut self.ssc)
+	}
+}
+
+impl SecureMessaging for Session {
+	fn wrap_command(&mut self, command: &ApduCommand) -> Result<ApduCommand<'static>> {
+		command.to_protected(self.cipher, &self.ks_enc, &self.ks_mac, &mut self.ssc)
+	}
+
+	fn unwrap_response(&mut self, response: ApduResponse) -> Result<ApduResponse> {
+		ApduResponse::from_protected(response, self.cipher, &self.ks_enc, &self.ks_mac, &mut self.ssc)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,234 @@
+use crate::error::{Error, Result};
+use crate::auth::error::BacError;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::{ApduResponse, TRAILER_OK};
+use crate::auth::bac::derive_session_keys;
+use crate::crypto::SecretKey;
+use crate::crypto::des::mac;
+use crate::crypto::tdes::decrypt;
+use crate::files::FILES;
+use crate::mrz::borrowed::MrzData;
+use crate::sm::{SecureMessaging, Session};
+use super::{Exchange, Trace};
+use super::error::Error as TraceError;
+use hex_fmt::HexFmt;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// Annotates and decrypts the exchanges of a recorded session.
+///
+/// Secure messaging is followed from either the MRZ, in which case the
+/// session keys are recovered from the BAC handshake in the trace, or from
+/// known session keys. Every MAC is verified along the way.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+	bac_keys: Option<(SecretKey, SecretKey)>,
+	rnd_ic: Option<Vec<u8>>,
+	session: Option<Session>,
+}
+
+/// A decoded exchange.
+#[derive(Debug)]
+pub struct DecodedExchange {
+	pub exchange: Exchange,
+	/// Human-readable description of the command
+	pub label: String,
+	/// Whether the exchange was protected by secure messaging
+	pub protected: bool,
+	/// Plaintext command APDU, if the exchange was protected and could be decrypted
+	pub command: Option<ApduCommand<'static>>,
+	/// Plaintext response APDU, if the exchange was protected and could be decrypted
+	pub response: Option<ApduResponse>,
+	/// MAC verification failures and other decoding errors
+	pub errors: Vec<DecodeError>,
+}
+
+/// An error decoding the command or the response of an exchange.
+#[derive(Debug)]
+pub enum DecodeError {
+	Command(Error),
+	Response(Error),
+}
+
+impl Decoder {
+	pub fn with_mrz<'a, M: MrzData<'a>>(mrz: &M) -> Self {
+		Self { bac_keys: Some((mrz.derive_key(1), mrz.derive_key(2))), rnd_ic: None, session: None }
+	}
+
+	/// Creates a decoder for a trace starting after the authentication
+	/// protocol, with the send sequence counter as it was at that point.
+	pub fn with_session(session: Session) -> Self {
+		Self { bac_keys: None, rnd_ic: None, session: Some(session) }
+	}
+
+	pub fn decode_trace(&mut self, trace: &Trace) -> Vec<DecodedExchange> {
+		trace.exchanges.iter().map(|exchange| self.decode(exchange)).collect()
+	}
+
+	pub fn decode(&mut self, exchange: &Exchange) -> DecodedExchange {
+		let mut decoded = DecodedExchange {
+			exchange: exchange.clone(),
+			label: String::new(),
+			protected: false,
+			command: None,
+			response: None,
+			errors: Vec::new(),
+		};
+
+		let command = match ApduCommand::try_from(exchange.command.as_slice()) {
+			Ok(command) => command,
+			Err(e) => {
+				decoded.label = String::from("Invalid command");
+				decoded.errors.push(DecodeError::Command(e.into()));
+				return decoded;
+			}
+		};
+		let response = ApduResponse::from(exchange.response.clone());
+
+		if command.cla & 0x0C == 0x0C {
+			decoded.protected = true;
+			self.decode_protected(&command, response, &mut decoded);
+		} else {
+			decoded.label = label(&command);
+			match command.ins {
+				0x84 if response.trailer == TRAILER_OK => self.rnd_ic = Some(response.data.clone()),
+				0x82 if response.trailer == TRAILER_OK => {
+					if let Err(e) = self.decode_external_authenticate(&command, &response) {
+						decoded.errors.push(e);
+					}
+				}
+				_ => {}
+			}
+		}
+
+		decoded
+	}
+
+	fn decode_protected(&mut self, command: &ApduCommand, response: ApduResponse, decoded: &mut DecodedExchange) {
+		let session = match &mut self.session {
+			Some(session) => session,
+			None => {
+				decoded.label = format!("Protected {}", label(command));
+				decoded.errors.push(DecodeError::Command(TraceError::MissingExchange("authentication protocol").into()));
+				return;
+			}
+		};
+
+		match session.unwrap_command(command) {
+			Ok(plain) => {
+				decoded.label = label(&plain);
+				decoded.command = Some(plain);
+			}
+			Err(e) => {
+				decoded.label = format!("Protected {}", label(command));
+				decoded.errors.push(DecodeError::Command(e));
+			}
+		}
+
+		match session.unwrap_response(response) {
+			Ok(plain) => decoded.response = Some(plain),
+			Err(e) => decoded.errors.push(DecodeError::Response(e)),
+		}
+	}
+
+	fn decode_external_authenticate(&mut self, command: &ApduCommand, response: &ApduResponse) -> std::result::Result<(), DecodeError> {
+		let (k_enc, k_mac) = self.bac_keys.as_ref().ok_or(DecodeError::Command(TraceError::MissingExchange("BAC keys").into()))?;
+		let rnd_ic = self.rnd_ic.take().ok_or(DecodeError::Command(TraceError::MissingExchange("GET CHALLENGE before EXTERNAL AUTHENTICATE").into()))?;
+		if rnd_ic.len() != 8 {
+			return Err(DecodeError::Command(TraceError::DataLength { expected: 8, actual: rnd_ic.len() }.into()));
+		}
+
+		// Verify and decrypt S = RND.IFD || RND.IC || K.IFD
+		let s = Self::decrypt_cryptogram(&command.data, k_enc, k_mac, Error::MacMismatch)
+			.map_err(DecodeError::Command)?;
+
+		// Verify and decrypt R = RND.IC || RND.IFD || K.IC
+		let r = Self::decrypt_cryptogram(&response.data, k_enc, k_mac, BacError::InvalidCryptogramMac.into())
+			.map_err(DecodeError::Response)?;
+
+		self.session = Some(derive_session_keys(&s[16..32], &r[16..32], &rnd_ic, &s[0..8]));
+		Ok(())
+	}
+
+	/// Verifies and decrypts the 32 byte cryptogram and 8 byte checksum of
+	/// EXTERNAL AUTHENTICATE, failing with the given error if the checksum does not match.
+	fn decrypt_cryptogram(data: &[u8], k_enc: &[u8], k_mac: &[u8], mac_mismatch: Error) -> Result<Zeroizing<Vec<u8>>> {
+		if data.len() != 40 {
+			return Err(TraceError::DataLength { expected: 40, actual: data.len() }.into());
+		}
+		let (e, m) = data.split_at(32);
+		let expected_m = mac(e, k_mac)?;
+		if m.ct_ne(&expected_m).into() {
+			return Err(mac_mismatch);
+		}
+		Ok(Zeroizing::new(decrypt(e, k_enc)?))
+	}
+}
+
+impl std::fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Command(e) => write!(f, "Command: {}", e),
+			Self::Response(e) => write!(f, "Response: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for DecodeError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Command(e) | Self::Response(e) => Some(e),
+		}
+	}
+}
+
+impl std::fmt::Display for DecodedExchange {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} {}", self.exchange.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Micros, true), self.label)?;
+		if let Some(ssc) = self.exchange.ssc {
+			write!(f, " (SSC {:016X})", ssc)?;
+		}
+		writeln!(f)?;
+		writeln!(f, "  > {:X}", HexFmt(&self.exchange.command))?;
+		if let Some(Ok(command)) = self.command.as_ref().map(|command| command.to_vec()) {
+			writeln!(f, "  > {:X} (plaintext)", HexFmt(command))?;
+		}
+		writeln!(f, "  < {:X}", HexFmt(&self.exchange.response))?;
+		if let Some(response) = &self.response {
+			writeln!(f, "  < {:X} (plaintext)", HexFmt(response.to_vec()))?;
+		}
+		if self.protected && self.errors.is_empty() {
+			writeln!(f, "  MAC verified")?;
+		}
+		for error in &self.errors {
+			writeln!(f, "  Error: {}", error)?;
+		}
+		Ok(())
+	}
+}
+
+fn label(command: &ApduCommand) -> String {
+	match command.ins {
+		0xA4 if command.p1 == 0x04 => format!("SELECT AID {:X}", HexFmt(&command.data)),
+		0xA4 if command.p1 == 0x02 && command.data.len() == 2 => {
+			let fileid = u16::from_be_bytes([command.data[0], command.data[1]]);
+			match FILES.iter().find(|file| file.fileid == fileid) {
+				Some(file) => format!("SELECT {} ({:04X})", file.name, fileid),
+				None => format!("SELECT EF {:04X}", fileid),
+			}
+		}
+		0xA4 => format!("SELECT P1={:02X} {:X}", command.p1, HexFmt(&command.data)),
+		0xB0 if command.p1 & 0x80 != 0 => format!("READ BINARY SFI {:02X} offset {} length {}", command.p1 & 0x1F, command.p2, command.rx_len),
+		0xB0 => format!("READ BINARY offset {} length {}", u16::from_be_bytes([command.p1, command.p2]), command.rx_len),
+		0xB1 => format!("READ BINARY (odd INS) length {}", command.rx_len),
+		0x84 => format!("GET CHALLENGE length {}", command.rx_len),
+		0x82 => String::from("EXTERNAL AUTHENTICATE"),
+		0x88 => String::from("INTERNAL AUTHENTICATE"),
+		0x22 => format!("MANAGE SECURITY ENVIRONMENT P1={:02X} P2={:02X}", command.p1, command.p2),
+		0x86 | 0x87 => String::from("GENERAL AUTHENTICATE"),
+		0x2A => format!("PERFORM SECURITY OPERATION P1={:02X} P2={:02X}", command.p1, command.p2),
+		0xC0 => format!("GET RESPONSE length {}", command.rx_len),
+		0xD6 => String::from("UPDATE BINARY"),
+		ins => format!("INS {:02X}", ins),
+	}
+}
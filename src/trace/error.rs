@@ -0,0 +1,44 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	InvalidLine(String),
+	InvalidHex(String),
+	InvalidTimestamp(chrono::format::ParseError),
+	InvalidSsc(std::num::ParseIntError),
+	/// A replayed command was sent after the last exchange of the trace
+	EndOfTrace,
+	/// A replayed command differs from the recorded command
+	CommandMismatch { position: usize, expected: Vec<u8>, actual: Vec<u8> },
+	/// The trace lacks an exchange required to recover the session
+	MissingExchange(&'static str),
+	/// A recorded command or response has an unexpected amount of data
+	DataLength { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::InvalidLine(line) => write!(f, "Invalid trace line \"{}\"", line),
+			Self::InvalidHex(s) => write!(f, "Invalid hex string \"{}\"", s),
+			Self::InvalidTimestamp(e) => write!(f, "Invalid timestamp: {}", e),
+			Self::InvalidSsc(e) => write!(f, "Invalid SSC: {}", e),
+			Self::EndOfTrace => write!(f, "End of trace reached"),
+			Self::CommandMismatch { position, expected, actual } => write!(f, "Command {:X} does not match command {:X} at position {} of trace", hex_fmt::HexFmt(actual), hex_fmt::HexFmt(expected), position),
+			Self::MissingExchange(exchange) => write!(f, "No {} in trace", exchange),
+			Self::DataLength { expected, actual } => write!(f, "Invalid data length {}, expected {}", actual, expected),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<chrono::format::ParseError> for Error {
+	fn from(e: chrono::format::ParseError) -> Self {
+		Self::InvalidTimestamp(e)
+	}
+}
+
+impl From<std::num::ParseIntError> for Error {
+	fn from(e: std::num::ParseIntError) -> Self {
+		Self::InvalidSsc(e)
+	}
+}
\ No newline at end of file
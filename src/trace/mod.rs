@@ -0,0 +1,94 @@
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use hex_fmt::HexFmt;
+use std::io::{BufRead, Write};
+
+mod decode;
+pub mod error;
+use error::Error;
+mod record;
+mod replay;
+pub use decode::{Decoder, DecodedExchange, DecodeError};
+pub use record::Recorder;
+pub use replay::{Replay, ReplayRng};
+
+const HEADER: &str = "# mrtd1 APDU trace: timestamp ssc command response";
+
+/// A single command/response pair sent over the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exchange {
+	pub timestamp: DateTime<Utc>,
+	/// Send sequence counter used to protect the command, if secure messaging was active
+	pub ssc: Option<u64>,
+	pub command: Vec<u8>,
+	pub response: Vec<u8>,
+}
+
+/// A recorded APDU session.
+///
+/// The text format has one exchange per line, formatted as
+/// `<RFC 3339 timestamp> <SSC or -> <command hex> <response hex>`.
+/// Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Trace {
+	pub exchanges: Vec<Exchange>,
+}
+
+impl Trace {
+	pub fn read<R: BufRead>(reader: R) -> Result<Self> {
+		let mut exchanges = Vec::new();
+		for line in reader.lines() {
+			let line = line?;
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			exchanges.push(line.parse()?);
+		}
+		Ok(Self { exchanges })
+	}
+
+	pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+		writeln!(writer, "{}", HEADER)?;
+		for exchange in &self.exchanges {
+			writeln!(writer, "{}", exchange)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::fmt::Display for Exchange {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} ", self.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Micros, true))?;
+		match self.ssc {
+			Some(ssc) => write!(f, "{:016X} ", ssc)?,
+			None => write!(f, "- ")?,
+		}
+		write!(f, "{:X} {:X}", HexFmt(&self.command), HexFmt(&self.response))
+	}
+}
+
+impl std::str::FromStr for Exchange {
+	type Err = Error;
+	fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+		let parts: Vec<&str> = s.split_whitespace().collect();
+		if parts.len() != 4 {
+			return Err(Error::InvalidLine(s.to_string()));
+		}
+		let timestamp = DateTime::parse_from_rfc3339(parts[0])?.with_timezone(&Utc);
+		let ssc = match parts[1] {
+			"-" => None,
+			ssc => Some(u64::from_str_radix(ssc, 16)?),
+		};
+		Ok(Self { timestamp, ssc, command: parse_hex(parts[2])?, response: parse_hex(parts[3])? })
+	}
+}
+
+fn parse_hex(s: &str) -> std::result::Result<Vec<u8>, Error> {
+	if !s.len().is_multiple_of(2) || !s.is_ascii() {
+		return Err(Error::InvalidHex(s.to_string()));
+	}
+	(0..s.len()).step_by(2)
+		.map(|i| u8::from_str_radix(&s[i..i+2], 16).map_err(|_| Error::InvalidHex(s.to_string())))
+		.collect()
+}
@@ -0,0 +1,92 @@
+use crate::error::Result;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::{ApduResponse, TRAILER_OK};
+use crate::transport::{Transport, AsyncTransport};
+use super::{Exchange, HEADER};
+use chrono::{DateTime, Utc};
+use std::io::Write;
+
+/// A transport wrapper which records every exchange to a writer, in the
+/// [`super::Trace`] text format.
+///
+/// The transport cannot see the send sequence counter of the secure messaging
+/// session, so it has to be provided through [`Recorder::set_ssc`] once
+/// session keys are established. From then on, the SSC is tracked for every
+/// protected command.
+///
+/// Both [`Transport`] and [`AsyncTransport`] are recorded, depending on the
+/// wrapped transport.
+pub struct Recorder<T, W: Write> {
+	inner: T,
+	writer: W,
+	ssc: Option<u64>,
+}
+
+impl<T, W: Write> Recorder<T, W> {
+	pub fn new(inner: T, mut writer: W) -> Result<Self> {
+		writeln!(writer, "{}", HEADER)?;
+		Ok(Self { inner, writer, ssc: None })
+	}
+
+	/// Sets the current send sequence counter, as returned by the authentication protocol.
+	pub fn set_ssc(&mut self, ssc: Option<u64>) {
+		self.ssc = ssc;
+	}
+
+	pub fn into_inner(self) -> (T, W) {
+		(self.inner, self.writer)
+	}
+
+	/// Returns the SSC of the command, if it is protected with secure messaging.
+	fn command_ssc(&mut self, command: &ApduCommand) -> Option<u64> {
+		// Both the command and the response increment the SSC, once the last
+		// command of a chain has been sent
+		let protected = command.cla & 0x0C == 0x0C && command.cla & 0x10 == 0;
+		if !protected {
+			return None;
+		}
+		self.ssc = self.ssc.map(|ssc| ssc.wrapping_add(1));
+		self.ssc
+	}
+
+	fn record(&mut self, timestamp: DateTime<Utc>, ssc: Option<u64>, command: &ApduCommand, res: &ApduResponse) -> Result<()> {
+		// Errors detected by the IC in secure messaging are sent without
+		// secure messaging, and do not increment the SSC
+		let protected = !res.data.is_empty() || res.trailer == TRAILER_OK;
+		if ssc.is_some() && protected {
+			self.ssc = self.ssc.map(|ssc| ssc.wrapping_add(1));
+		}
+		let exchange = Exchange { timestamp, ssc, command: command.to_vec()?, response: res.to_vec() };
+		writeln!(self.writer, "{}", exchange)?;
+		self.writer.flush()?;
+		Ok(())
+	}
+}
+
+impl<T: Transport, W: Write> Transport for Recorder<T, W> {
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		let timestamp = Utc::now();
+		let ssc = self.command_ssc(command);
+		let res = self.inner.transmit(command)?;
+		self.record(timestamp, ssc, command, &res)?;
+		Ok(res)
+	}
+
+	fn max_command_data(&self) -> usize {
+		self.inner.max_command_data()
+	}
+}
+
+impl<T: AsyncTransport + Send, W: Write + Send> AsyncTransport for Recorder<T, W> {
+	async fn transmit(&mut self, command: &ApduCommand<'_>) -> Result<ApduResponse> {
+		let timestamp = Utc::now();
+		let ssc = self.command_ssc(command);
+		let res = self.inner.transmit(command).await?;
+		self.record(timestamp, ssc, command, &res)?;
+		Ok(res)
+	}
+
+	fn max_command_data(&self) -> usize {
+		self.inner.max_command_data()
+	}
+}
\ No newline at end of file
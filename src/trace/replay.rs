@@ -0,0 +1,111 @@
+use crate::error::Result;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::ApduResponse;
+use crate::crypto::tdes::decrypt;
+use crate::mrz::borrowed::MrzData;
+use crate::transport::{Transport, AsyncTransport};
+use super::{Exchange, Trace};
+use super::error::Error;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+/// A transport which serves a recorded session back, in order.
+///
+/// Every command must match the recorded command byte for byte. Since BAC
+/// uses random terminal nonces, [`Replay::bac_rng`] must be used to obtain
+/// the nonces of the recorded session for [`crate::auth::bac::handshake_with_rng`].
+#[derive(Debug, Clone)]
+pub struct Replay {
+	exchanges: Vec<Exchange>,
+	position: usize,
+}
+
+impl Replay {
+	pub fn new(trace: Trace) -> Self {
+		Self { exchanges: trace.exchanges, position: 0 }
+	}
+
+	/// Returns true if every recorded exchange has been served.
+	pub fn is_finished(&self) -> bool {
+		self.position >= self.exchanges.len()
+	}
+
+	/// Recovers the terminal nonces RND.IFD and K.IFD of the recorded BAC
+	/// handshake from the EXTERNAL AUTHENTICATE command, using the MRZ.
+	///
+	/// The generator yields exactly the 24 bytes a single BAC handshake
+	/// consumes, so it cannot be reused for a second handshake.
+	pub fn bac_rng<'a, M: MrzData<'a>>(&self, mrz: &M) -> Result<ReplayRng> {
+		let ext_auth = self.exchanges.iter()
+			.find(|exchange| exchange.command.len() >= 5 + 40 && exchange.command[0] == 0x00 && exchange.command[1] == 0x82)
+			.ok_or(Error::MissingExchange("EXTERNAL AUTHENTICATE command"))?;
+
+		// S = RND.IFD || RND.IC || K.IFD
+		let s = Zeroizing::new(decrypt(&ext_auth.command[5..5+32], &mrz.derive_key(1))?);
+		let mut bytes = Vec::with_capacity(24);
+		bytes.extend_from_slice(&s[0..8]);
+		bytes.extend_from_slice(&s[16..32]);
+		Ok(ReplayRng { bytes, position: 0 })
+	}
+}
+
+impl Transport for Replay {
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		let exchange = self.exchanges.get(self.position).ok_or(Error::EndOfTrace)?;
+		let command = command.to_vec()?;
+		if command != exchange.command {
+			return Err(Error::CommandMismatch { position: self.position, expected: exchange.command.clone(), actual: command }.into());
+		}
+		self.position += 1;
+		Ok(ApduResponse::from(exchange.response.clone()))
+	}
+}
+
+impl AsyncTransport for Replay {
+	fn transmit(&mut self, command: &ApduCommand) -> impl std::future::Future<Output = Result<ApduResponse>> + Send {
+		std::future::ready(Transport::transmit(self, command))
+	}
+}
+
+/// A random number generator which yields a fixed sequence of bytes, in order
+/// to replay the nonces of a recorded session.
+///
+/// # Panics
+///
+/// [`RngCore::fill_bytes`], [`RngCore::next_u32`] and [`RngCore::next_u64`]
+/// panic when more bytes are requested than were recorded, as the replayed
+/// session has then diverged from the recording. [`RngCore::try_fill_bytes`]
+/// returns an error instead.
+#[derive(Debug, Clone)]
+pub struct ReplayRng {
+	bytes: Vec<u8>,
+	position: usize,
+}
+
+impl RngCore for ReplayRng {
+	fn next_u32(&mut self) -> u32 {
+		let mut buf = [0; 4];
+		self.fill_bytes(&mut buf);
+		u32::from_be_bytes(buf)
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		let mut buf = [0; 8];
+		self.fill_bytes(&mut buf);
+		u64::from_be_bytes(buf)
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		self.try_fill_bytes(dest).expect("replay RNG exhausted")
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> std::result::Result<(), rand::Error> {
+		let end = self.position + dest.len();
+		if end > self.bytes.len() {
+			return Err(rand::Error::new("replay RNG exhausted"));
+		}
+		dest.copy_from_slice(&self.bytes[self.position..end]);
+		self.position = end;
+		Ok(())
+	}
+}
@@ -0,0 +1,130 @@
+use crate::error::{Error, Result};
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::{ApduResponse, TRAILER_OK};
+use crate::apdu::response::status::Status;
+use super::{Transport, AsyncTransport, Protocol, Step};
+
+/// Maximum command data length of a short command APDU
+pub const MAX_SHORT_COMMAND_DATA: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransmissionState {
+	Start,
+	Chaining { offset: usize },
+	Final { retried: bool },
+}
+
+/// Transmission of a single command APDU, handling the transmission details of
+/// ISO/IEC 7816-4 on behalf of the caller:
+///
+/// - Command data longer than the reader supports is sent using command
+///   chaining (CLA bit 0x10), as per ISO/IEC 7816-4 section 5.3.3. Commands
+///   which are already part of a chain of the protocol, such as GENERAL
+///   AUTHENTICATE in PACE, are sent as they are
+/// - SW1 = '61' is followed by GET RESPONSE until all response data is received
+/// - SW1 = '6C' re-issues the command with the Le given in SW2, once. Commands
+///   protected by secure messaging are not re-issued, since the IC has already
+///   incremented its send sequence counter for them
+///
+/// The output is the complete response APDU to the original command.
+#[derive(Debug, Clone)]
+pub struct Transmission {
+	state: TransmissionState,
+	command: ApduCommand<'static>,
+	max_command_data: usize,
+	last: Option<ApduCommand<'static>>,
+	data: Vec<u8>,
+}
+
+impl Transmission {
+	pub fn new(command: &ApduCommand, max_command_data: usize) -> Self {
+		Self { state: TransmissionState::Start, command: command.clone().into_owned(), max_command_data: max_command_data.max(1), last: None, data: Vec::new() }
+	}
+
+	fn send(&mut self, command: ApduCommand<'static>) -> Step<ApduResponse> {
+		self.last = Some(command.clone());
+		Step::Transmit(command)
+	}
+
+	/// Sends the command data from the offset on, chaining if it does not fit
+	/// in a single command APDU.
+	fn send_from(&mut self, offset: usize) -> Step<ApduResponse> {
+		let remaining = self.command.data.len() - offset;
+		let chained = self.command.cla & 0x10 != 0;
+		if remaining > self.max_command_data && !chained {
+			let end = offset + self.max_command_data;
+			self.state = TransmissionState::Chaining { offset: end };
+			let command = ApduCommand::new(self.command.cla | 0x10, self.command.ins, self.command.p1, self.command.p2).with_data(self.command.data[offset..end].to_vec());
+			self.send(command)
+		} else {
+			self.state = TransmissionState::Final { retried: false };
+			let command = self.command.clone().with_data(self.command.data[offset..].to_vec());
+			self.send(command)
+		}
+	}
+}
+
+impl Protocol for Transmission {
+	type Output = ApduResponse;
+
+	fn step(&mut self, response: Option<ApduResponse>) -> Result<Step<ApduResponse>> {
+		match (self.state, response) {
+			(TransmissionState::Start, _) => Ok(self.send_from(0)),
+			(TransmissionState::Chaining { offset }, Some(res)) => {
+				// The card refused a part of the chain, so the command as a whole failed
+				if res.trailer != TRAILER_OK {
+					return Ok(Step::Done(res));
+				}
+				Ok(self.send_from(offset))
+			}
+			(TransmissionState::Final { retried }, Some(mut res)) => match res.trailer.status() {
+				// Response bytes still available, SW2 encodes the number of bytes
+				Status::BytesAvailable(n) => {
+					self.data.append(&mut res.data);
+					let rx_len = if n == 0 { 256 } else { n as usize };
+					self.state = TransmissionState::Final { retried: false };
+					// GET RESPONSE is sent without secure messaging or chaining
+					let command = ApduCommand::new(self.command.cla & 0xE3, 0xC0, 0x00, 0x00).with_rx_len(rx_len);
+					Ok(self.send(command))
+				}
+				// Wrong Le field, SW2 encodes the exact number of available bytes
+				Status::WrongLe(n) if !retried && self.command.cla & 0x0C == 0 => {
+					let rx_len = if n == 0 { 256 } else { n as usize };
+					let last = self.last.as_ref().ok_or(Error::ProtocolState)?;
+					self.state = TransmissionState::Final { retried: true };
+					let command = last.clone().with_rx_len(rx_len);
+					Ok(self.send(command))
+				}
+				_ => {
+					self.data.append(&mut res.data);
+					Ok(Step::Done(ApduResponse { data: std::mem::take(&mut self.data), trailer: res.trailer }))
+				}
+			},
+			(_, None) => Err(Error::ProtocolState),
+		}
+	}
+}
+
+/// Transmits a command APDU, handling GET RESPONSE, wrong Le and command
+/// chaining as described in [`Transmission`].
+pub fn transmit<T: Transport + ?Sized>(transport: &mut T, command: &ApduCommand) -> Result<ApduResponse> {
+	let mut transmission = Transmission::new(command, transport.max_command_data());
+	let mut response = None;
+	loop {
+		match transmission.step(response.take())? {
+			Step::Transmit(command) => response = Some(transport.transmit(&command)?),
+			Step::Done(response) => return Ok(response),
+		}
+	}
+}
+
+pub async fn transmit_async<T: AsyncTransport + ?Sized>(transport: &mut T, command: &ApduCommand<'_>) -> Result<ApduResponse> {
+	let mut transmission = Transmission::new(command, transport.max_command_data());
+	let mut response = None;
+	loop {
+		match transmission.step(response.take())? {
+			Step::Transmit(command) => response = Some(transport.transmit(&command).await?),
+			Step::Done(response) => return Ok(response),
+		}
+	}
+}
\ No newline at end of file
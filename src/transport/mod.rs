@@ -0,0 +1,112 @@
+use crate::error::Result;
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::ApduResponse;
+use std::future::Future;
+
+pub mod iso7816;
+#[cfg(feature = "nfc1")]
+mod nfc;
+#[cfg(feature = "pcsc")]
+pub mod pcsc;
+
+/// A channel to a contactless IC, capable of exchanging APDUs.
+///
+/// All protocol logic in this crate is generic over this trait, so that it can
+/// be used with any reader (or without a reader at all).
+pub trait Transport {
+	/// Transmits a command APDU to the IC and returns its response APDU.
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse>;
+
+	/// Maximum length of the command data the reader can send in a single
+	/// command APDU. Longer commands are sent using command chaining.
+	fn max_command_data(&self) -> usize {
+		iso7816::MAX_SHORT_COMMAND_DATA
+	}
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		(**self).transmit(command)
+	}
+
+	fn max_command_data(&self) -> usize {
+		(**self).max_command_data()
+	}
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		(**self).transmit(command)
+	}
+
+	fn max_command_data(&self) -> usize {
+		(**self).max_command_data()
+	}
+}
+
+/// Asynchronous variant of [`Transport`].
+pub trait AsyncTransport {
+	/// Transmits a command APDU to the IC and returns its response APDU.
+	fn transmit(&mut self, command: &ApduCommand) -> impl Future<Output = Result<ApduResponse>> + Send;
+
+	/// Maximum length of the command data the reader can send in a single
+	/// command APDU. Longer commands are sent using command chaining.
+	fn max_command_data(&self) -> usize {
+		iso7816::MAX_SHORT_COMMAND_DATA
+	}
+}
+
+impl<T: AsyncTransport + Send + ?Sized> AsyncTransport for &mut T {
+	fn transmit(&mut self, command: &ApduCommand) -> impl Future<Output = Result<ApduResponse>> + Send {
+		(**self).transmit(command)
+	}
+
+	fn max_command_data(&self) -> usize {
+		(**self).max_command_data()
+	}
+}
+
+/// The next action of a [`Protocol`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step<T> {
+	/// Transmit the command APDU, and resume the protocol with its response.
+	Transmit(ApduCommand<'static>),
+	/// The protocol has completed with the given output.
+	Done(T),
+}
+
+/// A protocol run between the terminal and the IC, written as a state machine
+/// which is independent of how APDUs are exchanged.
+///
+/// The same protocol logic is thereby shared by [`run`] and [`run_async`],
+/// which also take care of the ISO/IEC 7816-4 transmission details through
+/// [`iso7816::transmit`].
+pub trait Protocol {
+	type Output;
+
+	/// Advances the protocol. The first call receives no response, every
+	/// following call receives the response to the previously returned command.
+	fn step(&mut self, response: Option<ApduResponse>) -> Result<Step<Self::Output>>;
+}
+
+/// Runs a protocol to completion over a transport.
+pub fn run<T: Transport + ?Sized, P: Protocol>(transport: &mut T, mut protocol: P) -> Result<P::Output> {
+	let mut response = None;
+	loop {
+		match protocol.step(response.take())? {
+			Step::Transmit(command) => response = Some(iso7816::transmit(transport, &command)?),
+			Step::Done(output) => return Ok(output),
+		}
+	}
+}
+
+/// Runs a protocol to completion over an asynchronous transport.
+pub async fn run_async<T: AsyncTransport + ?Sized, P: Protocol>(transport: &mut T, mut protocol: P) -> Result<P::Output> {
+	let mut response = None;
+	loop {
+		match protocol.step(response.take())? {
+			Step::Transmit(command) => response = Some(iso7816::transmit_async(transport, &command).await?),
+			Step::Done(output) => return Ok(output),
+		}
+	}
+}
@@ -0,0 +1,23 @@
+use crate::error::{Error, Result};
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::ApduResponse;
+use super::Transport;
+use super::iso7816::MAX_SHORT_COMMAND_DATA;
+
+/// libnfc exchanges an APDU in a single frame of the reader, which holds at
+/// most 264 bytes for PN53x readers. Extended length APDUs are sent when a
+/// protocol requires them, but longer command data is sent using command
+/// chaining, so that it fits in a frame.
+impl Transport for nfc1::Device {
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		// Reserve room for the expected response data and SW1-SW2
+		let rx_len = command.rx_len + 2;
+		let res = self.initiator_transceive_bytes(&command.to_vec()?, rx_len, nfc1::Timeout::None)
+			.map_err(|e| Error::Transport(Box::new(e)))?;
+		Ok(ApduResponse::from(res))
+	}
+
+	fn max_command_data(&self) -> usize {
+		MAX_SHORT_COMMAND_DATA
+	}
+}
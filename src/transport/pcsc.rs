@@ -0,0 +1,68 @@
+use crate::error::{Error, Result};
+use crate::apdu::command::ApduCommand;
+use crate::apdu::response::owned::ApduResponse;
+use super::Transport;
+use super::iso7816::MAX_SHORT_COMMAND_DATA;
+use std::ffi::CString;
+
+/// A card present in a PC/SC reader.
+///
+/// Readers are assumed to exchange short APDUs only, and longer command data
+/// is sent using command chaining. Readers which exchange extended length
+/// APDUs with the card, as CCID readers with APDU level exchange do, can be
+/// used with [`Card::with_max_command_data`].
+pub struct Card {
+	card: pcsc::Card,
+	max_command_data: usize,
+}
+
+impl Card {
+	pub fn new(card: pcsc::Card) -> Self {
+		Self { card, max_command_data: MAX_SHORT_COMMAND_DATA }
+	}
+
+	/// Sets the maximum command data length of a single command APDU, for
+	/// example [`MAX_EXTENDED_LC`](crate::apdu::command::MAX_EXTENDED_LC) for
+	/// readers supporting extended length APDUs.
+	pub fn with_max_command_data(mut self, max_command_data: usize) -> Self {
+		self.max_command_data = max_command_data;
+		self
+	}
+
+	pub fn into_inner(self) -> pcsc::Card {
+		self.card
+	}
+}
+
+/// Connects to the card present in a PC/SC reader.
+///
+/// If `reader` is given, the first reader whose name contains it is used (for
+/// example `"Virtual PCD"` for a vpcd virtual reader), otherwise the first
+/// available reader is used.
+pub fn connect(reader: Option<&str>) -> Result<Card> {
+	let ctx = pcsc::Context::establish(pcsc::Scope::User).map_err(transport_error)?;
+	let readers = ctx.list_readers_owned().map_err(transport_error)?;
+	let reader: &CString = readers.iter()
+		.find(|name| reader.map(|reader| name.to_string_lossy().contains(reader)).unwrap_or(true))
+		.ok_or_else(|| Error::Transport("No matching PC/SC reader found".into()))?;
+	let card = ctx.connect(reader, pcsc::ShareMode::Shared, pcsc::Protocols::ANY).map_err(transport_error)?;
+	Ok(Card::new(card))
+}
+
+impl Transport for Card {
+	fn transmit(&mut self, command: &ApduCommand) -> Result<ApduResponse> {
+		// Reserve room for the expected response data and SW1-SW2
+		let rx_len = if command.case()?.is_extended() { pcsc::MAX_BUFFER_SIZE_EXTENDED } else { pcsc::MAX_BUFFER_SIZE };
+		let mut rx_buf = vec![0; (command.rx_len + 2).max(rx_len)];
+		let res = self.card.transmit(&command.to_vec()?, &mut rx_buf).map_err(transport_error)?;
+		Ok(ApduResponse::from(res.to_vec()))
+	}
+
+	fn max_command_data(&self) -> usize {
+		self.max_command_data
+	}
+}
+
+fn transport_error(e: pcsc::Error) -> Error {
+	Error::Transport(Box::new(e))
+}
\ No newline at end of file
@@ -0,0 +1,120 @@
+//! Worked example of ICAO 9303 MRTD v8 2021 Part 11, Appendix D to Part 11:
+//! Basic Access Control and Secure Messaging
+
+mod common;
+
+use common::{hex, FixedRng};
+use digest::{FixedOutputReset, KeyInit, Mac};
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::ApduResponse;
+use mrtd1::auth::bac;
+use mrtd1::crypto::des::mac;
+use mrtd1::crypto::retail_mac::RetailMac;
+use mrtd1::crypto::{kdf, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC};
+use mrtd1::mrz::borrowed::{Mrz, MrzData};
+use mrtd1::sm::{CipherSuite, SecureMessaging, Session};
+use mrtd1::transport::Transport;
+
+const MRZ: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C<3UTO6908061F9406236ZE184226B<<<<<14";
+
+const K_SEED: &str = "239AB9CB282DAF66231DC5A4DF6BFBAE";
+const K_ENC: &str = "AB94FDECF2674FDFB9B391F85D7F76F2";
+const K_MAC: &str = "7962D9ECE03D1ACD4C76089DCE131543";
+
+const RND_IC: &str = "4608F91988702212";
+const RND_IFD: &str = "781723860C06C226";
+const K_IFD: &str = "0B795240CB7049B01C19B33E32804F0B";
+const E_IFD: &str = "72C29C2371CC9BDB65B779B8E8D37B29ECC154AA56A8799FAE2F498F76ED92F2";
+const M_IFD: &str = "5F1448EEA8AD90A7";
+const E_IC: &str = "46B9342A41396CD7386BF5803104D7CEDC122B9132139BAF2EEDC94EE178534F";
+const M_IC: &str = "2F2D235D074D7449";
+
+const KS_ENC: &str = "979EC13B1CBFE9DCD01AB0FED307EAE5";
+const KS_MAC: &str = "F1CB1F1FB5ADF208806B89DC579DC1F8";
+const SSC: u64 = 0x887022120C06C226;
+
+/// The protected command and response APDUs of D.4, with their plain counterparts.
+const EXCHANGES: [(&str, &str, &str, &str); 3] = [
+	// SELECT EF.COM
+	("00A4020C02011E", "0CA4020C158709016375432908C044F68E08BF8B92D635FF24F800", "990290008E08FA855A5D4C50A8ED9000", "9000"),
+	// READ BINARY of the first four bytes
+	("00B0000004", "0CB000000D9701048E08ED6705417E96BA5500", "8709019FF0EC34F9922651990290008E08AD55CC17140B2DED9000", "60145F019000"),
+	// READ BINARY of the remaining 18 bytes
+	("00B0000412", "0CB000040D9701128E082EA28A70F3C7B53500", "871901FB9235F4E4037F2327DCC8964F1F9B8C30F42C8E2FFF224A990290008E08C8B2787EAEA07D749000", "04303130365F36063034303030305C0261759000"),
+];
+
+/// Answers the commands of D.3 with the responses of the IC.
+struct Chip(Vec<(Vec<u8>, Vec<u8>)>);
+
+impl Transport for Chip {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		let (expected, response) = self.0.remove(0);
+		assert_eq!(command.to_vec().unwrap(), expected);
+		Ok(ApduResponse::from(response))
+	}
+}
+
+#[test]
+fn d2_compute_keys_from_key_seed() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	assert_eq!(&mrz.derive_seed_key()[..], &hex(K_SEED)[..]);
+	assert_eq!(&kdf(&hex(K_SEED), COUNTER_ENC, KeyAlgorithm::Tdes)[..], &hex(K_ENC)[..]);
+	assert_eq!(&kdf(&hex(K_SEED), COUNTER_MAC, KeyAlgorithm::Tdes)[..], &hex(K_MAC)[..]);
+}
+
+#[test]
+fn d3_authentication_and_establishment_of_session_keys() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let mut chip = Chip(vec![
+		(hex("00A4040C07A0000002471001"), hex("9000")),
+		(hex("0084000008"), [hex(RND_IC), hex("9000")].concat()),
+		([hex("0082000028"), hex(E_IFD), hex(M_IFD), hex("28")].concat(), [hex(E_IC), hex(M_IC), hex("9000")].concat()),
+	]);
+	let mut rng = FixedRng([hex(RND_IFD), hex(K_IFD)].concat());
+	let session = bac::handshake_with_rng(&mut chip, &mrz, &mut rng).unwrap();
+	assert!(chip.0.is_empty());
+	assert_eq!(session, Session::new(CipherSuite::Tdes, hex(KS_ENC), hex(KS_MAC), SSC));
+}
+
+#[test]
+fn d4_secure_messaging() {
+	let mut terminal = Session::new(CipherSuite::Tdes, hex(KS_ENC), hex(KS_MAC), SSC);
+	let mut chip = terminal.clone();
+	for (i, (command, protected_command, protected_response, response)) in EXCHANGES.iter().enumerate() {
+		let command = ApduCommand::try_from(&hex(command)[..]).unwrap().into_owned();
+		let protected_command = ApduCommand::try_from(&hex(protected_command)[..]).unwrap().into_owned();
+
+		// Terminal side
+		assert_eq!(terminal.wrap_command(&command).unwrap(), protected_command);
+		assert_eq!(terminal.ssc(), SSC + 2 * i as u64 + 1);
+		assert_eq!(terminal.unwrap_response(ApduResponse::from(hex(protected_response))).unwrap(), ApduResponse::from(hex(response)));
+		assert_eq!(terminal.ssc(), SSC + 2 * i as u64 + 2);
+
+		// IC side
+		assert_eq!(chip.unwrap_command(&protected_command).unwrap(), command);
+		assert_eq!(chip.wrap_response(command.ins, &ApduResponse::from(hex(response))).unwrap(), ApduResponse::from(hex(protected_response)));
+	}
+}
+
+#[test]
+fn retail_mac() {
+	// M_IFD and M_IC of D.3, and the checksum of the protected SELECT command of D.4 over N
+	let cases = [(K_MAC, E_IFD, M_IFD), (K_MAC, E_IC, M_IC), (KS_MAC, "887022120C06C2270CA4020C800000008709016375432908C044F6", "BF8B92D635FF24F8")];
+	for (key, data, expected) in cases {
+		let (data, expected) = (hex(data), hex(expected));
+		assert_eq!(mac(&data, &hex(key)).unwrap(), expected);
+		let mut retail_mac = <RetailMac as KeyInit>::new_from_slice(&hex(key)).unwrap();
+		for chunk_len in [1, 3, 7, 8, 9] {
+			for chunk in data.chunks(chunk_len) {
+				Mac::update(&mut retail_mac, chunk);
+			}
+			assert_eq!(retail_mac.finalize_fixed_reset().to_vec(), expected);
+		}
+
+		// A reset discards data of a partial block
+		Mac::update(&mut retail_mac, &[0xFF; 5]);
+		Mac::reset(&mut retail_mac);
+		Mac::update(&mut retail_mac, &data);
+		retail_mac.verify_slice(&expected).unwrap();
+	}
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::hex;
+use mrtd1::auth::bac;
+use mrtd1::files::{read_file_async, EF_COM};
+use mrtd1::mrz::borrowed::Mrz;
+use mrtd1::simulator::VirtualChip;
+use mrtd1::trace::{Recorder, Trace};
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+/// The MRZ of ICAO 9303 Part 11, Appendix D
+const MRZ: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C<3UTO6908061F9406236ZE184226B<<<<<14";
+
+/// EF.COM of Appendix D.4
+const EF_COM_DATA: &str = "60145F0104303130365F36063034303030305C026175";
+
+fn assert_send<T: Send>(_: &T) {}
+
+/// Polls a future to completion. The simulated chip never returns pending,
+/// so no executor is needed.
+fn block_on<F: Future>(future: F) -> F::Output {
+	let mut future = pin!(future);
+	let mut cx = Context::from_waker(Waker::noop());
+	loop {
+		if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+			return output;
+		}
+	}
+}
+
+#[test]
+fn handshake_async_is_send() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let mut chip = VirtualChip::new(&mrz).with_file(EF_COM.fileid, hex(EF_COM_DATA));
+
+	let handshake = bac::handshake_async(&mut chip, &mrz);
+	assert_send(&handshake);
+	let mut session = block_on(handshake).unwrap();
+
+	let read = read_file_async(&mut chip, &mut session, &EF_COM);
+	assert_send(&read);
+	assert_eq!(block_on(read).unwrap(), hex(EF_COM_DATA));
+}
+
+#[test]
+fn record_async() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let chip = VirtualChip::new(&mrz).with_file(EF_COM.fileid, hex(EF_COM_DATA));
+	let mut recorder = Recorder::new(chip, Vec::new()).unwrap();
+
+	let handshake = bac::handshake_async(&mut recorder, &mrz);
+	assert_send(&handshake);
+	let mut session = block_on(handshake).unwrap();
+	let ssc = session.ssc();
+	recorder.set_ssc(Some(ssc));
+	assert_eq!(block_on(read_file_async(&mut recorder, &mut session, &EF_COM)).unwrap(), hex(EF_COM_DATA));
+
+	// SELECT, GET CHALLENGE and EXTERNAL AUTHENTICATE are sent without secure messaging,
+	// every following command and response increments the SSC
+	let (_, writer) = recorder.into_inner();
+	let trace = Trace::read(writer.as_slice()).unwrap();
+	assert!(trace.exchanges.len() > 3);
+	assert!(trace.exchanges[..3].iter().all(|exchange| exchange.ssc.is_none()));
+	for (i, exchange) in trace.exchanges[3..].iter().enumerate() {
+		assert_eq!(exchange.ssc, Some(ssc + 2 * i as u64 + 1));
+	}
+	assert_eq!(session.ssc(), ssc + 2 * (trace.exchanges.len() as u64 - 3));
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::hex;
+use mrtd1::apdu::command::{ApduCommand, Case};
+use mrtd1::apdu::commands::*;
+use mrtd1::apdu::error::Error;
+
+fn encode(command: &ApduCommand) -> Vec<u8> {
+	command.to_vec().unwrap()
+}
+
+fn parse(raw: &[u8]) -> (Case, ApduCommand<'_>) {
+	(Case::detect(raw).unwrap(), ApduCommand::try_from(raw).unwrap())
+}
+
+#[test]
+fn parse_cases() {
+	let raw = hex("00A4030C");
+	assert_eq!(parse(&raw), (Case::Case1, ApduCommand::new(0x00, 0xA4, 0x03, 0x0C)));
+
+	let raw = hex("0084000008");
+	assert_eq!(parse(&raw), (Case::Case2Short, ApduCommand::new(0x00, 0x84, 0x00, 0x00).with_rx_len(8)));
+	let raw = hex("00B0000000");
+	assert_eq!(parse(&raw), (Case::Case2Short, ApduCommand::new(0x00, 0xB0, 0x00, 0x00).with_rx_len(256)));
+
+	let raw = hex("00A4020C02011E");
+	assert_eq!(parse(&raw), (Case::Case3Short, ApduCommand::new(0x00, 0xA4, 0x02, 0x0C).with_data(hex("011E"))));
+
+	let raw = hex("00880000020102 00");
+	assert_eq!(parse(&raw), (Case::Case4Short, ApduCommand::new(0x00, 0x88, 0x00, 0x00).with_data(hex("0102")).with_rx_len(256)));
+
+	let raw = hex("00B0000000 0101");
+	assert_eq!(parse(&raw), (Case::Case2Extended, ApduCommand::new(0x00, 0xB0, 0x00, 0x00).with_rx_len(257)));
+	let raw = hex("00B0000000 0000");
+	assert_eq!(parse(&raw), (Case::Case2Extended, ApduCommand::new(0x00, 0xB0, 0x00, 0x00).with_rx_len(65536)));
+
+	let raw = [hex("00D6000000 0100"), vec![0xAA; 256]].concat();
+	assert_eq!(parse(&raw), (Case::Case3Extended, ApduCommand::new(0x00, 0xD6, 0x00, 0x00).with_data(vec![0xAA; 256])));
+
+	let raw = hex("0086000000 0002 7C00 0000");
+	assert_eq!(parse(&raw), (Case::Case4Extended, ApduCommand::new(0x00, 0x86, 0x00, 0x00).with_data(hex("7C00")).with_rx_len(65536)));
+
+	// Parsing and encoding are each other's inverse
+	for raw in ["00A4030C", "0084000008", "00A4020C02011E", "0088000002010200", "00B00000000101", "00860000000002 7C00 0000"] {
+		assert_eq!(encode(&ApduCommand::try_from(&hex(raw)[..]).unwrap()), hex(raw));
+	}
+}
+
+#[test]
+fn parse_errors() {
+	assert_eq!(ApduCommand::try_from(&hex("00A402")[..]), Err(Error::TooShort));
+	assert_eq!(ApduCommand::try_from(&[][..]), Err(Error::TooShort));
+
+	// Extended Lc of zero, or a truncated extended length field
+	assert_eq!(ApduCommand::try_from(&hex("00A4020C 0000")[..]), Err(Error::InvalidLc));
+	assert_eq!(ApduCommand::try_from(&hex("00A4020C 000000 0000")[..]), Err(Error::InvalidLc));
+
+	// Lc does not match the length of the command data
+	assert_eq!(ApduCommand::try_from(&hex("00A4020C 03 011E")[..]), Err(Error::InvalidLength { lc: 3, body_len: 3 }));
+	assert_eq!(ApduCommand::try_from(&hex("00A4020C 01 011E0000")[..]), Err(Error::InvalidLength { lc: 1, body_len: 5 }));
+	assert_eq!(ApduCommand::try_from(&hex("00D60000 000003 0102")[..]), Err(Error::InvalidLength { lc: 3, body_len: 5 }));
+}
+
+#[test]
+fn encode_le() {
+	let command = ApduCommand::new(0x00, 0xB0, 0x00, 0x00);
+	assert_eq!(encode(&command.clone().with_rx_len(1)), hex("00B0000001"));
+	assert_eq!(encode(&command.clone().with_rx_len(255)), hex("00B00000FF"));
+	assert_eq!(encode(&command.clone().with_rx_len(256)), hex("00B0000000"));
+	assert_eq!(encode(&command.clone().with_rx_len(257)), hex("00B00000000101"));
+	assert_eq!(encode(&command.clone().with_rx_len(65535)), hex("00B0000000FFFF"));
+	assert_eq!(encode(&command.clone().with_rx_len(65536)), hex("00B00000000000"));
+	assert_eq!(command.clone().with_rx_len(65537).to_vec(), Err(Error::RxLenTooLong(65537)));
+}
+
+#[test]
+fn encode_lc() {
+	let command = ApduCommand::new(0x00, 0xD6, 0x00, 0x00);
+	assert_eq!(encode(&command.clone().with_data(vec![0xAA; 255])), [hex("00D60000FF"), vec![0xAA; 255]].concat());
+	assert_eq!(encode(&command.clone().with_data(vec![0xAA; 256])), [hex("00D6000000 0100"), vec![0xAA; 256]].concat());
+	assert_eq!(encode(&command.clone().with_data(vec![0xAA; 65535])), [hex("00D6000000 FFFF"), vec![0xAA; 65535]].concat());
+	assert_eq!(command.clone().with_data(vec![0xAA; 65536]).to_vec(), Err(Error::DataTooLong(65536)));
+
+	// Case 4 is extended if either Lc or Le is, with a two byte Le after extended Lc
+	assert_eq!(encode(&command.clone().with_data(vec![0x01]).with_rx_len(256)), hex("00D600000101 00"));
+	assert_eq!(encode(&command.clone().with_data(vec![0x01]).with_rx_len(65536)), hex("00D60000000001 01 0000"));
+	assert_eq!(encode(&command.clone().with_data(vec![0xAA; 256]).with_rx_len(256)), [hex("00D6000000 0100"), vec![0xAA; 256], hex("0100")].concat());
+}
+
+#[test]
+fn builders() {
+	assert_eq!(encode(&select_aid(AID_EMRTD)), hex("00A4040C07 A0000002471001"));
+	assert_eq!(encode(&select_fid(0x011E)), hex("00A4020C02 011E"));
+	assert_eq!(encode(&get_challenge(8)), hex("0084000008"));
+
+	// ICAO 9303 Part 11 Appendix G.1: MSE:Set AT with id-PACE-ECDH-GM-AES-CBC-CMAC-128, MRZ and parameter ID 13
+	let oid = hex("04007F00070202040202");
+	assert_eq!(encode(&mse_set_at_pace(&oid, 1, Some(0x0D)).unwrap()), hex("0022C1A412 800A04007F00070202040202 830101 84010D"));
+	assert_eq!(encode(&general_authenticate(&[], false).unwrap()), hex("1086000002 7C00 00"));
+	assert_eq!(encode(&general_authenticate(&[(0x85, &[1, 2, 3, 4, 5, 6, 7, 8])], true).unwrap()), hex("008600000C 7C0A 85080102030405060708 00"));
+	assert_eq!(encode(&update_binary(0x8000, &[0x09][..]).unwrap()), hex("00D7000007 54028000 530109"));
+}
+
+#[test]
+fn data_object_length() {
+	let ga = general_authenticate(&[(0x81, &[0x00; 0x80])], false).unwrap();
+	assert_eq!(ga.data[..6], hex("7C8183 818180"));
+	let ga = general_authenticate(&[(0x81, &[0x00; 0x100])], false).unwrap();
+	assert_eq!(ga.data[..8], hex("7C820104 81820100"));
+
+	// Values that do not fit a two byte length are rejected instead of truncated
+	let value = vec![0x00; 0x10000];
+	assert_eq!(general_authenticate(&[(0x81, &value)], false), Err(Error::DataTooLong(0x10000)));
+	assert_eq!(mse_set_kat(&value, None), Err(Error::DataTooLong(0x10000)));
+	assert_eq!(update_binary(0x8000, &value[..]), Err(Error::DataTooLong(0x10000)));
+	assert_eq!(general_authenticate(&[(0x81, &value[..0xFFFC])], false), Err(Error::DataTooLong(0x10000)));
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_names() {
+	use mrtd1::apdu::command::{borrowed, owned};
+
+	assert_eq!(mrtd1::auth::bac::apdu_external_authenticate(&[0x01; 40]), mutual_authenticate(&[0x01; 40][..]));
+	assert_eq!(mrtd1::files::apdu_read_binary(0x20, 0x0104), read_binary(0x20, 0x0104));
+	let command: owned::ApduCommand = borrowed::ApduCommand::new(0x00, 0x84, 0x00, 0x00).with_rx_len(8);
+	assert_eq!(encode(&command), hex("0084000008"));
+	let session: mrtd1::auth::bac::SessionKeys = mrtd1::auth::bac::derive_session_keys(&[0x01; 16], &[0x02; 16], &[0x11; 8], &[0x22; 8]);
+	assert_eq!(session.ssc(), 0x1111111122222222);
+}
@@ -0,0 +1,72 @@
+#![allow(dead_code)]
+
+/// Decodes a hexadecimal string, ignoring whitespace.
+pub fn hex(s: &str) -> Vec<u8> {
+	let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+	(0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+}
+
+/// Returns the given bytes in order, for protocol runs with known nonces and keys.
+///
+/// Panics when more bytes are requested than were given, unless through
+/// `try_fill_bytes`.
+#[derive(Debug, Clone)]
+pub struct FixedRng(pub Vec<u8>);
+
+impl rand::RngCore for FixedRng {
+	fn next_u32(&mut self) -> u32 {
+		let mut buf = [0; 4];
+		self.fill_bytes(&mut buf);
+		u32::from_be_bytes(buf)
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		let mut buf = [0; 8];
+		self.fill_bytes(&mut buf);
+		u64::from_be_bytes(buf)
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		self.try_fill_bytes(dest).expect("fixed RNG exhausted")
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+		if dest.len() > self.0.len() {
+			return Err(rand::Error::new("fixed RNG exhausted"));
+		}
+		dest.copy_from_slice(&self.0[..dest.len()]);
+		self.0.drain(..dest.len());
+		Ok(())
+	}
+}
+
+/// Encodes a BER-TLV data object.
+pub fn tlv(tag: &[u8], value: &[u8]) -> Vec<u8> {
+	let mut data_object = tag.to_vec();
+	match value.len() {
+		len @ 0..=0x7F => data_object.push(len as u8),
+		len @ 0x80..=0xFF => data_object.extend([0x81, len as u8]),
+		len => data_object.extend([0x82, (len >> 8) as u8, len as u8]),
+	}
+	data_object.extend_from_slice(value);
+	data_object
+}
+
+/// Returns the tag and value of the data object with a single byte tag at the start of the data.
+pub fn parse_tlv(data: &[u8]) -> (u8, Vec<u8>) {
+	let (len, header_len) = match data[1] {
+		0x81 => (data[2] as usize, 3),
+		0x82 => (((data[2] as usize) << 8) | data[3] as usize, 4),
+		len => (len as usize, 2),
+	};
+	(data[0], data[header_len..header_len + len].to_vec())
+}
+
+/// Returns the data object with the given tag in the dynamic authentication data DO'7C'.
+pub fn dynamic_authentication_data(data: &[u8], tag: u8) -> Vec<u8> {
+	let (template_tag, template) = parse_tlv(data);
+	assert_eq!(template_tag, 0x7C);
+	let (data_object_tag, value) = parse_tlv(&template);
+	assert_eq!(data_object_tag, tag);
+	value
+}
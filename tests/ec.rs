@@ -0,0 +1,50 @@
+use mrtd1::crypto::ec::*;
+use num_bigint::BigUint;
+
+const CURVES: [Curve; 11] = [CURVE_SECP192R1, CURVE_SECP224R1, CURVE_SECP256R1, CURVE_SECP384R1, CURVE_SECP521R1, CURVE_BRAINPOOLP192R1, CURVE_BRAINPOOLP224R1, CURVE_BRAINPOOLP256R1, CURVE_BRAINPOOLP320R1, CURVE_BRAINPOOLP384R1, CURVE_BRAINPOOLP512R1];
+
+#[test]
+fn group_law() {
+	for curve in CURVES {
+		let g = curve.generator();
+		let n = curve.order();
+		assert!(curve.contains(&g), "{}", curve.name);
+		let g2 = curve.mul(&BigUint::from(2u8), &g).unwrap();
+		let g3 = curve.mul(&BigUint::from(3u8), &g).unwrap();
+		assert!(curve.contains(&g3), "{}", curve.name);
+		assert_eq!(curve.add(&g, &g), Some(g2.clone()), "{}", curve.name);
+		assert_eq!(curve.add(&g2, &g), Some(g3.clone()), "{}", curve.name);
+		assert_eq!(curve.mul(&BigUint::from(1u8), &g), Some(g.clone()), "{}", curve.name);
+
+		// Multiples of n are the point at infinity, and scalars are reduced modulo n
+		assert_eq!(curve.mul(&BigUint::ZERO, &g), None, "{}", curve.name);
+		assert_eq!(curve.mul(&n, &g), None, "{}", curve.name);
+		assert_eq!(curve.mul(&(&n + 3u8), &g), Some(g3.clone()), "{}", curve.name);
+		let minus_g = curve.mul(&(&n - 1u8), &g).unwrap();
+		assert_eq!(curve.add(&minus_g, &g), None, "{}", curve.name);
+
+		// (k1 + k2) G = k1 G + k2 G and k1 (k2 G) = k2 (k1 G)
+		let mut rng = rand::thread_rng();
+		let k1 = curve.random_scalar(&mut rng);
+		let k2 = curve.random_scalar(&mut rng);
+		let k1_g = curve.mul(&k1, &g).unwrap();
+		let k2_g = curve.mul(&k2, &g).unwrap();
+		assert_eq!(curve.mul(&(&k1 + &k2), &g), curve.add(&k1_g, &k2_g), "{}", curve.name);
+		assert_eq!(curve.mul(&k1, &k2_g), curve.mul(&k2, &k1_g), "{}", curve.name);
+	}
+}
+
+#[test]
+fn point_encoding() {
+	for curve in CURVES {
+		let point = curve.mul(&BigUint::from(3u8), &curve.generator()).unwrap();
+		let encoded = curve.encode_point(&point);
+		assert_eq!(encoded.len(), 1 + 2 * curve.field_len());
+		assert_eq!(curve.decode_point(&encoded), Some(point), "{}", curve.name);
+
+		let mut invalid = encoded.clone();
+		*invalid.last_mut().unwrap() ^= 0x01;
+		assert_eq!(curve.decode_point(&invalid), None, "{}", curve.name);
+		assert_eq!(curve.decode_point(&encoded[1..]), None, "{}", curve.name);
+	}
+}
@@ -0,0 +1,153 @@
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::{ApduResponse, ApduResponseTrailer, TRAILER_FILE_NOT_FOUND, TRAILER_OK};
+use mrtd1::auth::bac::handshake;
+use mrtd1::files::{read_file_by_sfi, read_file, File, EF_COM, EF_DG1};
+use mrtd1::mrz::borrowed::Mrz;
+use mrtd1::simulator::VirtualChip;
+use mrtd1::sm::SecureMessaging;
+use mrtd1::transport::Transport;
+
+const MRZ: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C36UTO7408122F1204159ZE184226B<<<<<10";
+
+/// Records the INS and P1 of each command.
+struct Chip {
+	chip: VirtualChip,
+	log: Vec<(u8, u8)>,
+}
+
+impl Transport for Chip {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		self.log.push((command.ins, command.p1));
+		Transport::transmit(&mut self.chip, command)
+	}
+}
+
+fn chip(mrz: &Mrz) -> Chip {
+	let chip = VirtualChip::new(mrz)
+		.with_file(0x011E, vec![0x60, 0x05, 1, 2, 3, 4, 5])
+		.with_file(0x0101, vec![0x61, 0x03, 9, 9, 9])
+		.with_file(0x0201, vec![0x42, 0x02, 7, 7]);
+	Chip { chip, log: vec![] }
+}
+
+/// Secure messaging which leaves APDUs as they are, apart from the status
+/// word of errors, which is sent in DO'99'.
+struct Plain;
+
+impl SecureMessaging for Plain {
+	fn wrap_command(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduCommand<'static>> {
+		Ok(command.clone().into_owned())
+	}
+
+	fn unwrap_response(&mut self, response: ApduResponse) -> mrtd1::Result<ApduResponse> {
+		match response.data[..] {
+			[0x99, 0x02, sw1, sw2] => Ok(ApduResponse { data: vec![], trailer: ApduResponseTrailer { sw1, sw2 } }),
+			_ => Ok(response),
+		}
+	}
+}
+
+/// Serves EF.DG1 in plain, rejecting reads by SFI with the given status word.
+struct PlainChip {
+	log: Vec<(u8, u8)>,
+	sfi_trailer: ApduResponseTrailer,
+	selected: bool,
+}
+
+impl Transport for PlainChip {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		self.log.push((command.ins, command.p1));
+		let dg1 = [0x61, 0x03, 9, 9, 9];
+		let (data, trailer) = match (command.ins, command.p1) {
+			(0xA4, 0x02) if command.data[..] == [0x01, 0x01] => {
+				self.selected = true;
+				(vec![], TRAILER_OK)
+			}
+			(0xB0, p1) if p1 & 0x80 != 0 => (vec![0x99, 0x02, self.sfi_trailer.sw1, self.sfi_trailer.sw2], self.sfi_trailer),
+			(0xB0, 0x00) if self.selected => {
+				let offset = command.p2 as usize;
+				(dg1[offset..(offset + command.rx_len).min(dg1.len())].to_vec(), TRAILER_OK)
+			}
+			_ => (vec![], TRAILER_FILE_NOT_FOUND),
+		};
+		Ok(ApduResponse { data, trailer })
+	}
+}
+
+#[test]
+fn read_by_sfi() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let mut chip = chip(&mrz);
+	let mut session = handshake(&mut chip, &mrz).unwrap();
+
+	chip.log.clear();
+	assert_eq!(read_file_by_sfi(&mut chip, &mut session, &EF_COM).unwrap(), vec![0x60, 0x05, 1, 2, 3, 4, 5]);
+	assert_eq!(chip.log, vec![(0xB0, 0x9E), (0xB0, 0x00)]);
+	assert_eq!(read_file_by_sfi(&mut chip, &mut session, &EF_DG1).unwrap(), vec![0x61, 0x03, 9, 9, 9]);
+	assert_eq!(read_file(&mut chip, &mut session, &EF_COM).unwrap(), vec![0x60, 0x05, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn read_by_sfi_fallback() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let mut chip = chip(&mrz);
+	let mut session = handshake(&mut chip, &mrz).unwrap();
+
+	// SFI unknown to the IC
+	chip.log.clear();
+	let file = File { fileid: 0x0201, sfi: Some(0x05), ..EF_DG1 };
+	assert_eq!(read_file_by_sfi(&mut chip, &mut session, &file).unwrap(), vec![0x42, 0x02, 7, 7]);
+	assert_eq!(chip.log, vec![(0xB0, 0x85), (0xA4, 0x02), (0xB0, 0x00)]);
+
+	for sw in [[0x6A, 0x81], [0x6A, 0x82], [0x6A, 0x86], [0x69, 0x81], [0x69, 0x86]] {
+		let mut chip = PlainChip { log: vec![], sfi_trailer: ApduResponseTrailer { sw1: sw[0], sw2: sw[1] }, selected: false };
+		assert_eq!(read_file_by_sfi(&mut chip, &mut Plain, &EF_DG1).unwrap(), vec![0x61, 0x03, 9, 9, 9]);
+		assert_eq!(chip.log, vec![(0xB0, 0x81), (0xA4, 0x02), (0xB0, 0x00), (0xB0, 0x00)]);
+	}
+}
+
+#[test]
+fn read_by_sfi_error() {
+	// Access conditions and other errors are not a reason to select the file
+	for sw in [[0x69, 0x82], [0x62, 0x82], [0x6F, 0x00]] {
+		let mut chip = PlainChip { log: vec![], sfi_trailer: ApduResponseTrailer { sw1: sw[0], sw2: sw[1] }, selected: false };
+		let err = read_file_by_sfi(&mut chip, &mut Plain, &EF_DG1).unwrap_err();
+		assert_eq!(err.trailer().map(|t| [t.sw1, t.sw2]), Some(sw));
+		assert_eq!(chip.log, vec![(0xB0, 0x81)]);
+	}
+
+	// Files missing from the IC are reported
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let mut chip = chip(&mrz);
+	let mut session = handshake(&mut chip, &mrz).unwrap();
+	let err = read_file(&mut chip, &mut session, &File { fileid: 0x0999, ..EF_DG1 }).unwrap_err();
+	assert_eq!(err.trailer().map(|t| [t.sw1, t.sw2]), Some([0x6A, 0x82]));
+	assert_eq!(read_file(&mut chip, &mut session, &EF_COM).unwrap(), vec![0x60, 0x05, 1, 2, 3, 4, 5]);
+}
+
+/// Rejects reads by SFI with an unprotected status word, which ends secure
+/// messaging.
+struct UnprotectedSfiError(Chip);
+
+impl Transport for UnprotectedSfiError {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		let res = self.0.transmit(command)?;
+		if command.ins == 0xB0 && command.p1 & 0x80 != 0 {
+			self.0.chip.reset();
+			return Ok(ApduResponse { data: vec![], trailer: TRAILER_FILE_NOT_FOUND });
+		}
+		Ok(res)
+	}
+}
+
+#[test]
+fn read_by_sfi_unprotected_error() {
+	// The session is out of step with the IC, so SELECT is not sent
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let mut chip = UnprotectedSfiError(chip(&mrz));
+	let mut session = handshake(&mut chip, &mrz).unwrap();
+	chip.0.log.clear();
+	let err = read_file_by_sfi(&mut chip, &mut session, &EF_DG1).unwrap_err();
+	assert_eq!(err.trailer(), Some(TRAILER_FILE_NOT_FOUND));
+	assert_eq!(chip.0.log, vec![(0xB0, 0x81)]);
+}
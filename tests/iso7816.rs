@@ -0,0 +1,103 @@
+mod common;
+
+use common::hex;
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::ApduResponse;
+use mrtd1::transport::{iso7816, Transport};
+
+/// Expects the given command APDUs in order, answering each with its response APDU.
+struct Script {
+	exchanges: Vec<(Vec<u8>, Vec<u8>)>,
+	max_command_data: usize,
+}
+
+impl Script {
+	fn new(exchanges: &[(&str, &str)]) -> Self {
+		Self { exchanges: exchanges.iter().map(|(command, response)| (hex(command), hex(response))).collect(), max_command_data: iso7816::MAX_SHORT_COMMAND_DATA }
+	}
+}
+
+impl Transport for Script {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		assert!(!self.exchanges.is_empty(), "unexpected command {:02X?}", command);
+		let (expected, response) = self.exchanges.remove(0);
+		assert_eq!(command.to_vec().unwrap(), expected);
+		Ok(ApduResponse::from(response))
+	}
+
+	fn max_command_data(&self) -> usize {
+		self.max_command_data
+	}
+}
+
+fn transmit(script: &mut Script, command: &str) -> ApduResponse {
+	let command = hex(command);
+	let response = iso7816::transmit(script, &ApduCommand::try_from(&command[..]).unwrap()).unwrap();
+	assert!(script.exchanges.is_empty());
+	response
+}
+
+#[test]
+fn get_response() {
+	// Response data is concatenated over GET RESPONSE until SW1 is no longer 61
+	let mut script = Script::new(&[
+		("0086000002 7C00 00", "7C0A 6102"),
+		("00C0000002", "8108 6100"),
+		("00C0000000", "0102030405060708 9000"),
+	]);
+	assert_eq!(transmit(&mut script, "0086000002 7C00 00"), ApduResponse::from(hex("7C0A 8108 0102030405060708 9000")));
+
+	// GET RESPONSE is sent without secure messaging or chaining bits
+	let mut script = Script::new(&[("1C86000001 01 00", "01 6101"), ("00C0000001", "02 6282")]);
+	assert_eq!(transmit(&mut script, "1C86000001 01 00"), ApduResponse::from(hex("0102 6282")));
+}
+
+#[test]
+fn wrong_le() {
+	// Re-issued once with the Le of SW2
+	let mut script = Script::new(&[("00B000000A", "6C05"), ("00B0000005", "0102030405 9000")]);
+	assert_eq!(transmit(&mut script, "00B000000A"), ApduResponse::from(hex("0102030405 9000")));
+
+	// A second wrong Le is returned to the caller
+	let mut script = Script::new(&[("00B000000A", "6C05"), ("00B0000005", "6C04")]);
+	assert_eq!(transmit(&mut script, "00B000000A"), ApduResponse::from(hex("6C04")));
+
+	// SW2 of zero is 256 bytes, which may be followed by GET RESPONSE
+	let mut script = Script::new(&[("00B0000001", "6C00"), ("00B0000000", "01 6101"), ("00C0000001", "02 9000")]);
+	assert_eq!(transmit(&mut script, "00B0000001"), ApduResponse::from(hex("0102 9000")));
+}
+
+#[test]
+fn wrong_le_secure_messaging() {
+	// The IC has incremented its SSC for the protected command, so it is not re-issued
+	let mut script = Script::new(&[("0CB000000D 9701048E08ED6705417E96BA55 00", "6C08")]);
+	assert_eq!(transmit(&mut script, "0CB000000D 9701048E08ED6705417E96BA55 00"), ApduResponse::from(hex("6C08")));
+}
+
+#[test]
+fn chaining() {
+	let mut script = Script::new(&[
+		("102A00BE04 01020304", "9000"),
+		("102A00BE04 05060708", "9000"),
+		("002A00BE01 09", "9000"),
+	]);
+	script.max_command_data = 4;
+	assert_eq!(transmit(&mut script, "002A00BE09 010203040506070809"), ApduResponse::from(hex("9000")));
+
+	// Response data of the last command of the chain
+	let mut script = Script::new(&[("1088000004 01020304", "9000"), ("0088000001 05 00", "0A0B 9000")]);
+	script.max_command_data = 4;
+	assert_eq!(transmit(&mut script, "0088000005 0102030405 00"), ApduResponse::from(hex("0A0B 9000")));
+}
+
+#[test]
+fn chaining_abort() {
+	// The IC refuses a part of the chain, after which the rest is not sent
+	let mut script = Script::new(&[("102A00BE04 01020304", "9000"), ("102A00BE04 05060708", "6883")]);
+	script.max_command_data = 4;
+	assert_eq!(transmit(&mut script, "002A00BE09 010203040506070809"), ApduResponse::from(hex("6883")));
+
+	let mut script = Script::new(&[("102A00BE04 01020304", "6A80")]);
+	script.max_command_data = 4;
+	assert_eq!(transmit(&mut script, "002A00BE09 010203040506070809"), ApduResponse::from(hex("6A80")));
+}
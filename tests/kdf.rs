@@ -0,0 +1,64 @@
+mod common;
+
+use common::hex;
+use mrtd1::crypto::{derive_key, kdf, KeyAlgorithm, SecretKey, COUNTER_ENC, COUNTER_MAC, COUNTER_PASSWORD};
+use mrtd1::mrz::borrowed::{Mrz, MrzData};
+
+const MRZ: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C<3UTO6908061F9406236ZE184226B<<<<<14";
+
+/// Shared secret K of ICAO 9303 Part 11, Appendix G.1
+const PACE_K: &str = "28768D20701247DAE81804C9E780EDE582A9996DB4A315020B2733197DB84925";
+/// Encoded MRZ password f(π) of ICAO 9303 Part 11, Appendix G.1
+const PACE_PASSWORD: &str = "7E2D2A41C74EA0B38CD36F863939BFA8E9032AAD";
+
+#[test]
+fn icao_d1_key_seed() {
+	// SHA-1 of the MRZ information L898902C<369080619406236
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	assert_eq!(&mrz.derive_password()[..], &hex("239AB9CB282DAF66231DC5A4DF6BFBAEDF477565")[..]);
+	assert_eq!(&mrz.derive_seed_key()[..], &hex("239AB9CB282DAF66231DC5A4DF6BFBAE")[..]);
+	assert_eq!(mrz.derive_key(0), SecretKey::from(hex("239AB9CB282DAF66231DC5A4DF6BFBAE")));
+}
+
+#[test]
+fn icao_d1_tdes_keys() {
+	let k_seed = hex("239AB9CB282DAF66231DC5A4DF6BFBAE");
+	let mrz = Mrz::try_from(MRZ).unwrap();
+
+	// Keydata AB94FCEDF2664EDF B9B291F85D7F77F2 with adjusted parity bits
+	let k_enc = kdf(&k_seed, COUNTER_ENC, KeyAlgorithm::Tdes);
+	assert_eq!(&k_enc[..], &hex("AB94FDECF2674FDF B9B391F85D7F76F2")[..]);
+	assert_eq!(k_enc.algorithm(), KeyAlgorithm::Tdes);
+	assert_eq!(derive_key(&k_seed, COUNTER_ENC), SecretKey::from(hex("AB94FDECF2674FDFB9B391F85D7F76F2")));
+	assert_eq!(mrz.derive_key(COUNTER_ENC), SecretKey::from(hex("AB94FDECF2674FDFB9B391F85D7F76F2")));
+
+	// Keydata 7862D9ECE03C1BCD 4D77089DCF131442 with adjusted parity bits
+	let k_mac = kdf(&k_seed, COUNTER_MAC, KeyAlgorithm::Tdes);
+	assert_eq!(&k_mac[..], &hex("7962D9ECE03D1ACD 4C76089DCE131543")[..]);
+	assert_eq!(mrz.derive_key(COUNTER_MAC), SecretKey::from(hex("7962D9ECE03D1ACD4C76089DCE131543")));
+
+	// Every byte of a 3DES key has odd parity
+	for byte in k_enc.iter().chain(k_mac.iter()) {
+		assert_eq!(byte.count_ones() % 2, 1, "{:02X}", byte);
+	}
+}
+
+#[test]
+fn icao_g1_aes_128_keys() {
+	assert_eq!(&kdf(&hex(PACE_PASSWORD), COUNTER_PASSWORD, KeyAlgorithm::Aes128)[..], &hex("89DED1B26624EC1E634C1989302849DD")[..]);
+	assert_eq!(&kdf(&hex(PACE_K), COUNTER_ENC, KeyAlgorithm::Aes128)[..], &hex("F5F0E35C0D7161EE6724EE513A0D9A7F")[..]);
+	assert_eq!(&kdf(&hex(PACE_K), COUNTER_MAC, KeyAlgorithm::Aes128)[..], &hex("FE251C7858B356B24514B3BD5F4297D1")[..]);
+}
+
+#[test]
+fn aes_256_keys() {
+	// SHA-256 of the inputs of Appendix G.1, for a cipher suite with AES-256
+	let k_pi = kdf(&hex(PACE_PASSWORD), COUNTER_PASSWORD, KeyAlgorithm::Aes256);
+	assert_eq!(&k_pi[..], &hex("D79A23C126202AC9051FEBFBC0E8A03B1C6645D85752B4B71408FA229AB6D56B")[..]);
+	assert_eq!(k_pi.algorithm(), KeyAlgorithm::Aes256);
+	assert_eq!(&kdf(&hex(PACE_K), COUNTER_ENC, KeyAlgorithm::Aes256)[..], &hex("8419651A9932A555FE20D96406746A82F750F4CCB3D6BE786D4630BCC681BF0E")[..]);
+	assert_eq!(&kdf(&hex(PACE_K), COUNTER_MAC, KeyAlgorithm::Aes256)[..], &hex("AA35FDB8D201BC2FD2BD98550C6FE549568C5E769BE67F04733673B7C910A59F")[..]);
+
+	// AES-192 uses the first 24 octets of the SHA-256 keydata
+	assert_eq!(&kdf(&hex(PACE_K), COUNTER_ENC, KeyAlgorithm::Aes192)[..], &hex("8419651A9932A555FE20D96406746A82F750F4CCB3D6BE78")[..]);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{hex, FixedRng};
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::{ApduResponse, TRAILER_OK};
+use mrtd1::auth::pace::{self, PACEALG_ECDH_GM_AES_CMAC_128, PACESDP_BRAINPOOLP256R1};
+use mrtd1::crypto::{kdf, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC};
+use mrtd1::mrz::borrowed::{Mrz, MrzData};
+use mrtd1::sm::Session;
+use mrtd1::transport::Transport;
+
+const MRZ: &str = "P<D<<MUSTERMANN<<ERIKA<<<<<<<<<<<<<<<<<<<<<<T220001293D<<6408125F1010318<<<<<<<<<<<<<<<6";
+
+/// Answers with the responses of the IC in order, and verifies the protected
+/// SELECT sent after the handshake with the session of the IC.
+struct Script {
+	responses: Vec<Vec<u8>>,
+	commands: Vec<Vec<u8>>,
+	session: Session,
+}
+
+impl Transport for Script {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		self.commands.push(command.to_vec()?);
+		if self.responses.is_empty() {
+			assert_eq!(self.session.unwrap_command(command)?.ins, 0xA4);
+			return self.session.wrap_response(0xA4, &ApduResponse { data: vec![], trailer: TRAILER_OK });
+		}
+		Ok(ApduResponse { data: self.responses.remove(0), trailer: TRAILER_OK })
+	}
+}
+
+#[test]
+fn ecdh_generic_mapping_g1() {
+	// ICAO 9303 Part 11, Appendix G.1 (PACE with ECDH Generic Mapping over BrainpoolP256r1)
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	assert_eq!(&mrz.derive_password()[..], hex("7E2D2A41C74EA0B38CD36F863939BFA8E9032AAD"));
+	let k = hex("28768D20701247DAE81804C9E780EDE582A9996DB4A315020B2733197DB84925");
+	let mut script = Script {
+		responses: vec![
+			vec![],
+			hex("7C12 801095A3A016522EE98D01E76CB6B98B42C3"),
+			hex("7C43 8241 04824FBA91C9CBE26BEF53A0EBE7342A3BF178CEA9F45DE0B70AA601651FBA3F5730D8C879AAA9C9F73991E61B58F4D52EB87A0A0C709A49DC63719363CCD13C54"),
+			hex("7C43 8441 049E880F842905B8B3181F7AF7CAA9F0EFB743847F44A306D2D28C1D9EC65DF6DB7764B22277A2EDDC3C265A9F018F9CB852E111B768B326904B59A0193776F094"),
+			hex("7C0A 86083ABB9674BCE93C08"),
+		],
+		commands: vec![],
+		session: Session::from_keys(kdf(&k, COUNTER_ENC, KeyAlgorithm::Aes128), kdf(&k, COUNTER_MAC, KeyAlgorithm::Aes128), 0),
+	};
+
+	// SK_Map,PCD and SK_PCD
+	let mut rng = FixedRng(hex("7F4EF07B9EA82FD78AD689B38D0BC78CF21F249D953BC46F4C6E19259C010F99 A73FB703AC1436A18E0CFA5ABB3F7BEC7A070E7A6788486BEE230C4A22762595"));
+	let pace = pace::handshake_with_rng(&mut script, &mrz, &PACEALG_ECDH_GM_AES_CMAC_128, &PACESDP_BRAINPOOLP256R1, &mut rng).unwrap();
+	assert!(!pace.chip_authenticated());
+	let session = pace.session;
+	assert_eq!(session.ks_enc(), hex("F5F0E35C0D7161EE6724EE513A0D9A7F"));
+	assert_eq!(session.ks_mac(), hex("FE251C7858B356B24514B3BD5F4297D1"));
+	assert_eq!(session.ssc(), 2);
+
+	assert_eq!(script.commands.len(), 6);
+	assert_eq!(script.commands[0], hex("0022C1A4 12 800A04007F0007020204020283010184010D"));
+	assert_eq!(script.commands[1], hex("10860000 02 7C00 00"));
+	assert_eq!(script.commands[2], hex("10860000 45 7C43 8141 047ACF3EFC982EC45565A4B155129EFBC74650DCBFA6362D896FC70262E0C2CC5E544552DCB6725218799115B55C9BAA6D9F6BC3A9618E70C25AF71777A9C4922D 00"));
+	assert_eq!(script.commands[3], hex("10860000 45 7C43 8341 042DB7A64C0355044EC9DF190514C625CBA2CEA48754887122F3A5EF0D5EDD301C3556F3B3B186DF10B857B58F6A7EB80F20BA5DC7BE1D43D9BF850149FBB36462 00"));
+	assert_eq!(script.commands[4], hex("00860000 0C 7C0A 8508C2B0BD78D94BA866 00"));
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use common::{dynamic_authentication_data, tlv};
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::{ApduResponse, ApduResponseTrailer, TRAILER_FILE_NOT_FOUND, TRAILER_OK};
+use mrtd1::auth::error::PaceError;
+use mrtd1::auth::pace::{self, PaceAlg, PACEALG_ECDH_CAM_AES_CMAC_128, PACESDP_BRAINPOOLP256R1};
+use mrtd1::crypto::ec::{Point, CURVE_BRAINPOOLP256R1};
+use mrtd1::crypto::{aes, kdf, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC, COUNTER_PASSWORD};
+use mrtd1::mrz::borrowed::{Mrz, MrzData};
+use mrtd1::sm::Session;
+use mrtd1::transport::Transport;
+use num_bigint::BigUint;
+use rand::RngCore;
+
+const MRZ: &str = "P<D<<MUSTERMANN<<ERIKA<<<<<<<<<<<<<<<<<<<<<<T220001293D<<6408125F1010318<<<<<<<<<<<<<<<6";
+const ID_PK_ECDH: &[u8] = &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x01, 0x02];
+
+fn random_scalar() -> BigUint {
+	CURVE_BRAINPOOLP256R1.random_scalar(&mut rand::thread_rng())
+}
+
+/// Encodes EF.CardSecurity as a ContentInfo with SignedData, whose SecurityInfos
+/// hold a PACE info and a ChipAuthenticationPublicKeyInfo for every public key.
+fn card_security(public_keys: &[Vec<u8>]) -> Vec<u8> {
+	let mut security_infos = tlv(&[0x30], &[tlv(&[0x06], PACEALG_ECDH_CAM_AES_CMAC_128.descriptor), tlv(&[0x02], &[0x02]), tlv(&[0x02], &[0x0D])].concat());
+	for (key_id, public_key) in public_keys.iter().enumerate() {
+		let algorithm = tlv(&[0x30], &tlv(&[0x06], &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01]));
+		let subject_public_key_info = tlv(&[0x30], &[algorithm, tlv(&[0x03], &[&[0x00], &public_key[..]].concat())].concat());
+		security_infos.extend(tlv(&[0x30], &[tlv(&[0x06], ID_PK_ECDH), subject_public_key_info, tlv(&[0x02], &[key_id as u8 + 1])].concat()));
+	}
+	let encap_content_info = tlv(&[0x30], &[tlv(&[0x06], &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x03, 0x02, 0x01]), tlv(&[0xA0], &tlv(&[0x04], &tlv(&[0x31], &security_infos)))].concat());
+	let digest_algorithms = tlv(&[0x31], &tlv(&[0x30], &tlv(&[0x06], &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01])));
+	let signed_data = tlv(&[0x30], &[tlv(&[0x02], &[0x03]), digest_algorithms, encap_content_info, tlv(&[0x31], &[])].concat());
+	tlv(&[0x30], &[tlv(&[0x06], &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02]), tlv(&[0xA0], &signed_data)].concat())
+}
+
+/// The IC side of PACE with ECDH Chip Authentication Mapping over BrainpoolP256r1.
+struct CamChip {
+	oid: Vec<u8>,
+	k_pi: Vec<u8>,
+	s: Vec<u8>,
+	sk_map: BigUint,
+	sk_ic: BigUint,
+	sk: BigUint,
+	generator: Option<Point>,
+	pk_ifd: Vec<u8>,
+	session: Option<Session>,
+	card_security: Vec<u8>,
+	/// Added to CAIC, so that it does not match the key pair of the IC
+	ca_ic_offset: u8,
+	/// Sent without secure messaging in response to the first READ BINARY
+	read_error: Option<ApduResponseTrailer>,
+	commands: usize,
+	reads: usize,
+	application_selected: bool,
+}
+
+impl CamChip {
+	fn new(alg: &PaceAlg) -> Self {
+		let curve = CURVE_BRAINPOOLP256R1;
+		let sk_ic = random_scalar();
+		let mut s = vec![0; 16];
+		rand::thread_rng().fill_bytes(&mut s);
+		Self {
+			oid: alg.descriptor.to_vec(),
+			k_pi: kdf(&Mrz::try_from(MRZ).unwrap().derive_password(), COUNTER_PASSWORD, alg.key_algorithm()).to_vec(),
+			s,
+			sk_map: random_scalar(),
+			card_security: card_security(&[curve.encode_point(&curve.mul(&sk_ic, &curve.generator()).unwrap())]),
+			sk_ic,
+			sk: random_scalar(),
+			generator: None,
+			pk_ifd: vec![],
+			session: None,
+			ca_ic_offset: 0,
+			read_error: None,
+			commands: 0,
+			reads: 0,
+			application_selected: false,
+		}
+	}
+
+	fn public_key(&self) -> Vec<u8> {
+		let curve = CURVE_BRAINPOOLP256R1;
+		curve.encode_point(&curve.mul(&self.sk_ic, &curve.generator()).unwrap())
+	}
+
+	fn other_public_key() -> Vec<u8> {
+		let curve = CURVE_BRAINPOOLP256R1;
+		curve.encode_point(&curve.mul(&random_scalar(), &curve.generator()).unwrap())
+	}
+
+	/// Encrypts the chip authentication data CAIC = SKIC^-1 * SKMap,IC mod n
+	/// to AIC = E(KSEnc, CAIC), with IV = E(KSEnc, -1).
+	fn chip_authentication_data(&self) -> Vec<u8> {
+		let n = CURVE_BRAINPOOLP256R1.order();
+		let ca_ic = (&self.sk_map * self.sk_ic.modpow(&(&n - 2u8), &n) + self.ca_ic_offset) % &n;
+		let mut data = CURVE_BRAINPOOLP256R1.encode_field_element(&ca_ic);
+		data.push(0x80);
+		data.resize(data.len().next_multiple_of(16), 0x00);
+		let ks_enc = self.session.as_ref().unwrap().ks_enc();
+		let iv = aes::encrypt(&[0xFF; 16], ks_enc, &aes::ZERO_IV).unwrap();
+		aes::encrypt(&data, ks_enc, &iv).unwrap()
+	}
+}
+
+impl Transport for CamChip {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		let curve = CURVE_BRAINPOOLP256R1;
+		let ok = |data| Ok(ApduResponse { data, trailer: TRAILER_OK });
+		self.commands += 1;
+		match self.commands {
+			1 => ok(vec![]),
+			2 => ok(tlv(&[0x7C], &tlv(&[0x80], &aes::encrypt(&self.s, &self.k_pi, &aes::ZERO_IV).unwrap()))),
+			3 => {
+				let pk_map_ifd = curve.decode_point(&dynamic_authentication_data(&command.data, 0x81)).unwrap();
+				let h = curve.mul(&self.sk_map, &pk_map_ifd).unwrap();
+				self.generator = curve.add(&curve.mul(&BigUint::from_bytes_be(&self.s), &curve.generator()).unwrap(), &h);
+				ok(tlv(&[0x7C], &tlv(&[0x82], &curve.encode_point(&curve.mul(&self.sk_map, &curve.generator()).unwrap()))))
+			}
+			4 => {
+				self.pk_ifd = dynamic_authentication_data(&command.data, 0x83);
+				let pk_ifd = curve.decode_point(&self.pk_ifd).unwrap();
+				let k = curve.encode_field_element(&curve.mul(&self.sk, &pk_ifd).unwrap().x);
+				self.session = Some(Session::from_keys(kdf(&k, COUNTER_ENC, KeyAlgorithm::Aes128), kdf(&k, COUNTER_MAC, KeyAlgorithm::Aes128), 0));
+				ok(tlv(&[0x7C], &tlv(&[0x84], &curve.encode_point(&curve.mul(&self.sk, self.generator.as_ref().unwrap()).unwrap()))))
+			}
+			5 => {
+				let ks_mac = self.session.as_ref().unwrap().ks_mac();
+				let t_ic = aes::mac(&tlv(&[0x7F, 0x49], &[tlv(&[0x06], &self.oid), tlv(&[0x86], &self.pk_ifd)].concat()), ks_mac).unwrap();
+				ok(tlv(&[0x7C], &[tlv(&[0x86], &t_ic), tlv(&[0x8A], &self.chip_authentication_data())].concat()))
+			}
+			_ => {
+				let session = self.session.as_mut().unwrap();
+				let command = session.unwrap_command(command)?;
+				let res = match command.ins {
+					0xB0 => {
+						assert!(!self.application_selected, "EF.CardSecurity is read from the master file");
+						self.reads += 1;
+						if let Some(trailer) = self.read_error.take() {
+							return Ok(ApduResponse { data: vec![], trailer });
+						}
+						let offset = if command.p1 & 0x80 != 0 {
+							assert_eq!(command.p1, 0x9D);
+							command.p2 as usize
+						} else {
+							u16::from_be_bytes([command.p1, command.p2]) as usize
+						};
+						let end = self.card_security.len().min(offset + command.rx_len);
+						ApduResponse { data: self.card_security[offset..end].to_vec(), trailer: TRAILER_OK }
+					}
+					0xA4 => {
+						self.application_selected = true;
+						ApduResponse { data: vec![], trailer: TRAILER_OK }
+					}
+					ins => panic!("unexpected INS {:02X}", ins),
+				};
+				session.wrap_response(command.ins, &res)
+			}
+		}
+	}
+}
+
+#[test]
+fn chip_authenticated() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let alg = PACEALG_ECDH_CAM_AES_CMAC_128;
+	let mut chip = CamChip::new(&alg);
+	let pace = pace::handshake(&mut chip, &mrz, &alg, &PACESDP_BRAINPOOLP256R1).unwrap();
+	assert!(pace.chip_authenticated());
+	assert_eq!(pace.chip_authentication_public_key, Some(chip.public_key()));
+	assert_eq!(Some(&pace.session), chip.session.as_ref());
+	assert!(chip.application_selected);
+
+	// The matching key is found among the keys of EF.CardSecurity, of which invalid points are skipped
+	let mut chip = CamChip::new(&alg);
+	chip.card_security = card_security(&[vec![0x04; 3], CamChip::other_public_key(), chip.public_key()]);
+	let pace = pace::handshake(&mut chip, &mrz, &alg, &PACESDP_BRAINPOOLP256R1).unwrap();
+	assert_eq!(pace.chip_authentication_public_key, Some(chip.public_key()));
+}
+
+#[test]
+fn chip_authentication_failed() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let alg = PACEALG_ECDH_CAM_AES_CMAC_128;
+
+	// EF.CardSecurity holds the public key of another IC
+	let mut chip = CamChip::new(&alg);
+	chip.card_security = card_security(&[CamChip::other_public_key()]);
+	let err = pace::handshake(&mut chip, &mrz, &alg, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, mrtd1::Error::Pace(PaceError::ChipAuthenticationFailed)), "{:?}", err);
+	assert!(!chip.application_selected);
+
+	// The chip authentication data does not match the key pair of the IC
+	let mut chip = CamChip::new(&alg);
+	chip.ca_ic_offset = 1;
+	let err = pace::handshake(&mut chip, &mrz, &alg, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, mrtd1::Error::Pace(PaceError::ChipAuthenticationFailed)), "{:?}", err);
+
+	// EF.CardSecurity without a Chip Authentication public key
+	let mut chip = CamChip::new(&alg);
+	chip.card_security = card_security(&[]);
+	let err = pace::handshake(&mut chip, &mrz, &alg, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, mrtd1::Error::Pace(PaceError::InvalidCardSecurity)), "{:?}", err);
+}
+
+#[test]
+fn read_card_security_unprotected_error() {
+	// The SSC was incremented for the protected READ BINARY, so the handshake
+	// ends without falling back to SELECT
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let alg = PACEALG_ECDH_CAM_AES_CMAC_128;
+	let mut chip = CamChip::new(&alg);
+	chip.read_error = Some(TRAILER_FILE_NOT_FOUND);
+	let err = pace::handshake(&mut chip, &mrz, &alg, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert_eq!(err.trailer(), Some(TRAILER_FILE_NOT_FOUND));
+	assert_eq!(chip.commands, 6);
+	assert_eq!(chip.reads, 1);
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use common::{dynamic_authentication_data, tlv};
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::{ApduResponse, TRAILER_OK};
+use mrtd1::auth::error::PaceError;
+use mrtd1::auth::pace::{self, *};
+use mrtd1::crypto::{aes, des, kdf, tdes, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC, COUNTER_PASSWORD};
+use mrtd1::mrz::borrowed::{Mrz, MrzData};
+use mrtd1::sm::Session;
+use mrtd1::transport::Transport;
+use num_bigint::BigUint;
+use rand::RngCore;
+
+const MRZ: &str = "P<D<<MUSTERMANN<<ERIKA<<<<<<<<<<<<<<<<<<<<<<T220001293D<<6408125F1010318<<<<<<<<<<<<<<<6";
+
+/// The IC side of PACE with DH Generic Mapping.
+struct DhChip {
+	p: BigUint,
+	g: BigUint,
+	q: BigUint,
+	element_len: usize,
+	algorithm: KeyAlgorithm,
+	oid: Vec<u8>,
+	k_pi: Vec<u8>,
+	s: Vec<u8>,
+	mapped_generator: BigUint,
+	sk_map: BigUint,
+	sk: BigUint,
+	pk_ifd: Vec<u8>,
+	pk_ic: Vec<u8>,
+	session: Option<Session>,
+	corrupt_token: bool,
+	max_command_data: usize,
+	commands: Vec<ApduCommand<'static>>,
+}
+
+impl DhChip {
+	fn new(alg: &PaceAlg, sdp: &PaceSdp, max_command_data: usize) -> Self {
+		let group = sdp.group().unwrap();
+		let mrz = Mrz::try_from(MRZ).unwrap();
+		let algorithm = alg.key_algorithm();
+		let random = |len: usize, modulus: &BigUint| {
+			let mut bytes = vec![0; len];
+			rand::thread_rng().fill_bytes(&mut bytes);
+			BigUint::from_bytes_be(&bytes) % modulus
+		};
+		let q = group.order();
+		let mut s = vec![0; if algorithm == KeyAlgorithm::Tdes { 8 } else { 16 }];
+		rand::thread_rng().fill_bytes(&mut s);
+		Self {
+			p: group.prime(),
+			g: group.generator(),
+			element_len: group.element_len(),
+			algorithm,
+			oid: alg.descriptor.to_vec(),
+			k_pi: kdf(&mrz.derive_password(), COUNTER_PASSWORD, algorithm).to_vec(),
+			s,
+			mapped_generator: BigUint::from(0u8),
+			sk_map: random(40, &q),
+			sk: random(40, &q),
+			q,
+			pk_ifd: vec![],
+			pk_ic: vec![],
+			session: None,
+			corrupt_token: false,
+			max_command_data,
+			commands: vec![],
+		}
+	}
+
+	fn encode(&self, element: &BigUint) -> Vec<u8> {
+		let bytes = element.to_bytes_be();
+		let mut encoded = vec![0; self.element_len - bytes.len()];
+		encoded.extend(bytes);
+		encoded
+	}
+
+	fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+		match self.algorithm {
+			KeyAlgorithm::Tdes => tdes::encrypt(data, &self.k_pi).unwrap(),
+			_ => aes::encrypt(data, &self.k_pi, &aes::ZERO_IV).unwrap(),
+		}
+	}
+
+	fn authentication_token(&self, public_key: &[u8]) -> Vec<u8> {
+		let ks_mac = self.session.as_ref().unwrap().ks_mac();
+		let input = tlv(&[0x7F, 0x49], &[tlv(&[0x06], &self.oid), tlv(&[0x84], public_key)].concat());
+		match self.algorithm {
+			KeyAlgorithm::Tdes => des::mac(&input, ks_mac).unwrap(),
+			_ => aes::mac(&input, ks_mac).unwrap(),
+		}
+	}
+}
+
+impl Transport for DhChip {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		self.commands.push(command.clone().into_owned());
+		let ok = |data| Ok(ApduResponse { data, trailer: TRAILER_OK });
+		match self.commands.len() {
+			1 => {
+				assert_eq!(command.ins, 0x22);
+				ok(vec![])
+			}
+			2 => ok(tlv(&[0x7C], &tlv(&[0x80], &self.encrypt(&self.s)))),
+			3 => {
+				// Map the nonce with the shared secret H of the mapping keys
+				let pk_map_ifd = BigUint::from_bytes_be(&dynamic_authentication_data(&command.data, 0x81));
+				let h = pk_map_ifd.modpow(&self.sk_map, &self.p);
+				self.mapped_generator = self.g.modpow(&BigUint::from_bytes_be(&self.s), &self.p) * h % &self.p;
+				ok(tlv(&[0x7C], &tlv(&[0x82], &self.encode(&self.g.modpow(&self.sk_map, &self.p)))))
+			}
+			4 => {
+				self.pk_ifd = dynamic_authentication_data(&command.data, 0x83);
+				assert_eq!(self.pk_ifd.len(), self.element_len);
+				let pk_ifd = BigUint::from_bytes_be(&self.pk_ifd);
+				assert_eq!(pk_ifd.modpow(&self.q, &self.p), BigUint::from(1u8));
+				self.pk_ic = self.encode(&self.mapped_generator.modpow(&self.sk, &self.p));
+				let k = self.encode(&pk_ifd.modpow(&self.sk, &self.p));
+				self.session = Some(Session::from_keys(kdf(&k, COUNTER_ENC, self.algorithm), kdf(&k, COUNTER_MAC, self.algorithm), 0));
+				ok(tlv(&[0x7C], &tlv(&[0x84], &self.pk_ic)))
+			}
+			5 => {
+				assert_eq!(dynamic_authentication_data(&command.data, 0x85), self.authentication_token(&self.pk_ic));
+				let mut t_ic = self.authentication_token(&self.pk_ifd);
+				if self.corrupt_token {
+					t_ic[0] ^= 0x01;
+				}
+				ok(tlv(&[0x7C], &tlv(&[0x86], &t_ic)))
+			}
+			_ => {
+				let session = self.session.as_mut().unwrap();
+				assert_eq!(session.unwrap_command(command)?.ins, 0xA4);
+				session.wrap_response(0xA4, &ApduResponse { data: vec![], trailer: TRAILER_OK })
+			}
+		}
+	}
+
+	fn max_command_data(&self) -> usize {
+		self.max_command_data
+	}
+}
+
+#[test]
+fn dh_generic_mapping() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	for (alg, sdp) in [
+		(PACEALG_DH_GM_3DES_CBC_CBC, PACESDP_DH_GROUP22),
+		(PACEALG_DH_GM_AES_CMAC_128, PACESDP_DH_GROUP22),
+		(PACEALG_DH_GM_AES_CMAC_192, PACESDP_DH_GROUP23),
+		(PACEALG_DH_GM_AES_CMAC_256, PACESDP_DH_GROUP24),
+	] {
+		let mut chip = DhChip::new(&alg, &sdp, 65535);
+		let session = pace::handshake(&mut chip, &mrz, &alg, &sdp).unwrap().session;
+		assert_eq!(Some(&session), chip.session.as_ref(), "{}", alg.name);
+
+		let mut chip = DhChip::new(&alg, &sdp, 65535);
+		chip.corrupt_token = true;
+		let err = pace::handshake(&mut chip, &mrz, &alg, &sdp).unwrap_err();
+		assert!(matches!(err, mrtd1::Error::Pace(PaceError::InvalidAuthenticationToken)), "{}", alg.name);
+	}
+}
+
+#[test]
+fn dh_2048_general_authenticate() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let (alg, sdp) = (PACEALG_DH_GM_AES_CMAC_128, PACESDP_DH_GROUP24);
+
+	// The chained GENERAL AUTHENTICATE commands are sent as they are, even by
+	// a transport which chains commands beyond short length
+	for max_command_data in [65535, 255] {
+		let mut chip = DhChip::new(&alg, &sdp, max_command_data);
+		let session = pace::handshake(&mut chip, &mrz, &alg, &sdp).unwrap().session;
+		assert_eq!(Some(&session), chip.session.as_ref());
+		assert_eq!(chip.commands.iter().map(|command| (command.cla, command.ins)).collect::<Vec<_>>(), vec![
+			(0x00, 0x22),
+			(0x10, 0x86),
+			(0x10, 0x86),
+			(0x10, 0x86),
+			(0x00, 0x86),
+			(0x0C, 0xA4),
+		]);
+
+		// The public keys of 256 bytes require extended length
+		for command in &chip.commands[2..4] {
+			assert_eq!(command.data.len(), 4 + 4 + 256);
+			assert!(command.case().unwrap().is_extended());
+			assert_eq!(command.to_vec().unwrap()[4..7], [0x00, 0x01, 0x08]);
+		}
+	}
+}
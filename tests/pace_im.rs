@@ -0,0 +1,253 @@
+mod common;
+
+use common::{dynamic_authentication_data, hex, tlv, FixedRng};
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::{ApduResponse, TRAILER_OK};
+use mrtd1::auth::error::PaceError;
+use mrtd1::auth::pace::{self, *};
+use mrtd1::crypto::ec::{Point, CURVE_BRAINPOOLP256R1, CURVE_SECP224R1};
+use mrtd1::crypto::{aes, des, kdf, tdes, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC, COUNTER_PASSWORD};
+use mrtd1::mrz::borrowed::{Mrz, MrzData};
+use mrtd1::sm::Session;
+use mrtd1::transport::Transport;
+use num_bigint::BigUint;
+use rand::RngCore;
+
+const MRZ: &str = "P<D<<MUSTERMANN<<ERIKA<<<<<<<<<<<<<<<<<<<<<<T220001293D<<6408125F1010318<<<<<<<<<<<<<<<6";
+
+// ICAO 9303 Part 11, Appendix G.3 (PACE with ECDH Integrated Mapping over BrainpoolP256r1)
+const G3_S: &str = "2923BE84E16CD6AE529049F1F1BBE9EB";
+const G3_T: &str = "5DD4CBFC96F5453B130D890A1CDBAE32";
+const G3_R: &str = "A2F8FF2DF50E52C6599F386ADCB595D229F6A167ADE2BE5F2C3296ADD5B7430E";
+const G3_GENERATOR_X: &str = "8E82D31559ED0FDE92A4D0498ADD3C23BABA94FB77691E31E90AEA77FB17D427";
+const G3_GENERATOR_Y: &str = "4C1AE14BD0C3DBAC0C871B7F3608169364437CA30AC243A089D3F266C1E60FAD";
+
+fn number(value: &str) -> BigUint {
+	BigUint::from_bytes_be(&hex(value))
+}
+
+fn random(len: usize) -> Vec<u8> {
+	let mut bytes = vec![0; len];
+	rand::thread_rng().fill_bytes(&mut bytes);
+	bytes
+}
+
+fn encrypt(algorithm: KeyAlgorithm, key: &[u8], data: &[u8]) -> Vec<u8> {
+	match algorithm {
+		KeyAlgorithm::Tdes => tdes::encrypt(data, key).unwrap(),
+		_ => aes::encrypt(data, key, &aes::ZERO_IV).unwrap(),
+	}
+}
+
+/// Pseudo-random number mapping Rp(s, t), written independently of the library.
+fn pseudo_random_number(algorithm: KeyAlgorithm, s: &[u8], t: &[u8], p: &BigUint) -> BigUint {
+	let (c0, c1) = match algorithm {
+		KeyAlgorithm::Tdes | KeyAlgorithm::Aes128 => (hex("A668892A7C41E3CA739F40B057D85904"), hex("A4E136AC725F738B01C1F60217C188AD")),
+		_ => (
+			hex("D463D65234124EF7897054986DCA0A174E28DF758CBAA03F240616414D5A1676"),
+			hex("54BD7255F0AAF831BEC3423FCF39D69B6CBF066677D0FAAE5AADD99DF8E53517"),
+		),
+	};
+	let mut key = encrypt(algorithm, t, s);
+	key.truncate(algorithm.key_len());
+	let mut x = vec![];
+	while x.len() * 8 < p.bits() as usize + 64 {
+		x.extend(encrypt(algorithm, &key, &c1));
+		key = encrypt(algorithm, &key, &c0);
+		key.truncate(algorithm.key_len());
+	}
+	BigUint::from_bytes_be(&x) % p
+}
+
+enum Mapped {
+	/// The generator is derived from s and t once t is received
+	Dh { p: BigUint, q: BigUint, element_len: usize, generator: BigUint },
+	Ec(Point),
+}
+
+/// The IC side of PACE with Integrated Mapping.
+struct ImChip {
+	mapped: Mapped,
+	algorithm: KeyAlgorithm,
+	oid: Vec<u8>,
+	k_pi: Vec<u8>,
+	s: Vec<u8>,
+	t: Vec<u8>,
+	sk: BigUint,
+	pk_ifd: Vec<u8>,
+	pk_ic: Vec<u8>,
+	session: Option<Session>,
+	commands: usize,
+}
+
+impl ImChip {
+	fn new(alg: &PaceAlg, mapped: Mapped, s: Vec<u8>) -> Self {
+		let algorithm = alg.key_algorithm();
+		Self {
+			mapped,
+			algorithm,
+			oid: alg.descriptor.to_vec(),
+			k_pi: kdf(&Mrz::try_from(MRZ).unwrap().derive_password(), COUNTER_PASSWORD, algorithm).to_vec(),
+			s,
+			t: vec![],
+			sk: BigUint::from_bytes_be(&random(20)),
+			pk_ifd: vec![],
+			pk_ic: vec![],
+			session: None,
+			commands: 0,
+		}
+	}
+
+	fn dh(alg: &PaceAlg, sdp: &PaceSdp) -> Self {
+		let group = sdp.group().unwrap();
+		let mapped = Mapped::Dh { p: group.prime(), q: group.order(), element_len: group.element_len(), generator: BigUint::ZERO };
+		let s = random(if alg.key_algorithm().key_len() > 16 { 32 } else { 16 });
+		Self::new(alg, mapped, s)
+	}
+
+	/// Returns the public key of the IC and the shared secret.
+	fn agree(&self, pk_ifd: &[u8]) -> (Vec<u8>, Vec<u8>) {
+		match &self.mapped {
+			Mapped::Dh { p, q, element_len, generator } => {
+				let encode = |value: BigUint| {
+					let bytes = value.to_bytes_be();
+					[vec![0; element_len - bytes.len()], bytes].concat()
+				};
+				let pk_ifd = BigUint::from_bytes_be(pk_ifd);
+				assert_eq!(pk_ifd.modpow(q, p), BigUint::from(1u8));
+				(encode(generator.modpow(&self.sk, p)), encode(pk_ifd.modpow(&self.sk, p)))
+			}
+			Mapped::Ec(generator) => {
+				let curve = CURVE_BRAINPOOLP256R1;
+				let pk_ifd = curve.decode_point(pk_ifd).unwrap();
+				let pk_ic = curve.mul(&self.sk, generator).unwrap();
+				(curve.encode_point(&pk_ic), curve.encode_field_element(&curve.mul(&self.sk, &pk_ifd).unwrap().x))
+			}
+		}
+	}
+
+	fn authentication_token(&self, public_key: &[u8]) -> Vec<u8> {
+		let ks_mac = self.session.as_ref().unwrap().ks_mac();
+		let tag = if matches!(self.mapped, Mapped::Dh { .. }) { 0x84 } else { 0x86 };
+		let input = tlv(&[0x7F, 0x49], &[tlv(&[0x06], &self.oid), tlv(&[tag], public_key)].concat());
+		match self.algorithm {
+			KeyAlgorithm::Tdes => des::mac(&input, ks_mac).unwrap(),
+			_ => aes::mac(&input, ks_mac).unwrap(),
+		}
+	}
+}
+
+impl Transport for ImChip {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		self.commands += 1;
+		let ok = |data| Ok(ApduResponse { data, trailer: TRAILER_OK });
+		match self.commands {
+			1 => ok(vec![]),
+			2 => ok(tlv(&[0x7C], &tlv(&[0x80], &encrypt(self.algorithm, &self.k_pi, &self.s)))),
+			3 => {
+				// The terminal sends the nonce t, and the IC responds without mapping data
+				self.t = dynamic_authentication_data(&command.data, 0x81);
+				assert_eq!(self.t.len(), self.algorithm.key_len());
+				if let (Mapped::Dh { p, q, generator, .. }, 16 | 32) = (&mut self.mapped, self.s.len()) {
+					let r = pseudo_random_number(self.algorithm, &self.s, &self.t, p);
+					*generator = r.modpow(&((&*p - 1u8) / &*q), p);
+				}
+				ok(vec![0x7C, 0x00])
+			}
+			4 => {
+				self.pk_ifd = dynamic_authentication_data(&command.data, 0x83);
+				let (pk_ic, k) = self.agree(&self.pk_ifd);
+				self.pk_ic = pk_ic;
+				self.session = Some(Session::from_keys(kdf(&k, COUNTER_ENC, self.algorithm), kdf(&k, COUNTER_MAC, self.algorithm), 0));
+				ok(tlv(&[0x7C], &tlv(&[0x84], &self.pk_ic)))
+			}
+			5 => {
+				assert_eq!(dynamic_authentication_data(&command.data, 0x85), self.authentication_token(&self.pk_ic));
+				ok(tlv(&[0x7C], &tlv(&[0x86], &self.authentication_token(&self.pk_ifd))))
+			}
+			_ => {
+				let session = self.session.as_mut().unwrap();
+				assert_eq!(session.unwrap_command(command)?.ins, 0xA4);
+				session.wrap_response(0xA4, &ApduResponse { data: vec![], trailer: TRAILER_OK })
+			}
+		}
+	}
+
+	fn max_command_data(&self) -> usize {
+		65535
+	}
+}
+
+#[test]
+fn pseudo_random_number_g3() {
+	// Three blocks x1 || x2 || x3 for the 256-bit prime of BrainpoolP256r1
+	let curve = CURVE_BRAINPOOLP256R1;
+	let r = pseudo_random_number(KeyAlgorithm::Aes128, &hex(G3_S), &hex(G3_T), &curve.prime());
+	assert_eq!(r, number(G3_R));
+
+	let generator = curve.map_to_point(&r).unwrap();
+	assert_eq!(generator, Point { x: number(G3_GENERATOR_X), y: number(G3_GENERATOR_Y) });
+
+	// The point encoding requires p ≡ 3 mod 4
+	assert_eq!(CURVE_SECP224R1.map_to_point(&r), None);
+}
+
+#[test]
+fn ecdh_integrated_mapping_g3() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let curve = CURVE_BRAINPOOLP256R1;
+	let generator = Point { x: number(G3_GENERATOR_X), y: number(G3_GENERATOR_Y) };
+	let mut chip = ImChip::new(&PACEALG_ECDH_IM_AES_CMAC_128, Mapped::Ec(generator.clone()), hex(G3_S));
+
+	// The terminal chooses t of Appendix G.3, so its public key is on the mapped generator of G.3
+	// SK_IFD is below n, so that it is not sampled again
+	let mut sk_ifd = random(32);
+	sk_ifd[0] &= 0x7F;
+	let mut rng = FixedRng([hex(G3_T), sk_ifd.clone(), random(256)].concat());
+	let session = pace::handshake_with_rng(&mut chip, &mrz, &PACEALG_ECDH_IM_AES_CMAC_128, &PACESDP_BRAINPOOLP256R1, &mut rng).unwrap().session;
+	assert_eq!(Some(&session), chip.session.as_ref());
+	assert_eq!(chip.t, hex(G3_T));
+	let sk_ifd = BigUint::from_bytes_be(&sk_ifd);
+	assert_eq!(chip.pk_ifd, curve.encode_point(&curve.mul(&sk_ifd, &generator).unwrap()));
+
+	// Curves with p ≡ 1 mod 4 are rejected
+	let err = pace::handshake(&mut chip, &mrz, &PACEALG_ECDH_IM_AES_CMAC_128, &PACESDP_SECP224R1).unwrap_err();
+	assert!(matches!(err, mrtd1::Error::Pace(PaceError::UnsupportedParameters)));
+}
+
+#[test]
+fn ecdh_integrated_mapping() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let curve = CURVE_BRAINPOOLP256R1;
+	for alg in [PACEALG_ECDH_IM_3DES_CBC_CBC, PACEALG_ECDH_IM_AES_CMAC_192, PACEALG_ECDH_IM_AES_CMAC_256] {
+		let algorithm = alg.key_algorithm();
+		let s = random(if algorithm.key_len() > 16 { 32 } else { 16 });
+		let t = random(algorithm.key_len());
+		let generator = curve.map_to_point(&pseudo_random_number(algorithm, &s, &t, &curve.prime())).unwrap();
+		let mut chip = ImChip::new(&alg, Mapped::Ec(generator), s);
+		let mut rng = FixedRng([t, random(256)].concat());
+		let session = pace::handshake_with_rng(&mut chip, &mrz, &alg, &PACESDP_BRAINPOOLP256R1, &mut rng).unwrap().session;
+		assert_eq!(Some(&session), chip.session.as_ref(), "{}", alg.name);
+	}
+}
+
+#[test]
+fn dh_integrated_mapping() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	for (alg, sdp) in [
+		(PACEALG_DH_IM_3DES_CBC_CBC, PACESDP_DH_GROUP22),
+		(PACEALG_DH_IM_AES_CMAC_128, PACESDP_DH_GROUP22),
+		(PACEALG_DH_IM_AES_CMAC_192, PACESDP_DH_GROUP23),
+		(PACEALG_DH_IM_AES_CMAC_256, PACESDP_DH_GROUP24),
+	] {
+		let mut chip = ImChip::dh(&alg, &sdp);
+		let session = pace::handshake(&mut chip, &mrz, &alg, &sdp).unwrap().session;
+		assert_eq!(Some(&session), chip.session.as_ref(), "{}", alg.name);
+	}
+
+	// A nonce which is not of the length of the constants
+	let mut chip = ImChip::dh(&PACEALG_DH_IM_3DES_CBC_CBC, &PACESDP_DH_GROUP22);
+	chip.s.truncate(8);
+	let err = pace::handshake(&mut chip, &mrz, &PACEALG_DH_IM_3DES_CBC_CBC, &PACESDP_DH_GROUP22).unwrap_err();
+	assert!(matches!(err, mrtd1::Error::Pace(PaceError::InvalidResponse)));
+}
@@ -0,0 +1,273 @@
+mod common;
+
+use common::{dynamic_authentication_data, parse_tlv, tlv};
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::{ApduResponse, ApduResponseTrailer, TRAILER_OK};
+use mrtd1::auth::error::PaceError;
+use mrtd1::auth::pace::{self, PaceAlg, Password, PACEALG_ECDH_GM_AES_CMAC_128, PACESDP_BRAINPOOLP256R1, PASSWORD_CAN, PASSWORD_MRZ, PASSWORD_PIN, PASSWORD_PUK};
+use mrtd1::crypto::ec::{Point, CURVE_BRAINPOOLP256R1};
+use mrtd1::crypto::{aes, kdf, KeyAlgorithm, COUNTER_ENC, COUNTER_MAC, COUNTER_PASSWORD};
+use mrtd1::mrz::borrowed::{Mrz, MrzData};
+use mrtd1::sm::Session;
+use mrtd1::transport::Transport;
+use mrtd1::Error;
+use num_bigint::BigUint;
+use rand::RngCore;
+
+const MRZ: &str = "P<D<<MUSTERMANN<<ERIKA<<<<<<<<<<<<<<<<<<<<<<T220001293D<<6408125F1010318<<<<<<<<<<<<<<<6";
+const CAN: &str = "500540";
+const PIN: &str = "123456";
+const ALG: PaceAlg = PACEALG_ECDH_GM_AES_CMAC_128;
+
+fn random_scalar() -> BigUint {
+	CURVE_BRAINPOOLP256R1.random_scalar(&mut rand::thread_rng())
+}
+
+fn status(sw1: u8, sw2: u8) -> ApduResponse {
+	ApduResponse { data: vec![], trailer: ApduResponseTrailer { sw1, sw2 } }
+}
+
+/// An eID chip with the MRZ, the CAN and a PIN with a retry counter, which
+/// performs PACE with ECDH Generic Mapping over BrainpoolP256r1.
+struct PasswordChip {
+	mrz: Vec<u8>,
+	pin_retries: u8,
+	pin_deactivated: bool,
+	oid: Vec<u8>,
+	password_reference: u8,
+	s: Vec<u8>,
+	generator: Option<Point>,
+	pk_ifd: Vec<u8>,
+	pk_ic: Vec<u8>,
+	/// Session keys of the current run, until the IC is authenticated
+	pending: Option<Session>,
+	/// Session of a successful run, which secures the following commands
+	established: Option<Session>,
+	session: Option<Session>,
+	can_authenticated: bool,
+	/// INS of every command and whether it was sent with secure messaging
+	log: Vec<(u8, bool)>,
+	application_selected: bool,
+}
+
+impl PasswordChip {
+	fn new(pin_retries: u8) -> Self {
+		Self {
+			mrz: Mrz::try_from(MRZ).unwrap().derive_password().to_vec(),
+			pin_retries,
+			pin_deactivated: false,
+			oid: vec![],
+			password_reference: 0,
+			s: vec![],
+			generator: None,
+			pk_ifd: vec![],
+			pk_ic: vec![],
+			pending: None,
+			established: None,
+			session: None,
+			can_authenticated: false,
+			log: vec![],
+			application_selected: false,
+		}
+	}
+
+	fn log(&self) -> Vec<String> {
+		self.log.iter().map(|(ins, protected)| format!("{:02X}{}", ins, if *protected { "p" } else { "" })).collect()
+	}
+
+	fn authentication_token(&self, public_key: &[u8]) -> Vec<u8> {
+		let ks_mac = self.pending.as_ref().unwrap().ks_mac();
+		aes::mac(&tlv(&[0x7F, 0x49], &[tlv(&[0x06], &self.oid), tlv(&[0x86], public_key)].concat()), ks_mac).unwrap()
+	}
+
+	fn process(&mut self, command: &ApduCommand) -> ApduResponse {
+		let curve = CURVE_BRAINPOOLP256R1;
+		let ok = |data| ApduResponse { data, trailer: TRAILER_OK };
+		match (command.ins, command.data.get(2)) {
+			(0x22, _) => {
+				let mut data = &command.data[..];
+				while !data.is_empty() {
+					let (tag, value) = parse_tlv(data);
+					match tag {
+						0x80 => self.oid = value.clone(),
+						0x83 => self.password_reference = value[0],
+						_ => {}
+					}
+					data = &data[2 + value.len()..];
+				}
+				if self.password_reference == PASSWORD_PIN {
+					if self.pin_deactivated {
+						return status(0x62, 0x83);
+					}
+					match self.pin_retries {
+						0 => return status(0x63, 0xC0),
+						// The suspended PIN must be resumed with the CAN
+						1 if !self.can_authenticated => return status(0x63, 0xC1),
+						3 => {}
+						retries => return status(0x63, 0xC0 | retries),
+					}
+				}
+				ok(vec![])
+			}
+			(0x86, None) => {
+				self.s = vec![0; 16];
+				rand::thread_rng().fill_bytes(&mut self.s);
+				let password = match self.password_reference {
+					PASSWORD_MRZ => self.mrz.clone(),
+					PASSWORD_CAN => CAN.as_bytes().to_vec(),
+					_ => PIN.as_bytes().to_vec(),
+				};
+				let k_pi = kdf(&password, COUNTER_PASSWORD, KeyAlgorithm::Aes128);
+				ok(tlv(&[0x7C], &tlv(&[0x80], &aes::encrypt(&self.s, &k_pi, &aes::ZERO_IV).unwrap())))
+			}
+			(0x86, Some(0x81)) => {
+				let sk_map = random_scalar();
+				let pk_map_ifd = curve.decode_point(&dynamic_authentication_data(&command.data, 0x81)).unwrap();
+				let h = curve.mul(&sk_map, &pk_map_ifd).unwrap();
+				self.generator = curve.add(&curve.mul(&BigUint::from_bytes_be(&self.s), &curve.generator()).unwrap(), &h);
+				ok(tlv(&[0x7C], &tlv(&[0x82], &curve.encode_point(&curve.mul(&sk_map, &curve.generator()).unwrap()))))
+			}
+			(0x86, Some(0x83)) => {
+				let sk = random_scalar();
+				self.pk_ifd = dynamic_authentication_data(&command.data, 0x83);
+				let pk_ifd = curve.decode_point(&self.pk_ifd).unwrap();
+				let k = curve.encode_field_element(&curve.mul(&sk, &pk_ifd).unwrap().x);
+				self.pending = Some(Session::from_keys(kdf(&k, COUNTER_ENC, KeyAlgorithm::Aes128), kdf(&k, COUNTER_MAC, KeyAlgorithm::Aes128), 0));
+				self.pk_ic = curve.encode_point(&curve.mul(&sk, self.generator.as_ref().unwrap()).unwrap());
+				ok(tlv(&[0x7C], &tlv(&[0x84], &self.pk_ic)))
+			}
+			(0x86, Some(0x85)) => {
+				if dynamic_authentication_data(&command.data, 0x85) != self.authentication_token(&self.pk_ic) {
+					if self.password_reference == PASSWORD_PIN {
+						self.pin_retries -= 1;
+						return status(0x63, 0xC0 | self.pin_retries);
+					}
+					return status(0x63, 0x00);
+				}
+				match self.password_reference {
+					PASSWORD_PIN => self.pin_retries = 3,
+					PASSWORD_CAN => self.can_authenticated = true,
+					_ => {}
+				}
+				let t_ic = self.authentication_token(&self.pk_ifd);
+				self.established = self.pending.take();
+				ok(tlv(&[0x7C], &tlv(&[0x86], &t_ic)))
+			}
+			(0xA4, _) => {
+				self.application_selected = true;
+				ok(vec![])
+			}
+			_ => panic!("unexpected command {:?}", command),
+		}
+	}
+}
+
+impl Transport for PasswordChip {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		let protected = command.cla & 0x0C == 0x0C;
+		self.log.push((command.ins, protected));
+		if protected {
+			let mut session = self.session.take().expect("secure messaging is not established");
+			let command = session.unwrap_command(command)?;
+			let res = self.process(&command);
+			let res = session.wrap_response(command.ins, &res)?;
+			// A successful PACE run replaces the secure messaging channel
+			self.session = Some(self.established.take().unwrap_or(session));
+			Ok(res)
+		} else {
+			// A command without secure messaging ends it
+			let res = self.process(command);
+			self.session = self.established.take();
+			Ok(res)
+		}
+	}
+}
+
+#[test]
+fn passwords() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	assert_eq!(Password::from(&mrz), Password::mrz(&mrz));
+	assert_eq!(Password::from(&mrz).reference(), PASSWORD_MRZ);
+	assert_eq!(Password::can(CAN).reference(), PASSWORD_CAN);
+	assert_eq!(Password::pin(PIN).reference(), PASSWORD_PIN);
+	assert_eq!(Password::puk("1234567890").reference(), PASSWORD_PUK);
+	assert_eq!(Password::can(CAN).encoded(), CAN.as_bytes());
+	assert_eq!(Password::can(CAN).derive_key(KeyAlgorithm::Aes128), kdf(CAN.as_bytes(), COUNTER_PASSWORD, KeyAlgorithm::Aes128));
+	assert!(!format!("{:?}", Password::pin(PIN)).contains(PIN));
+
+	for password in [Password::from(&mrz), Password::can(CAN), Password::pin(PIN)] {
+		let mut chip = PasswordChip::new(3);
+		let pace = pace::handshake(&mut chip, password, &ALG, &PACESDP_BRAINPOOLP256R1).unwrap();
+		assert_eq!(Some(&pace.session), chip.session.as_ref());
+		assert!(chip.application_selected);
+	}
+}
+
+#[test]
+fn retry_counter() {
+	// Every wrong PIN decrements the retry counter, until the PIN is suspended
+	let mut chip = PasswordChip::new(3);
+	let err = pace::handshake(&mut chip, Password::pin("000000"), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::AuthenticationFailed { retries: Some(2) })), "{:?}", err);
+	let err = pace::handshake(&mut chip, Password::pin("000000"), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::AuthenticationFailed { retries: Some(1) })), "{:?}", err);
+
+	// 63C1 suspends the PIN without a preceding PACE with the CAN
+	let err = pace::handshake(&mut chip, Password::pin(PIN), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::PasswordSuspended)), "{:?}", err);
+	assert_eq!(chip.log().len(), 11);
+
+	// 63C2 is a warning, and the retry counter is reset by the correct PIN
+	let mut chip = PasswordChip::new(2);
+	pace::handshake(&mut chip, Password::pin(PIN), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap();
+	assert_eq!(chip.pin_retries, 3);
+
+	// A wrong PIN after the warning reports the remaining retries
+	let mut chip = PasswordChip::new(2);
+	let err = pace::handshake(&mut chip, Password::pin("000000"), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::AuthenticationFailed { retries: Some(1) })), "{:?}", err);
+
+	// 63C0 blocks the PIN
+	let mut chip = PasswordChip::new(0);
+	let err = pace::handshake(&mut chip, Password::pin(PIN), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::PasswordBlocked)), "{:?}", err);
+	assert_eq!(chip.log(), ["22"]);
+
+	// 6283 is sent for a deactivated PIN
+	let mut chip = PasswordChip::new(3);
+	chip.pin_deactivated = true;
+	let err = pace::handshake(&mut chip, Password::pin(PIN), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::PasswordDeactivated)), "{:?}", err);
+	assert_eq!(chip.log(), ["22"]);
+
+	// The CAN has no retry counter
+	let mut chip = PasswordChip::new(3);
+	let err = pace::handshake(&mut chip, Password::can("111111"), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::AuthenticationFailed { retries: None })), "{:?}", err);
+}
+
+#[test]
+fn resume() {
+	let mut chip = PasswordChip::new(1);
+	let pace = pace::resume(&mut chip, Password::can(CAN), Password::pin(PIN), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap();
+	assert_eq!(chip.pin_retries, 3);
+	assert_eq!(Some(&pace.session), chip.session.as_ref());
+	assert_eq!(pace.session.ssc(), 2);
+	assert!(chip.application_selected);
+	// PACE with the CAN without secure messaging, PACE with the PIN in the
+	// channel of the CAN, and SELECT in the channel of the PIN
+	assert_eq!(chip.log(), ["22", "86", "86", "86", "86", "22p", "86p", "86p", "86p", "86p", "A4p"]);
+
+	// A wrong PIN during resume reports the retry counter
+	let mut chip = PasswordChip::new(1);
+	let err = pace::resume(&mut chip, Password::can(CAN), Password::pin("000000"), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::AuthenticationFailed { retries: Some(0) })), "{:?}", err);
+	assert!(!chip.application_selected);
+
+	// A wrong CAN ends resume before PACE with the PIN
+	let mut chip = PasswordChip::new(1);
+	let err = pace::resume(&mut chip, Password::can("111111"), Password::pin(PIN), &ALG, &PACESDP_BRAINPOOLP256R1).unwrap_err();
+	assert!(matches!(err, Error::Pace(PaceError::AuthenticationFailed { retries: None })), "{:?}", err);
+	assert_eq!(chip.log().len(), 5);
+	assert_eq!(chip.pin_retries, 1);
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "pcsc")]
+
+use mrtd1::auth::bac;
+use mrtd1::files::{read_file, EF_COM, EF_DG1};
+use mrtd1::mrz::borrowed::Mrz;
+use mrtd1::transport::{self, Transport};
+use mrtd1::transport::iso7816::MAX_SHORT_COMMAND_DATA;
+
+/// The MRZ of ICAO 9303 Part 11, Appendix D
+const MRZ: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C<3UTO6908061F9406236ZE184226B<<<<<14";
+
+/// Reads a virtual eMRTD through vpcd, for example `vicc -t ePass`. The MRZ
+/// of the card can be set with `MRTD1_MRZ`, and defaults to Appendix D.
+#[test]
+#[ignore = "requires pcscd with vpcd and a virtual eMRTD"]
+fn virtual_card() {
+	let mrz = std::env::var("MRTD1_MRZ").unwrap_or_else(|_| MRZ.into());
+	let mrz = Mrz::try_from(mrz.as_str()).unwrap();
+	let mut card = transport::pcsc::connect(Some("Virtual PCD")).unwrap();
+	assert_eq!(card.max_command_data(), MAX_SHORT_COMMAND_DATA);
+
+	let mut session = bac::handshake(&mut card, &mrz).unwrap();
+	let com = read_file(&mut card, &mut session, &EF_COM).unwrap();
+	assert_eq!(com[0], 0x60);
+	let dg1 = read_file(&mut card, &mut session, &EF_DG1).unwrap();
+	assert_eq!(dg1[0], 0x61);
+}
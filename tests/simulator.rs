@@ -0,0 +1,150 @@
+mod common;
+
+use common::{hex, FixedRng};
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::ApduResponse;
+use mrtd1::auth::bac;
+use mrtd1::files::{read_file, read_file_by_sfi, EF_COM, EF_DG1, EF_DG2, EF_DG3};
+use mrtd1::mrz::borrowed::Mrz;
+use mrtd1::simulator::VirtualChip;
+use mrtd1::sm::{CipherSuite, SecureMessaging, Session};
+use mrtd1::transport::Transport;
+use rand::RngCore;
+
+/// The MRZ of ICAO 9303 Part 11, Appendix D
+const MRZ: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C<3UTO6908061F9406236ZE184226B<<<<<14";
+
+/// EF.COM of Appendix D.4
+const EF_COM_DATA: &str = "60145F0104303130365F36063034303030305C026175";
+
+/// Records the raw command and response APDUs exchanged with the chip.
+struct Recording<R: RngCore> {
+	chip: VirtualChip<R>,
+	exchanges: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<R: RngCore> Transport for Recording<R> {
+	fn transmit(&mut self, command: &ApduCommand) -> mrtd1::Result<ApduResponse> {
+		let response = Transport::transmit(&mut self.chip, command)?;
+		self.exchanges.push((command.to_vec().unwrap(), response.to_vec()));
+		Ok(response)
+	}
+}
+
+fn file(tag: u8, len: usize) -> Vec<u8> {
+	let mut data = match len {
+		0..=0xFFFF => vec![tag, 0x82, (len >> 8) as u8, len as u8],
+		_ => vec![tag, 0x83, (len >> 16) as u8, (len >> 8) as u8, len as u8],
+	};
+	data.extend((0..len).map(|i| (i * 7) as u8));
+	data
+}
+
+#[test]
+fn bac_icao_example() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+
+	// RND.IC and K.IC of the IC, and RND.IFD and K.IFD of the terminal, of Appendix D.3
+	let chip_rng = FixedRng(hex("4608F91988702212 0B4F80323EB3191CB04970CB4052790B"));
+	let mut rng = FixedRng(hex("781723860C06C226 0B795240CB7049B01C19B33E32804F0B"));
+	let chip = VirtualChip::with_rng(&mrz, chip_rng).with_file(EF_COM.fileid, hex(EF_COM_DATA));
+	let mut chip = Recording { chip, exchanges: vec![] };
+
+	let mut session = bac::handshake_with_rng(&mut chip, &mrz, &mut rng).unwrap();
+	assert_eq!(session, Session::new(CipherSuite::Tdes, hex("979EC13B1CBFE9DCD01AB0FED307EAE5"), hex("F1CB1F1FB5ADF208806B89DC579DC1F8"), 0x887022120C06C226));
+	assert_eq!(chip.exchanges[2], (
+		hex("0082000028 72C29C2371CC9BDB65B779B8E8D37B29ECC154AA56A8799FAE2F498F76ED92F2 5F1448EEA8AD90A7 28"),
+		hex("46B9342A41396CD7386BF5803104D7CEDC122B9132139BAF2EEDC94EE178534F 2F2D235D074D7449 9000"),
+	));
+
+	// Reading EF.COM is the exchange of Appendix D.4
+	chip.exchanges.clear();
+	assert_eq!(read_file(&mut chip, &mut session, &EF_COM).unwrap(), hex(EF_COM_DATA));
+	assert_eq!(chip.exchanges, vec![
+		(hex("0CA4020C158709016375432908C044F68E08BF8B92D635FF24F800"), hex("990290008E08FA855A5D4C50A8ED9000")),
+		(hex("0CB000000D9701048E08ED6705417E96BA5500"), hex("8709019FF0EC34F9922651990290008E08AD55CC17140B2DED9000")),
+		(hex("0CB000040D9701128E082EA28A70F3C7B53500"), hex("871901FB9235F4E4037F2327DCC8964F1F9B8C30F42C8E2FFF224A990290008E08C8B2787EAEA07D749000")),
+	]);
+	assert_eq!(session.ssc(), 0x887022120C06C22C);
+}
+
+#[test]
+fn bac_wrong_mrz() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let other = Mrz::try_from("P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C36UTO7408122F1204159ZE184226B<<<<<10").unwrap();
+	let mut chip = VirtualChip::new(&other);
+	let err = bac::handshake(&mut chip, &mrz).unwrap_err();
+	assert_eq!(err.trailer().map(|t| [t.sw1, t.sw2]), Some([0x63, 0x00]));
+}
+
+#[test]
+fn read_files() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let dg1 = file(0x61, 0x5D);
+	let dg2 = file(0x75, 0x9000);
+	let dg3 = file(0x63, 0x10000);
+	let mut chip = VirtualChip::new(&mrz)
+		.with_file(EF_COM.fileid, hex(EF_COM_DATA))
+		.with_file(EF_DG1.fileid, dg1.clone())
+		.with_file(EF_DG2.fileid, dg2.clone())
+		.with_file(EF_DG3.fileid, dg3.clone());
+	let mut session = bac::handshake(&mut chip, &mrz).unwrap();
+
+	// Files beyond offset 7FFF are read with odd INS B1
+	assert_eq!(read_file(&mut chip, &mut session, &EF_DG2).unwrap(), dg2);
+	assert_eq!(read_file_by_sfi(&mut chip, &mut session, &EF_DG1).unwrap(), dg1);
+	assert_eq!(read_file_by_sfi(&mut chip, &mut session, &EF_DG2).unwrap(), dg2);
+	assert_eq!(read_file(&mut chip, &mut session, &EF_COM).unwrap(), hex(EF_COM_DATA));
+
+	// Files of 64 KiB or more have a length of three bytes
+	assert_eq!(read_file(&mut chip, &mut session, &EF_DG3).unwrap(), dg3);
+	assert_eq!(read_file_by_sfi(&mut chip, &mut session, &EF_DG3).unwrap(), dg3);
+
+	// The session is aborted after the chip is reset
+	chip.reset();
+	assert!(read_file(&mut chip, &mut session, &EF_COM).is_err());
+}
+
+#[test]
+fn protected_round_trips() {
+	let commands = [
+		ApduCommand::new(0x00, 0xA4, 0x02, 0x0C).with_data(hex("011E")),
+		ApduCommand::new(0x00, 0xB0, 0x00, 0x00).with_rx_len(4),
+		ApduCommand::new(0x00, 0xB0, 0x00, 0x00).with_rx_len(256),
+		ApduCommand::new(0x00, 0xB1, 0x00, 0x00).with_data(hex("54028000")).with_rx_len(0x1000),
+		ApduCommand::new(0x00, 0xD6, 0x00, 0x00).with_data(vec![0xAA; 300]),
+		ApduCommand::new(0x00, 0x84, 0x00, 0x00).with_rx_len(8),
+	];
+	let responses = [hex("9000"), hex("60145F019000"), [vec![0x55; 300], hex("9000")].concat(), hex("6282")];
+	for (cipher, key_len) in [(CipherSuite::Tdes, 16), (CipherSuite::Aes, 16), (CipherSuite::Aes, 24), (CipherSuite::Aes, 32)] {
+		let ks_enc: Vec<u8> = (0..key_len as u8).collect();
+		let ks_mac: Vec<u8> = (0..key_len as u8).map(|b| b ^ 0x5A).collect();
+		let (mut terminal_ssc, mut chip_ssc) = (7, 7);
+		let mut chip = Session::new(cipher, ks_enc.clone(), ks_mac.clone(), 7);
+		for command in &commands {
+			// ApduCommand::to_protected and Session::unwrap_command
+			let protected = command.to_protected(cipher, &ks_enc, &ks_mac, &mut terminal_ssc).unwrap();
+			assert_eq!(protected.cla, 0x0C);
+			assert_eq!(chip.unwrap_command(&protected).unwrap(), command.clone());
+			assert_eq!(chip.ssc(), terminal_ssc);
+			assert_eq!(ApduCommand::from_protected(&protected, cipher, &ks_enc, &ks_mac, &mut chip_ssc).unwrap(), command.clone());
+
+			// Session::wrap_response and ApduResponse::from_protected, with
+			// DO'85' in response to odd INS and DO'87' otherwise
+			for response in &responses {
+				let response = ApduResponse::from(response.clone());
+				let protected = chip.wrap_response(command.ins, &response).unwrap();
+				if !response.data.is_empty() {
+					assert_eq!(protected.data[0], if command.ins == 0xB1 { 0x85 } else { 0x87 });
+				}
+				assert_eq!(ApduResponse::from_protected(protected.clone(), cipher, &ks_enc, &ks_mac, &mut terminal_ssc).unwrap(), response);
+				assert_eq!(response.to_protected(command.ins, cipher, &ks_enc, &ks_mac, &mut chip_ssc).unwrap(), protected);
+			}
+		}
+
+		// The terminal side of a session is the same as its parts
+		let mut terminal = Session::new(cipher, ks_enc.clone(), ks_mac.clone(), terminal_ssc);
+		let protected = terminal.wrap_command(&commands[0]).unwrap();
+		assert_eq!(chip.unwrap_command(&protected).unwrap(), commands[0]);
+	}
+}
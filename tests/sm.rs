@@ -0,0 +1,94 @@
+mod common;
+
+use common::hex;
+use mrtd1::apdu::command::ApduCommand;
+use mrtd1::apdu::response::owned::ApduResponse;
+use mrtd1::error::Error;
+use mrtd1::sm::error::Error as SmError;
+use mrtd1::sm::{CipherSuite, SecureMessaging, Session};
+
+const KS_ENC: &str = "979EC13B1CBFE9DCD01AB0FED307EAE5";
+const KS_MAC: &str = "F1CB1F1FB5ADF208806B89DC579DC1F8";
+
+/// Builds a protected response with a valid MAC around the given DO'87'.
+fn protected_response(cipher: CipherSuite, do87: &[u8], ssc: u64) -> ApduResponse {
+	let do99 = hex("99029000");
+	let mac = cipher.mac(&[cipher.ssc_bytes(ssc + 1), do87.to_vec(), do99.clone()].concat(), &hex(KS_MAC)).unwrap();
+	ApduResponse::from([do87.to_vec(), do99, vec![0x8E, 0x08], mac, vec![0x90, 0x00]].concat())
+}
+
+#[test]
+fn response_padding_indicator() {
+	for cipher in [CipherSuite::Tdes, CipherSuite::Aes] {
+		for do87 in [hex("8700"), hex("870902 0000000000000000")] {
+			let mut ssc = 0;
+			let res = protected_response(cipher, &do87, ssc);
+			let err = ApduResponse::from_protected(res, cipher, &hex(KS_ENC), &hex(KS_MAC), &mut ssc).unwrap_err();
+			assert!(matches!(err, Error::SecureMessaging(SmError::InvalidPaddingIndicator)), "{:?}", err);
+		}
+	}
+}
+
+#[test]
+fn response_mac_mismatch() {
+	let cipher = CipherSuite::Tdes;
+	let mut res = protected_response(cipher, &hex("870901 0000000000000000"), 0);
+	let n = res.data.len();
+	res.data[n - 1] ^= 0x01;
+	let mut ssc = 0;
+	let err = ApduResponse::from_protected(res, cipher, &hex(KS_ENC), &hex(KS_MAC), &mut ssc).unwrap_err();
+	assert!(matches!(err, Error::MacMismatch), "{:?}", err);
+}
+
+/// SELECT EF.COM and READ BINARY of its first four bytes, protected with the
+/// session keys of ICAO 9303 Part 11, Appendix G.1 and an SSC starting at zero
+const AES_128_EXCHANGES: [(&str, &str, &str, &str); 2] = [
+	("00A4020C02011E", "0CA4020C1D871101EE0E4724F4465C1BE9C2F73ABDD73A3D8E08835D1B54575C955F00", "990290008E08BEA7B381C494A0799000", "9000"),
+	("00B0000004", "0CB000000D9701048E08AA6BA54F44DF836400", "871101645C0B1F998A088278D07942BBE94B60990290008E0894A33C6AA2D8CCC89000", "60145F019000"),
+];
+
+/// The exchanges of [`AES_128_EXCHANGES`] with AES-256 session keys derived from K of Appendix G.1
+const AES_256_EXCHANGES: [(&str, &str, &str, &str); 2] = [
+	("00A4020C02011E", "0CA4020C1D87110159D158A79C5104D7D3BA711FCC7CD4418E084D36DEDFCA26D7C400", "990290008E08E2FF9E39494C262F9000", "9000"),
+	("00B0000004", "0CB000000D9701048E08B840684541565C6900", "871101F5B3BF9DE00CD0347982815DD0D75D5B990290008E08436CA0B21CCDFFDD9000", "60145F019000"),
+];
+
+fn check_exchanges(mut terminal: Session, exchanges: &[(&str, &str, &str, &str)]) {
+	let mut chip = terminal.clone();
+	for (command, protected_command, protected_response, response) in exchanges {
+		let command = ApduCommand::try_from(&hex(command)[..]).unwrap().into_owned();
+		let protected_command = ApduCommand::try_from(&hex(protected_command)[..]).unwrap().into_owned();
+		assert_eq!(terminal.wrap_command(&command).unwrap(), protected_command);
+		assert_eq!(chip.unwrap_command(&protected_command).unwrap(), command);
+		assert_eq!(chip.wrap_response(command.ins, &ApduResponse::from(hex(response))).unwrap(), ApduResponse::from(hex(protected_response)));
+		assert_eq!(terminal.unwrap_response(ApduResponse::from(hex(protected_response))).unwrap(), ApduResponse::from(hex(response)));
+	}
+	assert_eq!(terminal.ssc(), 2 * exchanges.len() as u64);
+	assert_eq!(terminal, chip);
+}
+
+#[test]
+fn aes_128_vectors() {
+	let session = Session::new(CipherSuite::Aes, hex("F5F0E35C0D7161EE6724EE513A0D9A7F"), hex("FE251C7858B356B24514B3BD5F4297D1"), 0);
+	check_exchanges(session, &AES_128_EXCHANGES);
+}
+
+#[test]
+fn aes_256_vectors() {
+	let ks_enc = hex("8419651A9932A555FE20D96406746A82F750F4CCB3D6BE786D4630BCC681BF0E");
+	let ks_mac = hex("AA35FDB8D201BC2FD2BD98550C6FE549568C5E769BE67F04733673B7C910A59F");
+	check_exchanges(Session::new(CipherSuite::Aes, ks_enc, ks_mac, 0), &AES_256_EXCHANGES);
+}
+
+#[test]
+fn ssc_encoding() {
+	assert_eq!(CipherSuite::Tdes.ssc_bytes(0x887022120C06C226), hex("887022120C06C226"));
+	assert_eq!(CipherSuite::Aes.ssc_bytes(1), hex("00000000000000000000000000000001"));
+
+	// The 8 byte SSC of 3DES wraps around
+	let mut terminal = Session::new(CipherSuite::Tdes, hex(KS_ENC), hex(KS_MAC), u64::MAX);
+	let mut chip = terminal.clone();
+	let command = ApduCommand::new(0x00, 0xB0, 0x00, 0x00).with_rx_len(4);
+	assert_eq!(chip.unwrap_command(&terminal.wrap_command(&command).unwrap()).unwrap(), command);
+	assert_eq!(terminal.ssc(), 0);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::hex;
+use mrtd1::apdu::commands::select_fid;
+use mrtd1::apdu::response::owned::{TRAILER_CONDITIONS_NOT_SATISFIED, TRAILER_WRONG_SM_OBJECTS};
+use mrtd1::auth::bac;
+use mrtd1::auth::error::BacError;
+use mrtd1::files::{read_file, EF_COM, EF_DG1};
+use mrtd1::mrz::borrowed::Mrz;
+use mrtd1::simulator::VirtualChip;
+use mrtd1::sm::{self, SecureMessaging};
+use mrtd1::trace::error::Error as TraceError;
+use mrtd1::trace::{DecodeError, Decoder, Recorder, Replay, Trace};
+use mrtd1::transport::Transport;
+use mrtd1::Error;
+use rand::RngCore;
+
+/// The MRZ of ICAO 9303 Part 11, Appendix D
+const MRZ: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<L898902C<3UTO6908061F9406236ZE184226B<<<<<14";
+
+/// EF.COM of Appendix D.4
+const EF_COM_DATA: &str = "60145F0104303130365F36063034303030305C026175";
+
+const EF_DG1_DATA: &str = "615B5F1F58";
+
+#[test]
+fn record_replay_decode() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let dg1 = [hex(EF_DG1_DATA), MRZ.as_bytes().to_vec()].concat();
+	let chip = VirtualChip::new(&mrz).with_file(EF_COM.fileid, hex(EF_COM_DATA)).with_file(EF_DG1.fileid, dg1.clone());
+
+	// Record BAC and reading two files
+	let mut recorder = Recorder::new(chip, Vec::new()).unwrap();
+	let mut session = bac::handshake(&mut recorder, &mrz).unwrap();
+	let bac_session = session.clone();
+	recorder.set_ssc(Some(session.ssc()));
+	assert_eq!(read_file(&mut recorder, &mut session, &EF_COM).unwrap(), hex(EF_COM_DATA));
+	assert_eq!(read_file(&mut recorder, &mut session, &EF_DG1).unwrap(), dg1);
+	let (_, writer) = recorder.into_inner();
+
+	// The text format round-trips
+	let trace = Trace::read(writer.as_slice()).unwrap();
+	let mut written = Vec::new();
+	trace.write(&mut written).unwrap();
+	assert_eq!(written, writer);
+
+	// Replay with the terminal nonces of the recording
+	let mut replay = Replay::new(trace.clone());
+	let mut rng = replay.bac_rng(&mrz).unwrap();
+	let mut replayed = bac::handshake_with_rng(&mut replay, &mrz, &mut rng).unwrap();
+	assert_eq!(replayed, bac_session);
+	assert_eq!(read_file(&mut replay, &mut replayed, &EF_COM).unwrap(), hex(EF_COM_DATA));
+	assert_eq!(read_file(&mut replay, &mut replayed, &EF_DG1).unwrap(), dg1);
+	assert!(replay.is_finished());
+	assert!(rng.try_fill_bytes(&mut [0]).is_err());
+
+	// A command that differs from the recording fails the replay
+	let mut replay = Replay::new(trace.clone());
+	assert!(bac::handshake(&mut replay, &mrz).is_err());
+
+	// Decode with the MRZ, which derives the session keys from the BAC exchanges
+	let decoded = Decoder::with_mrz(&mrz).decode_trace(&trace);
+	assert_eq!(decoded.len(), trace.exchanges.len());
+	for exchange in &decoded {
+		assert!(exchange.errors.is_empty(), "{}", exchange);
+	}
+	assert_eq!(decoded[1].label, "GET CHALLENGE length 8");
+	assert_eq!(decoded[3].label, "SELECT EF_COM (011E)");
+	assert!(decoded[3].protected);
+	let responses: Vec<u8> = decoded.iter()
+		.filter(|exchange| exchange.label.starts_with("READ BINARY"))
+		.flat_map(|exchange| exchange.response.as_ref().unwrap().data.clone())
+		.collect();
+	assert_eq!(responses, [hex(EF_COM_DATA), dg1].concat());
+
+	// Decode the protected exchanges with the session keys
+	let decoded = Decoder::with_session(bac_session).decode_trace(&Trace { exchanges: trace.exchanges[3..].to_vec() });
+	for exchange in &decoded {
+		assert!(exchange.protected);
+		assert!(exchange.errors.is_empty(), "{}", exchange);
+	}
+
+	// A tampered response fails the MAC verification
+	let mut tampered = trace.clone();
+	let response = &mut tampered.exchanges.last_mut().unwrap().response;
+	response[2] ^= 0x01;
+	let decoded = Decoder::with_mrz(&mrz).decode_trace(&tampered);
+	assert!(matches!(decoded.last().unwrap().errors[..], [DecodeError::Response(_)]), "{:?}", decoded.last().unwrap().errors);
+}
+
+#[test]
+fn record_unprotected_errors() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let chip = VirtualChip::new(&mrz).with_file(EF_COM.fileid, hex(EF_COM_DATA));
+	let mut recorder = Recorder::new(chip, Vec::new()).unwrap();
+	let mut session = bac::handshake(&mut recorder, &mrz).unwrap();
+	recorder.set_ssc(Some(session.ssc()));
+	read_file(&mut recorder, &mut session, &EF_COM).unwrap();
+	let ssc = session.ssc();
+
+	// A wrong checksum ends secure messaging with an unprotected 6988, which
+	// does not increment the SSC
+	let mut command = session.wrap_command(&select_fid(EF_COM.fileid)).unwrap();
+	*command.data.to_mut().last_mut().unwrap() ^= 0x01;
+	assert_eq!(recorder.transmit(&command).unwrap().trailer, TRAILER_WRONG_SM_OBJECTS);
+	assert_eq!(session.ssc(), ssc + 1);
+
+	let res = sm::transmit(&mut recorder, &select_fid(EF_COM.fileid), &mut session).unwrap();
+	assert_eq!(res.trailer, TRAILER_CONDITIONS_NOT_SATISFIED);
+	assert_eq!(session.ssc(), ssc + 2);
+
+	let (_, writer) = recorder.into_inner();
+	let trace = Trace::read(writer.as_slice()).unwrap();
+	let sscs: Vec<_> = trace.exchanges.iter().rev().take(3).map(|exchange| exchange.ssc).collect();
+	assert_eq!(sscs, [Some(ssc + 2), Some(ssc + 1), Some(ssc - 1)]);
+}
+
+#[test]
+fn decode_external_authenticate_errors() {
+	let mrz = Mrz::try_from(MRZ).unwrap();
+	let mut recorder = Recorder::new(VirtualChip::new(&mrz), Vec::new()).unwrap();
+	bac::handshake(&mut recorder, &mrz).unwrap();
+	let (_, writer) = recorder.into_inner();
+	let trace = Trace::read(writer.as_slice()).unwrap();
+	let decode = |trace: &Trace| Decoder::with_mrz(&mrz).decode_trace(trace).remove(2).errors;
+
+	// The lengths of the command and the response are checked separately
+	let mut short_command = trace.clone();
+	let command = &mut short_command.exchanges[2].command;
+	*command = [&[0x00, 0x82, 0x00, 0x00, 0x20][..], &command[5..37], &[0x28]].concat();
+	let errors = decode(&short_command);
+	assert!(matches!(errors[..], [DecodeError::Command(Error::Trace(TraceError::DataLength { expected: 40, actual: 32 }))]), "{:?}", errors);
+
+	let mut short_response = trace.clone();
+	short_response.exchanges[2].response.drain(..8);
+	let errors = decode(&short_response);
+	assert!(matches!(errors[..], [DecodeError::Response(Error::Trace(TraceError::DataLength { expected: 40, actual: 32 }))]), "{:?}", errors);
+
+	// So are their checksums
+	let mut command_mac = trace.clone();
+	command_mac.exchanges[2].command[5] ^= 0x01;
+	let errors = decode(&command_mac);
+	assert!(matches!(errors[..], [DecodeError::Command(Error::MacMismatch)]), "{:?}", errors);
+
+	let mut response_mac = trace.clone();
+	response_mac.exchanges[2].response[0] ^= 0x01;
+	let errors = decode(&response_mac);
+	assert!(matches!(errors[..], [DecodeError::Response(Error::Bac(BacError::InvalidCryptogramMac))]), "{:?}", errors);
+}